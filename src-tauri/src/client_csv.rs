@@ -0,0 +1,489 @@
+//! Reading clients from CSV files and writing them back out.
+
+use std::collections::HashMap;
+
+use csv::{Position, ReaderBuilder, StringRecord, Writer};
+
+use crate::{
+    documents::{format_date, format_money},
+    schema::{
+        client_import_schema::{ClientCsvMapping, ImportRowResult, ImportRowStatus},
+        client_schema::{Client, NewClient},
+        error::{AppResult, ErrorResponse, FieldError},
+    },
+    validation::{find_matches, normalize_email, normalize_phone, validate_client},
+};
+
+/// A parsed CSV file. Each row keeps its number, counted from 1 after the header.
+pub struct CsvTable {
+    pub headers: Vec<String>,
+    pub rows: Vec<(usize, StringRecord)>,
+}
+
+/// Column index of every mapped client field.
+pub struct ColumnIndexes {
+    username: Option<usize>,
+    email: Option<usize>,
+    phone: Option<usize>,
+    company_name: Option<usize>,
+    city: Option<usize>,
+    address: Option<usize>,
+    tax_number: Option<usize>,
+    credit_limit: Option<usize>,
+}
+
+fn csv_error(message: &str, code: u16, e: impl ToString) -> ErrorResponse {
+    logger::log_error(message, code, Some(&e.to_string()));
+    ErrorResponse::new(code, message, Some(e.to_string()))
+}
+
+/// Line a record starts on. The reader places records after empty lines at
+/// the first of those lines.
+fn record_line(contents: &[u8], position: &Position) -> u64 {
+    let skipped = contents
+        .get(position.byte() as usize..)
+        .unwrap_or_default()
+        .iter()
+        .take_while(|b| matches!(b, b'\r' | b'\n'))
+        .filter(|b| **b == b'\n')
+        .count();
+    position.line() + skipped as u64
+}
+
+pub fn read_table(contents: &[u8]) -> AppResult<CsvTable> {
+    let mut reader = ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(contents);
+
+    let headers = reader
+        .headers()
+        .map_err(|e| csv_error("Failed to read CSV header", 400, e))?
+        .iter()
+        .map(str::to_string)
+        .collect();
+    // Rows are numbered by line, so empty lines, which the reader drops, still count
+    let first_line = reader.position().line();
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(|e| csv_error("Failed to read CSV file", 400, e))?;
+        // Skip rows left empty, e.g. `,,,` at the end of spreadsheet exports
+        if record.iter().all(str::is_empty) {
+            continue;
+        }
+        let line = record
+            .position()
+            .map_or(first_line, |p| record_line(contents, p));
+        rows.push(((line - first_line) as usize + 1, record));
+    }
+    Ok(CsvTable { headers, rows })
+}
+
+fn header_key(header: &str) -> String {
+    header
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .collect::<String>()
+        .to_lowercase()
+}
+
+/// Resolves the column of every client field, falling back to a column named
+/// after the field. Fails when an explicitly mapped column does not exist or
+/// when no column holds the name or phone number.
+pub fn resolve_mapping(
+    headers: &[String],
+    mapping: &ClientCsvMapping,
+) -> AppResult<(ClientCsvMapping, ColumnIndexes)> {
+    let by_key: HashMap<String, usize> = headers
+        .iter()
+        .enumerate()
+        .map(|(i, h)| (header_key(h), i))
+        .collect();
+
+    let mut errors = Vec::new();
+    let mut resolve = |field: &str, column: &Option<String>| -> Option<usize> {
+        match column {
+            Some(column) => {
+                let index = headers.iter().position(|h| h == column);
+                if index.is_none() {
+                    errors.push(FieldError {
+                        field: field.to_string(),
+                        message: format!("Column \"{}\" not found in the file", column),
+                    });
+                }
+                index
+            }
+            None => by_key.get(&header_key(field)).copied(),
+        }
+    };
+    let indexes = ColumnIndexes {
+        username: resolve("username", &mapping.username),
+        email: resolve("email", &mapping.email),
+        phone: resolve("phone", &mapping.phone),
+        company_name: resolve("companyName", &mapping.company_name),
+        city: resolve("city", &mapping.city),
+        address: resolve("address", &mapping.address),
+        tax_number: resolve("taxNumber", &mapping.tax_number),
+        credit_limit: resolve("creditLimit", &mapping.credit_limit),
+    };
+
+    for (field, index) in [("username", indexes.username), ("phone", indexes.phone)] {
+        if index.is_none() && !errors.iter().any(|e| e.field == field) {
+            errors.push(FieldError {
+                field: field.to_string(),
+                message: "No column mapped to this field".to_string(),
+            });
+        }
+    }
+    if !errors.is_empty() {
+        logger::log_error("Invalid CSV column mapping", 400, None);
+        return Err(ErrorResponse::validation(
+            "Invalid CSV column mapping",
+            errors,
+        ));
+    }
+
+    let column = |index: Option<usize>| index.map(|i| headers[i].clone());
+    let resolved = ClientCsvMapping {
+        username: column(indexes.username),
+        email: column(indexes.email),
+        phone: column(indexes.phone),
+        company_name: column(indexes.company_name),
+        city: column(indexes.city),
+        address: column(indexes.address),
+        tax_number: column(indexes.tax_number),
+        credit_limit: column(indexes.credit_limit),
+    };
+    Ok((resolved, indexes))
+}
+
+fn parse_row(record: &StringRecord, columns: &ColumnIndexes) -> (NewClient, Vec<FieldError>) {
+    let text = |index: Option<usize>| {
+        index
+            .and_then(|i| record.get(i))
+            .unwrap_or_default()
+            .to_string()
+    };
+    let optional = |index: Option<usize>| Some(text(index)).filter(|t| !t.is_empty());
+
+    let mut errors = Vec::new();
+    let credit_limit = match optional(columns.credit_limit) {
+        Some(limit) => match limit.parse::<f64>() {
+            Ok(limit) => Some(limit),
+            Err(_) => {
+                errors.push(FieldError {
+                    field: "creditLimit".to_string(),
+                    message: format!("\"{}\" is not a number", limit),
+                });
+                None
+            }
+        },
+        None => None,
+    };
+
+    let client = NewClient {
+        id: None,
+        username: text(columns.username),
+        email: optional(columns.email),
+        phone: text(columns.phone),
+        company_name: text(columns.company_name),
+        city: text(columns.city),
+        address: text(columns.address),
+        contacts: Vec::new(),
+        addresses: Vec::new(),
+        tax_number: optional(columns.tax_number),
+        tags: Vec::new(),
+        group_id: None,
+        price_list_id: None,
+        credit_limit,
+    };
+    errors.extend(validate_client(&client));
+    (client, errors)
+}
+
+/// Validates every row and flags rows that match an existing client or an
+/// earlier row of the same file.
+pub fn check_rows(
+    table: &CsvTable,
+    columns: &ColumnIndexes,
+    existing: &[Client],
+) -> Vec<ImportRowResult> {
+    let mut seen_phones: HashMap<String, usize> = HashMap::new();
+    let mut seen_emails: HashMap<String, usize> = HashMap::new();
+
+    table
+        .rows
+        .iter()
+        .map(|(line, record)| {
+            let (client, errors) = parse_row(record, columns);
+            if !errors.is_empty() {
+                return ImportRowResult {
+                    row: *line,
+                    status: ImportRowStatus::Invalid,
+                    client: Some(client),
+                    errors,
+                    matches: Vec::new(),
+                    duplicate_of_row: None,
+                };
+            }
+
+            let phone = normalize_phone(&client.phone);
+            let email = client.email.as_deref().map(normalize_email);
+            let duplicate_of_row = seen_phones
+                .get(&phone)
+                .or_else(|| email.as_ref().and_then(|e| seen_emails.get(e)))
+                .copied();
+            seen_phones.entry(phone).or_insert(*line);
+            if let Some(email) = email {
+                seen_emails.entry(email).or_insert(*line);
+            }
+
+            let matches = find_matches(&client, existing);
+            let status = if matches.is_empty() && duplicate_of_row.is_none() {
+                ImportRowStatus::Valid
+            } else {
+                ImportRowStatus::Duplicate
+            };
+            ImportRowResult {
+                row: *line,
+                status,
+                client: Some(client),
+                errors,
+                matches,
+                duplicate_of_row,
+            }
+        })
+        .collect()
+}
+
+/// Writes the rejected rows as they appeared in the file, with the reasons
+/// in an extra `errors` column.
+pub fn build_error_report(table: &CsvTable, results: &[ImportRowResult]) -> AppResult<Vec<u8>> {
+    let reasons: HashMap<usize, String> = results
+        .iter()
+        .map(|result| {
+            let mut reasons: Vec<String> = result
+                .errors
+                .iter()
+                .map(|e| format!("{}: {}", e.field, e.message))
+                .collect();
+            if let Some(row) = result.duplicate_of_row {
+                reasons.push(format!("Duplicate of row {}", row));
+            }
+            for m in &result.matches {
+                reasons.push(format!("Matches client {} ({})", m.client_id, m.username));
+            }
+            (result.row, reasons.join("; "))
+        })
+        .collect();
+
+    let mut writer = Writer::from_writer(Vec::new());
+    let write_error = |e: csv::Error| csv_error("Failed to write import error report", 500, e);
+    let mut header = table.headers.clone();
+    header.extend(["row".to_string(), "errors".to_string()]);
+    writer.write_record(&header).map_err(write_error)?;
+    for (line, record) in &table.rows {
+        if let Some(reason) = reasons.get(line) {
+            let mut row: Vec<String> = record.iter().map(str::to_string).collect();
+            row.resize(table.headers.len(), String::new());
+            row.extend([line.to_string(), reason.clone()]);
+            writer.write_record(&row).map_err(write_error)?;
+        }
+    }
+    writer
+        .into_inner()
+        .map_err(|e| csv_error("Failed to write import error report", 500, e))
+}
+
+/// Writes clients with their financial fields. The contact columns use the
+/// names the importer maps by default.
+pub fn build_clients_csv(clients: &[Client]) -> AppResult<Vec<u8>> {
+    let mut writer = Writer::from_writer(Vec::new());
+    let write_error = |e: csv::Error| csv_error("Failed to write clients CSV", 500, e);
+    writer
+        .write_record([
+            "id",
+            "username",
+            "email",
+            "phone",
+            "companyName",
+            "city",
+            "address",
+            "taxNumber",
+            "status",
+            "creditLimit",
+            "totalOwed",
+            "totalPaid",
+            "outstandingBalance",
+            "invoiceCount",
+            "createdAt",
+        ])
+        .map_err(write_error)?;
+    for client in clients {
+        writer
+            .write_record([
+                client.id.map(|id| id.to_hex()).unwrap_or_default(),
+                client.username.clone(),
+                client.email.clone().unwrap_or_default(),
+                client.phone.clone(),
+                client.company_name.clone(),
+                client.city.clone(),
+                client.address.clone(),
+                client.tax_number.clone().unwrap_or_default(),
+                client.status.to_string(),
+                client.credit_limit.map(format_money).unwrap_or_default(),
+                format_money(client.total_owed),
+                format_money(client.total_paid),
+                format_money(client.outstanding_balance),
+                client.invoices.len().to_string(),
+                format_date(&client.created_at),
+            ])
+            .map_err(write_error)?;
+    }
+    writer
+        .into_inner()
+        .map_err(|e| csv_error("Failed to write clients CSV", 500, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        schema::client_import_schema::ImportRowStatus,
+        test_fixtures::{client, object_id},
+    };
+
+    const CLIENTS: &str = "\
+Full Name,E-mail,Phone,Company Name,City,Credit Limit
+Mona Adel,mona@nile-traders.example,+20 100 123 4567,Nile Traders,Cairo,5000
+,,,,,
+
+Omar Said,omar@delta.example,0122 333 4444,Delta Foods,Giza,
+Omar S.,OMAR@delta.example,0155 666 7777,,Giza,
+Laila Hassan,laila@example,0111 222 3333,,Tanta,lots
+";
+
+    fn resolved(contents: &str) -> (CsvTable, ColumnIndexes) {
+        let table = read_table(contents.as_bytes()).unwrap();
+        let mapping = ClientCsvMapping {
+            username: Some("Full Name".to_string()),
+            ..Default::default()
+        };
+        let (_, columns) = resolve_mapping(&table.headers, &mapping).unwrap();
+        (table, columns)
+    }
+
+    fn error_fields(error: ErrorResponse) -> Vec<String> {
+        error.fields.into_iter().map(|e| e.field).collect()
+    }
+
+    #[test]
+    fn numbers_rows_by_line_after_skipping_blank_ones() {
+        let table = read_table(CLIENTS.as_bytes()).unwrap();
+        assert_eq!(table.headers[0], "Full Name");
+        let rows: Vec<usize> = table.rows.iter().map(|(row, _)| *row).collect();
+        // Row 2 is only commas and row 3 is empty
+        assert_eq!(rows, [1, 4, 5, 6]);
+        assert_eq!(&table.rows[1].1[0], "Omar Said");
+    }
+
+    #[test]
+    fn maps_unmapped_fields_by_header_name() {
+        let table = read_table(CLIENTS.as_bytes()).unwrap();
+        let mapping = ClientCsvMapping {
+            username: Some("Full Name".to_string()),
+            ..Default::default()
+        };
+        let (resolved, columns) = resolve_mapping(&table.headers, &mapping).unwrap();
+
+        assert_eq!(resolved.username.as_deref(), Some("Full Name"));
+        // Matched ignoring case, spaces and punctuation
+        assert_eq!(resolved.email.as_deref(), Some("E-mail"));
+        assert_eq!(resolved.company_name.as_deref(), Some("Company Name"));
+        assert_eq!(resolved.credit_limit.as_deref(), Some("Credit Limit"));
+        assert_eq!(resolved.address, None);
+        assert_eq!(resolved.tax_number, None);
+        assert_eq!(columns.phone, Some(2));
+    }
+
+    #[test]
+    fn rejects_mapping_to_missing_column() {
+        let headers = vec!["Name".to_string(), "Mobile".to_string()];
+        let mapping = ClientCsvMapping {
+            username: Some("Name".to_string()),
+            phone: Some("Phone".to_string()),
+            ..Default::default()
+        };
+        let error = resolve_mapping(&headers, &mapping).err().unwrap();
+        assert_eq!(error.code, 400);
+        // Reported once, as a missing column rather than an unmapped field
+        assert_eq!(error_fields(error), ["phone"]);
+    }
+
+    #[test]
+    fn requires_name_and_phone_columns() {
+        let headers = vec!["Email".to_string(), "City".to_string()];
+        let error = resolve_mapping(&headers, &ClientCsvMapping::default())
+            .err()
+            .unwrap();
+        assert_eq!(error_fields(error), ["username", "phone"]);
+    }
+
+    #[test]
+    fn flags_invalid_rows_and_duplicates() {
+        let (table, columns) = resolved(CLIENTS);
+        let results = check_rows(&table, &columns, &[client()]);
+        assert_eq!(results.len(), 4);
+
+        // Matches the existing client on phone, email and company
+        assert_eq!(results[0].row, 1);
+        assert_eq!(results[0].status, ImportRowStatus::Duplicate);
+        assert_eq!(results[0].matches[0].client_id, object_id(1));
+        assert_eq!(results[0].duplicate_of_row, None);
+
+        assert_eq!(results[1].status, ImportRowStatus::Valid);
+        assert!(results[1].matches.is_empty());
+
+        // Same email as row 4 in a different case
+        assert_eq!(results[2].row, 5);
+        assert_eq!(results[2].status, ImportRowStatus::Duplicate);
+        assert_eq!(results[2].duplicate_of_row, Some(4));
+
+        assert_eq!(results[3].status, ImportRowStatus::Invalid);
+        let fields: Vec<&str> = results[3].errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(fields, ["creditLimit", "email"]);
+    }
+
+    #[test]
+    fn flags_duplicate_phone_in_file() {
+        let (table, columns) = resolved(
+            "Full Name,Phone\n\
+             Mona Adel,+20 100 123 4567\n\
+             Mona A.,0100 123 4567\n",
+        );
+        let results = check_rows(&table, &columns, &[]);
+        assert_eq!(results[0].status, ImportRowStatus::Valid);
+        assert_eq!(results[1].status, ImportRowStatus::Duplicate);
+        assert_eq!(results[1].duplicate_of_row, Some(1));
+    }
+
+    #[test]
+    fn error_report_keeps_rejected_rows() {
+        let (table, columns) = resolved(CLIENTS);
+        let rejected: Vec<ImportRowResult> = check_rows(&table, &columns, &[client()])
+            .into_iter()
+            .filter(|result| result.status != ImportRowStatus::Valid)
+            .collect();
+        let report = String::from_utf8(build_error_report(&table, &rejected).unwrap()).unwrap();
+        let lines: Vec<&str> = report.lines().collect();
+
+        assert_eq!(
+            lines[0],
+            "Full Name,E-mail,Phone,Company Name,City,Credit Limit,row,errors"
+        );
+        assert_eq!(lines.len(), 4);
+        assert!(lines[1].ends_with(",1,Matches client 010101010101010101010101 (Mona Adel)"));
+        assert!(lines[2].ends_with(",5,Duplicate of row 4"));
+        assert!(lines[3].starts_with("Laila Hassan,laila@example,0111 222 3333,,Tanta,lots,6,"));
+    }
+}
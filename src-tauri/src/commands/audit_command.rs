@@ -0,0 +1,66 @@
+use futures::TryStreamExt;
+use mongodb::{bson::doc, ClientSession};
+use tauri::State;
+use tokio::sync::Mutex;
+
+use crate::{
+    db::MongoDbState,
+    schema::{
+        audit_schema::AuditEntry,
+        collections::Collection,
+        error::{AppResult, ErrorResponse},
+    },
+    utils::parse_object_id,
+};
+
+/// Adds an entry to the audit trail, as part of the transaction making the
+/// change when there is one.
+pub async fn record_audit(
+    db: &MongoDbState,
+    session: Option<&mut ClientSession>,
+    entry: &AuditEntry,
+) -> AppResult<()> {
+    let collection = db.get_collection::<AuditEntry>(Collection::AuditLog);
+    let insert = collection.insert_one(entry);
+    match session {
+        Some(session) => insert.session(session).await,
+        None => insert.await,
+    }
+    .map_err(|e| {
+        logger::log_error("Failed to record audit entry", 500, Some(&e.to_string()));
+        ErrorResponse::new(500, "Failed to record audit entry", Some(e.to_string()))
+    })?;
+    Ok(())
+}
+
+// List the audit trail, newest first, optionally for a single document
+#[tauri::command]
+pub async fn list_audit_log(
+    entity_id: Option<String>,
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<Vec<AuditEntry>> {
+    let db = db.lock().await;
+    let collection = db.get_collection::<AuditEntry>(Collection::AuditLog);
+
+    let filter = match &entity_id {
+        Some(entity_id) => doc! { "entityId": parse_object_id(entity_id, "Entity")? },
+        None => doc! {},
+    };
+    let entries: Vec<AuditEntry> = collection
+        .find(filter)
+        .sort(doc! { "created_at": -1 })
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to fetch audit log", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to fetch audit log", Some(e.to_string()))
+        })?
+        .try_collect()
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to parse audit log", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to parse audit log", Some(e.to_string()))
+        })?;
+
+    logger::log_info(&format!("List {} audit entries", entries.len()), 200, None);
+    Ok(entries)
+}
@@ -1,44 +1,145 @@
 use crate::{
+    commands::audit_command::record_audit,
     db::MongoDbState,
     schema::{
-        client_schema::{Client, ClinetStatus, NewClient},
+        audit_schema::{AuditAction, AuditEntry},
+        client_schema::{
+            BalanceMismatch, Client, ClientBalances, ClientCheck, ClientMatch, ClientTotals,
+            ClinetStatus, NewClient, ReconciliationReport,
+        },
         collections::Collection,
-        error::{AppResult, ErrorResponse},
+        error::{AppResult, ErrorResponse, FieldError},
     },
     utils::parse_object_id,
+    validation::{find_matches, validate_client},
 };
 
+use std::collections::{HashMap, HashSet};
+
 use futures::TryStreamExt;
-use mongodb::bson::{self, doc, to_document, Bson, DateTime as MongoDateTime, Document};
+use mongodb::{
+    bson::{self, doc, oid::ObjectId, to_document, Bson, DateTime as MongoDateTime, Document},
+    options::ReturnDocument,
+    ClientSession,
+};
 use tauri::State;
 use tokio::sync::Mutex;
 
-// Add new Client document to the database
+/// Clients a new client could duplicate. Tombstones left by merges are not candidates.
+pub async fn load_match_candidates(db: &MongoDbState) -> AppResult<Vec<Client>> {
+    db.get_collection::<Client>(Collection::Client)
+        .find(doc! { "mergedInto": { "$exists": false } })
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to fetch clients", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to fetch clients", Some(e.to_string()))
+        })?
+        .try_collect()
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to parese client data", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to parese client data", Some(e.to_string()))
+        })
+}
+
+/// Existing clients that share a phone number, email address or company name with `client`.
+async fn find_duplicate_clients(
+    db: &MongoDbState,
+    client: &NewClient,
+) -> AppResult<Vec<ClientMatch>> {
+    let existing = load_match_candidates(db).await?;
+    Ok(find_matches(client, &existing))
+}
+
+/// The document stored for a newly added client.
+pub fn new_client_record(client: NewClient) -> Client {
+    Client {
+        id: None,
+        username: client.username,
+        email: client.email,
+        phone: client.phone,
+        company_name: client.company_name,
+        city: client.city,
+        address: client.address,
+        contacts: client.contacts,
+        addresses: client.addresses,
+        invoices: Vec::new(), // Assuming new client has no invoices by default
+        total_owed: 0.0,      // Assuming new client has no debt
+        total_paid: 0.0,      // Assuming new client has not paid anything yet
+        outstanding_balance: 0.0, // Assuming no outstanding balance initially
+        tax_number: client.tax_number,
+        tags: client.tags,
+        group_id: client.group_id,
+        price_list_id: client.price_list_id,
+        credit_limit: client.credit_limit,
+        merged_into: None,
+        deactivated_at: None,
+        deactivation_reason: None,
+        anonymized_at: None,
+        status: ClinetStatus::Active,
+        created_at: MongoDateTime::now(),
+        updated_at: MongoDateTime::now(),
+    }
+}
+
+// Validate a client before adding it and list existing clients it may duplicate
+#[tauri::command]
+pub async fn check_new_client(
+    client: NewClient,
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<ClientCheck> {
+    let db = db.lock().await;
+
+    let errors = validate_client(&client);
+    let matches = find_duplicate_clients(&db, &client).await?;
+    logger::log_info(
+        &format!(
+            "Checked new client with {} errors and {} possible duplicates",
+            errors.len(),
+            matches.len()
+        ),
+        200,
+        None,
+    );
+
+    Ok(ClientCheck { errors, matches })
+}
+
+// Add new Client document to the database. Possible duplicates are rejected
+// unless `allow_duplicate` is set.
 #[tauri::command]
 pub async fn add_new_client(
     client: NewClient,
+    allow_duplicate: Option<bool>,
     db: State<'_, Mutex<MongoDbState>>,
 ) -> AppResult<Client> {
     // lock the db State to work saftelr
     let db = db.lock().await;
 
+    let errors = validate_client(&client);
+    if !errors.is_empty() {
+        logger::log_error("Invalid client data", 400, None);
+        return Err(ErrorResponse::validation("Invalid client data", errors));
+    }
+    if !allow_duplicate.unwrap_or(false) {
+        let matches = find_duplicate_clients(&db, &client).await?;
+        if !matches.is_empty() {
+            let details = matches
+                .iter()
+                .map(|m| format!("{} ({})", m.client_id, m.username))
+                .collect::<Vec<_>>()
+                .join(", ");
+            logger::log_error("Possible duplicate client", 409, Some(&details));
+            return Err(ErrorResponse::new(
+                409,
+                "Possible duplicate client",
+                Some(details),
+            ));
+        }
+    }
+
     let collection = db.get_collection::<Document>(Collection::Client);
-    let mut client = Client {
-        id: None,
-        username: client.username, // Assumes `NewClient` has a `username` field
-        email: client.email,       // Assumes `NewClient` has an `email` field
-        phone: client.phone,       // Assumes `NewClient` has a `phone` field
-        company_name: client.company_name, // Assumes `NewClient` has a `company_name` field
-        city: client.city,         // Assumes `NewClient` has a `city` field
-        address: client.address,   // Assumes `NewClient` has an `address` field
-        invoices: Vec::new(),      // Assuming new client has no invoices by default
-        total_owed: 0.0,           // Assuming new client has no debt
-        total_paid: 0.0,           // Assuming new client has not paid anything yet
-        outstanding_balance: 0.0,  // Assuming no outstanding balance initially
-        status: ClinetStatus::Active,
-        created_at: MongoDateTime::now(),
-        updated_at: MongoDateTime::now(),
-    };
+    let mut client = new_client_record(client);
     // Serialize the client data into a BSON document
     let doc = to_document(&client).map_err(|e| {
         logger::log_error("Failed to serialize client data", 400, Some(&e.to_string()));
@@ -94,19 +195,178 @@ pub async fn list_all_clients(db: State<'_, Mutex<MongoDbState>>) -> AppResult<V
     Ok(clients)
 }
 
+/// Records a change already saved to a client. A failed write is logged by
+/// `record_audit` but does not fail the change.
+async fn audit_client_change(
+    db: &MongoDbState,
+    action: AuditAction,
+    id: ObjectId,
+    fields: Vec<String>,
+    changed_by: Option<String>,
+    reason: Option<String>,
+) {
+    let entry = AuditEntry {
+        id: None,
+        action,
+        entity_id: id,
+        fields,
+        performed_by: changed_by,
+        reason,
+        created_at: MongoDateTime::now(),
+    };
+    record_audit(db, None, &entry).await.ok();
+}
+
+// List the clients with all of the given tags and, optionally, in a group
+#[tauri::command]
+pub async fn list_clients_by_segment(
+    tags: Option<Vec<String>>,
+    group_id: Option<String>,
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<Vec<Client>> {
+    let db = db.lock().await;
+    let collection = db.get_collection::<Client>(Collection::Client);
+
+    let mut filter = doc! { "mergedInto": { "$exists": false } };
+    if let Some(tags) = tags.filter(|tags| !tags.is_empty()) {
+        filter.insert("tags", doc! { "$all": tags });
+    }
+    if let Some(group_id) = group_id {
+        filter.insert("groupId", parse_object_id(&group_id, "Client group")?);
+    }
+
+    let clients: Vec<Client> = collection
+        .find(filter)
+        .sort(doc! { "username": 1 })
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to fetch clients", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to fetch clients", Some(e.to_string()))
+        })?
+        .try_collect()
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to parese client data", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to parese client data", Some(e.to_string()))
+        })?;
+    logger::log_info(
+        &format!("Return {} clients in segment", clients.len()),
+        200,
+        None,
+    );
+
+    Ok(clients)
+}
+
+/// Profile fields `update_client` may change. Status, balances and merge or
+/// anonymization markers have their own commands.
+const EDITABLE_CLIENT_FIELDS: [&str; 13] = [
+    "username",
+    "email",
+    "phone",
+    "company_name",
+    "city",
+    "address",
+    "contacts",
+    "addresses",
+    "taxNumber",
+    "tags",
+    "groupId",
+    "priceListId",
+    "creditLimit",
+];
+
+/// Errors for keys in an update that are not editable profile fields.
+/// `_id` and `updated_at` are accepted and ignored.
+fn non_editable_fields(updated_fields: &Document) -> Vec<FieldError> {
+    updated_fields
+        .keys()
+        .filter(|key| {
+            !["_id", "updated_at"].contains(&key.as_str())
+                && !EDITABLE_CLIENT_FIELDS.contains(&key.as_str())
+        })
+        .map(|key| FieldError {
+            field: key.clone(),
+            message: "This field cannot be changed here".to_string(),
+        })
+        .collect()
+}
+
 // Update Client Data
 #[tauri::command]
 pub async fn update_client(
     db: State<'_, Mutex<MongoDbState>>,
     client_id: String,
     updated_fields: Document, // Use a `Document` for flexibility in updating fields
+    changed_by: Option<String>,
 ) -> AppResult<Client> {
+    let errors = non_editable_fields(&updated_fields);
+    if !errors.is_empty() {
+        logger::log_error("Update of non-editable client fields", 400, None);
+        return Err(ErrorResponse::validation("Invalid client data", errors));
+    }
+    let mut updated_fields = updated_fields;
+    updated_fields.remove("_id");
+    updated_fields.remove("updated_at");
+
     let db = db.lock().await;
     let collection = db.get_collection::<Document>(Collection::Client);
     let id = parse_object_id(&client_id, "Client")?;
 
+    // Validate the client as it will be after the update
+    let mut merged = collection
+        .find_one(doc! { "_id": id })
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to find client", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to find client", Some(e.to_string()))
+        })?
+        .ok_or_else(|| {
+            logger::log_error("Client not found", 404, None);
+            ErrorResponse::new(404, "Client not found", None)
+        })?;
+    if merged.get_datetime("anonymizedAt").is_ok() {
+        logger::log_error(
+            &format!("Update of anonymized client {}", client_id),
+            409,
+            None,
+        );
+        return Err(ErrorResponse::new(
+            409,
+            "Anonymized clients cannot be edited",
+            None,
+        ));
+    }
+    if merged.get_object_id("mergedInto").is_ok() {
+        logger::log_error(&format!("Update of merged client {}", client_id), 409, None);
+        return Err(ErrorResponse::new(
+            409,
+            "Merged clients cannot be edited",
+            None,
+        ));
+    }
+    merged.extend(updated_fields.clone());
+    let merged = bson::from_document::<NewClient>(merged).map_err(|e| {
+        logger::log_error("Invalid client data", 400, Some(&e.to_string()));
+        ErrorResponse::new(400, "Invalid client data", Some(e.to_string()))
+    })?;
+    // Only the fields being changed are checked, so clients saved before a rule
+    // was added can still be edited
+    let errors: Vec<FieldError> = validate_client(&merged)
+        .into_iter()
+        .filter(|error| {
+            let field = error.field.split(['[', '.']).next().unwrap_or_default();
+            updated_fields.contains_key(field)
+        })
+        .collect();
+    if !errors.is_empty() {
+        logger::log_error("Invalid client data", 400, None);
+        return Err(ErrorResponse::validation("Invalid client data", errors));
+    }
+
+    let changed_fields: Vec<String> = updated_fields.keys().cloned().collect();
+
     // Add `updated_at` to the update document
-    let mut updated_fields = updated_fields.clone();
     updated_fields.insert("updated_at", MongoDateTime::now());
 
     // Prepare the update document with `$set` for partial updates
@@ -145,6 +405,16 @@ pub async fn update_client(
             })?;
             logger::log_info("Retrun updated document", 200, None);
 
+            audit_client_change(
+                &db,
+                AuditAction::Updated,
+                id,
+                changed_fields,
+                changed_by,
+                None,
+            )
+            .await;
+
             Ok(updated_client)
         }
         None => {
@@ -201,11 +471,18 @@ pub async fn find_client_by_id(
     Ok(client)
 }
 
-// Delete client by Id
+// Open balances below half a cent are rounding noise
+const MIN_OPEN_BALANCE: f64 = 0.005;
+
+// Deactivate a client, recording why. Clients who still owe money stay active
+// unless `force` is set.
 #[tauri::command]
 pub async fn deactive_client(
     db: State<'_, Mutex<MongoDbState>>,
     client_id: String,
+    reason: String,
+    force: Option<bool>,
+    changed_by: Option<String>,
 ) -> AppResult<Client> {
     // Lock the database to safely access it
     let db = db.lock().await;
@@ -214,15 +491,72 @@ pub async fn deactive_client(
     // Parse the client ID into an ObjectId
     let id = parse_object_id(&client_id, "Client")?;
 
+    let reason = reason.trim();
+    if reason.is_empty() {
+        logger::log_error("Deactivation reason is required", 400, None);
+        return Err(ErrorResponse::validation(
+            "Deactivation reason is required",
+            vec![FieldError {
+                field: "reason".to_string(),
+                message: "Give a reason for deactivating the client".to_string(),
+            }],
+        ));
+    }
+
+    let client = collection
+        .find_one(doc! { "_id": id })
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to fetch client", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to fetch client", Some(e.to_string()))
+        })?
+        .ok_or_else(|| ErrorResponse::new(404, "Client not found", None))?;
+    if !client.is_active() {
+        logger::log_error(
+            &format!("Client {} is already inactive", client_id),
+            409,
+            None,
+        );
+        return Err(ErrorResponse::new(409, "Client is already inactive", None));
+    }
+
+    if client.outstanding_balance >= MIN_OPEN_BALANCE {
+        let details = format!("Outstanding balance: {:.2}", client.outstanding_balance);
+        if !force.unwrap_or(false) {
+            logger::log_error(
+                &format!("Client {} still has an open balance", client_id),
+                409,
+                Some(&details),
+            );
+            return Err(ErrorResponse::new(
+                409,
+                "Client still has an open balance",
+                Some(details),
+            ));
+        }
+        logger::log_warn(
+            &format!("Deactivating client {} with an open balance", client_id),
+            200,
+            Some(&details),
+        );
+    }
+
     // Define the update document
-    let update = doc! { "$set": { "status": ClinetStatus::InActive.to_string() } };
+    let update = doc! { "$set": {
+        "status": ClinetStatus::InActive.to_string(),
+        "deactivatedAt": MongoDateTime::now(),
+        "deactivationReason": reason,
+        "updated_at": MongoDateTime::now(),
+    } };
 
-    // Perform the update operation and retrieve the updated document
+    // Perform the update operation and retrieve the updated document. The status
+    // filter keeps a concurrent deactivation from overwriting this one.
     let updated_client = collection
         .find_one_and_update(
-            doc! { "_id": id }, // Filter by client ID
-            update,             // Set the status to Inactive
+            doc! { "_id": id, "status": ClinetStatus::Active.to_string() },
+            update,
         )
+        .return_document(ReturnDocument::After)
         .await
         .map_err(|e| {
             logger::log_error("Failed to deactivate client", 500, Some(&e.to_string()));
@@ -230,16 +564,25 @@ pub async fn deactive_client(
         })?;
 
     // Handle the case where no document was found
-    let updated_client =
-        updated_client.ok_or_else(|| ErrorResponse::new(404, "Client not found", None))?;
+    let updated_client = updated_client
+        .ok_or_else(|| ErrorResponse::new(404, "Client not found or already inactive", None))?;
     logger::log_info(
         &format!(
             "Find and deactivate clinet with ID: {}",
             updated_client.id.unwrap()
         ),
         200,
-        None,
+        Some(reason),
     );
+    audit_client_change(
+        &db,
+        AuditAction::Deactivated,
+        id,
+        vec!["status".to_string()],
+        changed_by,
+        Some(reason.to_string()),
+    )
+    .await;
 
     Ok(updated_client)
 }
@@ -248,6 +591,7 @@ pub async fn deactive_client(
 pub async fn activate_client(
     db: State<'_, Mutex<MongoDbState>>,
     client_id: String,
+    changed_by: Option<String>,
 ) -> AppResult<Client> {
     // Lock the database to safely access it
     let db = db.lock().await;
@@ -257,13 +601,25 @@ pub async fn activate_client(
     let id = parse_object_id(&client_id, "Client")?;
 
     // Define the update document to set the status to "Active"
-    let update = doc! { "$set": { "status": ClinetStatus::Active.to_string() } };
+    let update = doc! {
+        "$set": {
+            "status": ClinetStatus::Active.to_string(),
+            "updated_at": MongoDateTime::now(),
+        },
+        "$unset": { "deactivatedAt": "", "deactivationReason": "" },
+    };
 
     let updated_client_doc = collection
         .find_one_and_update(
-            doc! { "_id": id, "status": { "$ne": ClinetStatus::Active.to_string() } }, // Update only if not active
+            doc! {
+                "_id": id,
+                "status": { "$ne": ClinetStatus::Active.to_string() }, // Update only if not active
+                "anonymizedAt": { "$exists": false },
+                "mergedInto": { "$exists": false },
+            },
             update,
         )
+        .return_document(ReturnDocument::After)
         .await
         .map_err(|e| {
             logger::log_error("Failed to activate client", 500, Some(&e.to_string()));
@@ -271,9 +627,32 @@ pub async fn activate_client(
             ErrorResponse::new(500, "Failed to activate client", Some(e.to_string()))
         })?;
 
-    // Handle case where no document was updated (client already active or not found)
-    let updated_client_doc = updated_client_doc
-        .ok_or_else(|| ErrorResponse::new(404, "Client not found or already active", None))?;
+    // Handle case where no document was updated
+    let Some(updated_client_doc) = updated_client_doc else {
+        let existing = collection.find_one(doc! { "_id": id }).await.map_err(|e| {
+            logger::log_error("Failed to find client", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to find client", Some(e.to_string()))
+        })?;
+        return Err(match existing {
+            Some(client) if client.anonymized_at.is_some() => {
+                logger::log_error(
+                    &format!("Activation of anonymized client {}", client_id),
+                    409,
+                    None,
+                );
+                ErrorResponse::new(409, "Anonymized clients cannot be reactivated", None)
+            }
+            Some(client) if client.merged_into.is_some() => {
+                logger::log_error(
+                    &format!("Activation of merged client {}", client_id),
+                    409,
+                    None,
+                );
+                ErrorResponse::new(409, "Merged clients cannot be reactivated", None)
+            }
+            _ => ErrorResponse::new(404, "Client not found or already active", None),
+        });
+    };
     logger::log_info(
         &format!(
             "Find and active clinet with ID: {}",
@@ -283,5 +662,864 @@ pub async fn activate_client(
         None,
     );
 
+    audit_client_change(
+        &db,
+        AuditAction::Activated,
+        id,
+        vec!["status".to_string()],
+        changed_by,
+        None,
+    )
+    .await;
+
     Ok(updated_client_doc)
 }
+
+/// Aggregation computing invoice IDs and totals per client from the invoices
+/// matching `filter`. Clients without invoices get no document.
+pub fn client_totals_pipeline(filter: Document) -> Vec<Document> {
+    vec![
+        doc! { "$match": filter },
+        doc! { "$sort": { "created_at": 1 } },
+        doc! { "$group": {
+            "_id": "$clientId",
+            "invoices": { "$push": "$_id" },
+            "totalOwed": { "$sum": { "$subtract": ["$totalPrice", "$totalPaid"] } },
+            "totalPaid": { "$sum": "$totalPaid" },
+        } },
+    ]
+}
+
+async fn find_client_in_session(
+    db: &MongoDbState,
+    session: &mut ClientSession,
+    id: ObjectId,
+) -> AppResult<Client> {
+    db.get_collection::<Client>(Collection::Client)
+        .find_one(doc! { "_id": id })
+        .session(session)
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to find client", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to find client", Some(e.to_string()))
+        })?
+        .ok_or_else(|| {
+            logger::log_error(&format!("Client not found for ID: {}", id), 404, None);
+            ErrorResponse::new(404, &format!("Client not found for ID: {}", id), None)
+        })
+}
+
+async fn merge_in_session(
+    db: &MongoDbState,
+    session: &mut ClientSession,
+    source_id: ObjectId,
+    target_id: ObjectId,
+    merged_by: Option<String>,
+) -> AppResult<Client> {
+    let source = find_client_in_session(db, session, source_id).await?;
+    let target = find_client_in_session(db, session, target_id).await?;
+    for client in [&source, &target] {
+        if let Some(merged_into) = client.merged_into {
+            logger::log_error(
+                &format!(
+                    "Client {} was already merged",
+                    client.id.unwrap_or_default()
+                ),
+                409,
+                None,
+            );
+            return Err(ErrorResponse::new(
+                409,
+                "Client was already merged",
+                Some(format!(
+                    "Client {} was merged into {}",
+                    client.id.unwrap_or_default(),
+                    merged_into
+                )),
+            ));
+        }
+    }
+
+    // Invoices may only move to a client that can still be billed
+    let target_problem = if target.anonymized_at.is_some() {
+        Some("anonymized")
+    } else if !target.is_active() {
+        Some("inactive")
+    } else {
+        None
+    };
+    if let Some(problem) = target_problem {
+        logger::log_error(
+            &format!("Cannot merge into {} client {}", problem, target_id),
+            409,
+            None,
+        );
+        return Err(ErrorResponse::new(
+            409,
+            &format!("Cannot merge into an {} client", problem),
+            Some(format!("Client {} is {}", target_id, problem)),
+        ));
+    }
+
+    // Move the invoices over
+    db.get_collection::<Document>(Collection::Invoice)
+        .update_many(
+            doc! { "clientId": source_id },
+            doc! { "$set": { "clientId": target_id, "updated_at": MongoDateTime::now() } },
+        )
+        .session(&mut *session)
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to move invoices", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to move invoices", Some(e.to_string()))
+        })?;
+
+    // Notes and the email log follow the invoices, so the target's timeline is complete
+    for collection in [Collection::ClientNote, Collection::EmailDelivery] {
+        let name = collection.as_str().to_string();
+        db.get_collection::<Document>(collection)
+            .update_many(
+                doc! { "clientId": source_id },
+                doc! { "$set": { "clientId": target_id } },
+            )
+            .session(&mut *session)
+            .await
+            .map_err(|e| {
+                logger::log_error(
+                    &format!("Failed to move {}", name),
+                    500,
+                    Some(&e.to_string()),
+                );
+                ErrorResponse::new(
+                    500,
+                    &format!("Failed to move {}", name),
+                    Some(e.to_string()),
+                )
+            })?;
+    }
+
+    // Recompute the target totals from the invoices it now owns
+    let totals_doc = db
+        .get_collection::<Document>(Collection::Invoice)
+        .aggregate(client_totals_pipeline(doc! { "clientId": target_id }))
+        .session(&mut *session)
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to compute client totals", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to compute client totals", Some(e.to_string()))
+        })?
+        .next(&mut *session)
+        .await
+        .transpose()
+        .map_err(|e| {
+            logger::log_error("Failed to compute client totals", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to compute client totals", Some(e.to_string()))
+        })?;
+    let totals = match totals_doc {
+        Some(totals_doc) => bson::from_document::<ClientTotals>(totals_doc).map_err(|e| {
+            logger::log_error("Failed to parse client totals", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to parse client totals", Some(e.to_string()))
+        })?,
+        None => ClientTotals {
+            client_id: target_id,
+            ..Default::default()
+        },
+    };
+
+    let client_collection = db.get_collection::<Client>(Collection::Client);
+    let merged = client_collection
+        .find_one_and_update(
+            doc! { "_id": target_id },
+            doc! { "$set": {
+                "invoices": &totals.invoices,
+                "totalOwed": totals.total_owed,
+                "totalPaid": totals.total_paid,
+                "outstandingBalance": totals.total_owed,
+                "updated_at": MongoDateTime::now(),
+            } },
+        )
+        .return_document(ReturnDocument::After)
+        .session(&mut *session)
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to update client data", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to update client data", Some(e.to_string()))
+        })?
+        .ok_or_else(|| ErrorResponse::new(404, "Client not found", None))?;
+
+    // Leave the source behind as an inactive tombstone pointing at the target
+    client_collection
+        .update_one(
+            doc! { "_id": source_id },
+            doc! { "$set": {
+                "status": ClinetStatus::InActive.to_string(),
+                "invoices": [],
+                "totalOwed": 0.0,
+                "totalPaid": 0.0,
+                "outstandingBalance": 0.0,
+                "mergedInto": target_id,
+                "deactivatedAt": MongoDateTime::now(),
+                "deactivationReason": format!("Merged into client {}", target_id),
+                "updated_at": MongoDateTime::now(),
+            } },
+        )
+        .session(&mut *session)
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to update client data", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to update client data", Some(e.to_string()))
+        })?;
+
+    let entry = AuditEntry {
+        id: None,
+        action: AuditAction::Merged,
+        entity_id: target_id,
+        fields: vec![
+            "invoices".to_string(),
+            "totalOwed".to_string(),
+            "totalPaid".to_string(),
+            "outstandingBalance".to_string(),
+        ],
+        performed_by: merged_by,
+        reason: Some(format!("Merged client {} into this one", source_id)),
+        created_at: MongoDateTime::now(),
+    };
+    record_audit(db, Some(session), &entry).await?;
+
+    Ok(merged)
+}
+
+// Merge a duplicate client into another, active one. The source keeps no invoices
+// and remains as an inactive tombstone pointing at the target.
+#[tauri::command]
+pub async fn merge_clients(
+    source_client_id: String,
+    target_client_id: String,
+    merged_by: Option<String>,
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<Client> {
+    let db = db.lock().await;
+    let source_id = parse_object_id(&source_client_id, "Client")?;
+    let target_id = parse_object_id(&target_client_id, "Client")?;
+    if source_id == target_id {
+        logger::log_error("Cannot merge a client into itself", 400, None);
+        return Err(ErrorResponse::new(
+            400,
+            "Cannot merge a client into itself",
+            None,
+        ));
+    }
+
+    let mut session = db.start_session().await?;
+    session.start_transaction().await.map_err(|e| {
+        logger::log_error(
+            "Failed to start MongoDB transaction.",
+            500,
+            Some(&e.to_string()),
+        );
+        ErrorResponse::new(
+            500,
+            "Failed to start MongoDB transaction.",
+            Some(e.to_string()),
+        )
+    })?;
+
+    let merged = match merge_in_session(&db, &mut session, source_id, target_id, merged_by).await {
+        Ok(merged) => merged,
+        Err(e) => {
+            session.abort_transaction().await.ok();
+            return Err(e);
+        }
+    };
+
+    session.commit_transaction().await.map_err(|e| {
+        logger::log_error(
+            "Failed to commit MongoDB transaction.",
+            500,
+            Some(&e.to_string()),
+        );
+        ErrorResponse::new(
+            500,
+            "Failed to commit MongoDB transaction.",
+            Some(e.to_string()),
+        )
+    })?;
+    logger::log_info(
+        &format!(
+            "Merged client {} into {} with {} invoices",
+            source_client_id,
+            target_client_id,
+            merged.invoices.len()
+        ),
+        200,
+        None,
+    );
+    Ok(merged)
+}
+
+// Name shown in place of an anonymized client's username
+const ANONYMIZED_NAME: &str = "Anonymized client";
+// Subject kept on an anonymized client's deliveries; rendered subjects can name the client
+const REDACTED_SUBJECT: &str = "[redacted]";
+
+async fn anonymize_in_session(
+    db: &MongoDbState,
+    session: &mut ClientSession,
+    id: ObjectId,
+    performed_by: Option<String>,
+    reason: Option<String>,
+) -> AppResult<Client> {
+    let client = find_client_in_session(db, session, id).await?;
+    if client.anonymized_at.is_some() {
+        logger::log_error(&format!("Client {} is already anonymized", id), 409, None);
+        return Err(ErrorResponse::new(
+            409,
+            "Client is already anonymized",
+            None,
+        ));
+    }
+
+    let now = MongoDateTime::now();
+    let mut set = doc! {
+        "username": ANONYMIZED_NAME,
+        "email": Bson::Null,
+        "phone": "",
+        "address": "",
+        "contacts": [],
+        "addresses": [],
+        "anonymizedAt": now,
+        "updated_at": now,
+    };
+    // An erased client can no longer be contacted, so it is deactivated too
+    if client.is_active() {
+        set.insert("status", ClinetStatus::InActive.to_string());
+        set.insert("deactivatedAt", now);
+        set.insert("deactivationReason", "Personal data erased");
+    }
+    let anonymized = db
+        .get_collection::<Client>(Collection::Client)
+        .find_one_and_update(doc! { "_id": id }, doc! { "$set": set })
+        .return_document(ReturnDocument::After)
+        .session(&mut *session)
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to anonymize client", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to anonymize client", Some(e.to_string()))
+        })?
+        .ok_or_else(|| ErrorResponse::new(404, "Client not found", None))?;
+
+    // Delivery records keep the addresses and subjects documents were emailed with
+    let deliveries = db
+        .get_collection::<Document>(Collection::EmailDelivery)
+        .update_many(
+            doc! { "clientId": id },
+            doc! { "$set": { "to": [], "subject": REDACTED_SUBJECT } },
+        )
+        .session(&mut *session)
+        .await
+        .map_err(|e| {
+            logger::log_error(
+                "Failed to anonymize email deliveries",
+                500,
+                Some(&e.to_string()),
+            );
+            ErrorResponse::new(
+                500,
+                "Failed to anonymize email deliveries",
+                Some(e.to_string()),
+            )
+        })?;
+
+    let mut fields: Vec<String> = [
+        "username",
+        "email",
+        "phone",
+        "address",
+        "contacts",
+        "addresses",
+    ]
+    .into_iter()
+    .map(str::to_string)
+    .collect();
+    if deliveries.modified_count > 0 {
+        fields.push(format!("{}.to", Collection::EmailDelivery.as_str()));
+        fields.push(format!("{}.subject", Collection::EmailDelivery.as_str()));
+    }
+
+    // Invoices keep a copy of who they were billed to, and so do their revisions.
+    // The company name, city and tax number stay, as they do on the client.
+    let billing_fields = |prefix: &str| {
+        let mut set = doc! {
+            format!("{}.contact", prefix): Bson::Null,
+            format!("{}.email", prefix): Bson::Null,
+            format!("{}.phone", prefix): "",
+            format!("{}.address", prefix): "",
+        };
+        if client.company_name.trim().is_empty() {
+            set.insert(format!("{}.name", prefix), ANONYMIZED_NAME);
+        }
+        set
+    };
+    let invoices = db
+        .get_collection::<Document>(Collection::Invoice)
+        .update_many(
+            doc! { "clientId": id, "billingAddress": { "$ne": null } },
+            doc! { "$set": billing_fields("billingAddress") },
+        )
+        .session(&mut *session)
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to anonymize invoices", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to anonymize invoices", Some(e.to_string()))
+        })?;
+    if invoices.modified_count > 0 {
+        fields.push(format!("{}.billingAddress", Collection::Invoice.as_str()));
+    }
+    let revisions = db
+        .get_collection::<Document>(Collection::InvoiceRevision)
+        .update_many(
+            doc! { "invoice.clientId": id, "invoice.billingAddress": { "$ne": null } },
+            doc! { "$set": billing_fields("invoice.billingAddress") },
+        )
+        .session(&mut *session)
+        .await
+        .map_err(|e| {
+            logger::log_error(
+                "Failed to anonymize invoice revisions",
+                500,
+                Some(&e.to_string()),
+            );
+            ErrorResponse::new(
+                500,
+                "Failed to anonymize invoice revisions",
+                Some(e.to_string()),
+            )
+        })?;
+    if revisions.modified_count > 0 {
+        fields.push(format!(
+            "{}.invoice.billingAddress",
+            Collection::InvoiceRevision.as_str()
+        ));
+    }
+
+    // Notes are free text that may hold anything the client told staff
+    let notes = db
+        .get_collection::<Document>(Collection::ClientNote)
+        .delete_many(doc! { "clientId": id })
+        .session(&mut *session)
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to delete client notes", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to delete client notes", Some(e.to_string()))
+        })?;
+    if notes.deleted_count > 0 {
+        fields.push(Collection::ClientNote.as_str().to_string());
+    }
+    let entry = AuditEntry {
+        id: None,
+        action: AuditAction::Anonymized,
+        entity_id: id,
+        fields,
+        performed_by,
+        reason,
+        created_at: now,
+    };
+    record_audit(db, Some(session), &entry).await?;
+
+    Ok(anonymized)
+}
+
+// Erase a client's personal data at their request. Invoices, balances and the
+// links between them are kept, as they must be for tax records.
+#[tauri::command]
+pub async fn anonymize_client(
+    client_id: String,
+    performed_by: Option<String>,
+    reason: Option<String>,
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<Client> {
+    let db = db.lock().await;
+    let id = parse_object_id(&client_id, "Client")?;
+
+    let mut session = db.start_session().await?;
+    session.start_transaction().await.map_err(|e| {
+        logger::log_error(
+            "Failed to start MongoDB transaction.",
+            500,
+            Some(&e.to_string()),
+        );
+        ErrorResponse::new(
+            500,
+            "Failed to start MongoDB transaction.",
+            Some(e.to_string()),
+        )
+    })?;
+
+    let anonymized = match anonymize_in_session(&db, &mut session, id, performed_by, reason).await {
+        Ok(anonymized) => anonymized,
+        Err(e) => {
+            session.abort_transaction().await.ok();
+            return Err(e);
+        }
+    };
+
+    session.commit_transaction().await.map_err(|e| {
+        logger::log_error(
+            "Failed to commit MongoDB transaction.",
+            500,
+            Some(&e.to_string()),
+        );
+        ErrorResponse::new(
+            500,
+            "Failed to commit MongoDB transaction.",
+            Some(e.to_string()),
+        )
+    })?;
+    logger::log_info(&format!("Anonymized client {}", client_id), 200, None);
+    Ok(anonymized)
+}
+
+// Stored and computed amounts closer than this are treated as equal
+const BALANCE_TOLERANCE: f64 = 0.005;
+
+// Misnamed field older versions wrote next to `outstandingBalance`
+const LEGACY_OUTSTANDING_BALANCE: &str = "outstanding_balance";
+
+/// Compares a client's stored balances with the totals computed from its invoices.
+fn balance_mismatch(client: &Client, totals: &ClientTotals) -> Option<BalanceMismatch> {
+    let differs = |a: f64, b: f64| (a - b).abs() > BALANCE_TOLERANCE;
+    let stored_invoices: HashSet<&ObjectId> = client.invoices.iter().collect();
+    let computed_invoices: HashSet<&ObjectId> = totals.invoices.iter().collect();
+    if !differs(client.total_owed, totals.total_owed)
+        && !differs(client.total_paid, totals.total_paid)
+        && !differs(client.outstanding_balance, totals.total_owed)
+        && stored_invoices == computed_invoices
+    {
+        return None;
+    }
+    Some(BalanceMismatch {
+        client_id: totals.client_id,
+        username: client.username.clone(),
+        stored: ClientBalances {
+            total_owed: client.total_owed,
+            total_paid: client.total_paid,
+            outstanding_balance: client.outstanding_balance,
+            invoice_count: client.invoices.len(),
+        },
+        computed: ClientBalances {
+            total_owed: totals.total_owed,
+            total_paid: totals.total_paid,
+            outstanding_balance: totals.total_owed,
+            invoice_count: totals.invoices.len(),
+        },
+    })
+}
+
+/// The update setting a client's stored balances to `totals`.
+fn balance_correction(totals: &ClientTotals) -> Document {
+    doc! {
+        "$set": {
+            "invoices": &totals.invoices,
+            "totalOwed": totals.total_owed,
+            "totalPaid": totals.total_paid,
+            "outstandingBalance": totals.total_owed,
+            "updated_at": MongoDateTime::now(),
+        },
+        "$unset": { LEGACY_OUTSTANDING_BALANCE: "" },
+    }
+}
+
+/// The filter and update dropping the legacy balance field from every client.
+fn legacy_balance_cleanup() -> (Document, Document) {
+    (
+        doc! { LEGACY_OUTSTANDING_BALANCE: { "$exists": true } },
+        doc! { "$unset": { LEGACY_OUTSTANDING_BALANCE: "" } },
+    )
+}
+
+async fn apply_corrections_in_session(
+    db: &MongoDbState,
+    session: &mut ClientSession,
+    corrections: Vec<(ObjectId, Document)>,
+) -> AppResult<()> {
+    let collection = db.get_collection::<Document>(Collection::Client);
+    for (client_id, update) in corrections {
+        collection
+            .update_one(doc! { "_id": client_id }, update)
+            .session(&mut *session)
+            .await
+            .map_err(|e| {
+                logger::log_error(
+                    &format!("Failed to correct balances of client: {}", client_id),
+                    500,
+                    Some(&e.to_string()),
+                );
+                ErrorResponse::new(
+                    500,
+                    "Failed to correct client balances",
+                    Some(e.to_string()),
+                )
+            })?;
+    }
+
+    let (filter, update) = legacy_balance_cleanup();
+    collection
+        .update_many(filter, update)
+        .session(&mut *session)
+        .await
+        .map_err(|e| {
+            logger::log_error(
+                "Failed to correct client balances",
+                500,
+                Some(&e.to_string()),
+            );
+            ErrorResponse::new(
+                500,
+                "Failed to correct client balances",
+                Some(e.to_string()),
+            )
+        })?;
+    Ok(())
+}
+
+// Recompute every client's totals from their invoices and report the clients
+// whose stored totals differ. With `apply` the stored totals are corrected.
+#[tauri::command]
+pub async fn reconcile_client_balances(
+    apply: Option<bool>,
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<ReconciliationReport> {
+    let db = db.lock().await;
+    let apply = apply.unwrap_or(false);
+    let client_collection = db.get_collection::<Client>(Collection::Client);
+
+    let totals: Vec<Document> = db
+        .get_collection::<Document>(Collection::Invoice)
+        .aggregate(client_totals_pipeline(doc! {}))
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to compute client totals", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to compute client totals", Some(e.to_string()))
+        })?
+        .try_collect()
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to compute client totals", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to compute client totals", Some(e.to_string()))
+        })?;
+    let mut totals_by_client = HashMap::new();
+    for totals_doc in totals {
+        let totals = bson::from_document::<ClientTotals>(totals_doc).map_err(|e| {
+            logger::log_error("Failed to parse client totals", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to parse client totals", Some(e.to_string()))
+        })?;
+        totals_by_client.insert(totals.client_id, totals);
+    }
+
+    let clients: Vec<Client> = client_collection
+        .find(doc! {})
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to fetch clients", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to fetch clients", Some(e.to_string()))
+        })?
+        .try_collect()
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to parese client data", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to parese client data", Some(e.to_string()))
+        })?;
+
+    let clients_checked = clients.len();
+    let mut mismatches = Vec::new();
+    let mut corrections = Vec::new();
+    for client in clients {
+        let Some(client_id) = client.id else {
+            continue;
+        };
+        let totals = totals_by_client
+            .remove(&client_id)
+            .unwrap_or_else(|| ClientTotals {
+                client_id,
+                ..Default::default()
+            });
+        if let Some(mismatch) = balance_mismatch(&client, &totals) {
+            corrections.push((client_id, balance_correction(&totals)));
+            mismatches.push(mismatch);
+        }
+    }
+
+    if apply {
+        // Every correction is applied or none is, so the report never claims a
+        // partial fix
+        let mut session = db.start_session().await?;
+        session.start_transaction().await.map_err(|e| {
+            logger::log_error(
+                "Failed to start MongoDB transaction.",
+                500,
+                Some(&e.to_string()),
+            );
+            ErrorResponse::new(
+                500,
+                "Failed to start MongoDB transaction.",
+                Some(e.to_string()),
+            )
+        })?;
+        if let Err(e) = apply_corrections_in_session(&db, &mut session, corrections).await {
+            session.abort_transaction().await.ok();
+            return Err(e);
+        }
+        session.commit_transaction().await.map_err(|e| {
+            logger::log_error(
+                "Failed to commit MongoDB transaction.",
+                500,
+                Some(&e.to_string()),
+            );
+            ErrorResponse::new(
+                500,
+                "Failed to commit MongoDB transaction.",
+                Some(e.to_string()),
+            )
+        })?;
+    }
+
+    logger::log_info(
+        &format!(
+            "Reconciled {} clients, {} mismatched{}",
+            clients_checked,
+            mismatches.len(),
+            if apply { " and corrected" } else { "" }
+        ),
+        200,
+        None,
+    );
+    Ok(ReconciliationReport {
+        applied: apply,
+        clients_checked,
+        mismatches,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{client, object_id};
+
+    fn totals(invoices: Vec<ObjectId>, total_owed: f64, total_paid: f64) -> ClientTotals {
+        ClientTotals {
+            client_id: object_id(1),
+            invoices,
+            total_owed,
+            total_paid,
+        }
+    }
+
+    fn client_with(invoices: Vec<ObjectId>, total_owed: f64, total_paid: f64) -> Client {
+        Client {
+            invoices,
+            total_owed,
+            total_paid,
+            outstanding_balance: total_owed,
+            ..client()
+        }
+    }
+
+    #[test]
+    fn profile_fields_are_editable() {
+        let update = doc! {
+            "_id": ObjectId::new(),
+            "username": "Mona",
+            "phone": "01012345678",
+            "creditLimit": 5000.0,
+            "updated_at": MongoDateTime::now(),
+        };
+        assert!(non_editable_fields(&update).is_empty());
+    }
+
+    #[test]
+    fn status_balances_and_markers_are_rejected() {
+        let update = doc! {
+            "username": "Mona",
+            "status": "Inactive",
+            "outstandingBalance": 0.0,
+            "totalOwed": 0.0,
+            "mergedInto": ObjectId::new(),
+        };
+        let fields: Vec<String> = non_editable_fields(&update)
+            .into_iter()
+            .map(|error| error.field)
+            .collect();
+        assert_eq!(
+            fields,
+            ["status", "outstandingBalance", "totalOwed", "mergedInto"]
+        );
+    }
+
+    #[test]
+    fn matching_balances_are_not_reported() {
+        let invoices = vec![object_id(21), object_id(22)];
+        let client = client_with(invoices.clone(), 150.0, 50.0);
+        // Rounding noise and invoice order don't count as a mismatch
+        let totals = totals(vec![object_id(22), object_id(21)], 150.004, 50.0);
+        assert!(balance_mismatch(&client, &totals).is_none());
+    }
+
+    #[test]
+    fn drifted_balances_are_reported() {
+        let client = client_with(vec![object_id(21)], 120.0, 50.0);
+        let mismatch = balance_mismatch(&client, &totals(vec![object_id(21)], 150.0, 50.0))
+            .expect("total owed differs");
+        assert_eq!(mismatch.client_id, object_id(1));
+        assert_eq!(mismatch.stored.total_owed, 120.0);
+        assert_eq!(mismatch.computed.total_owed, 150.0);
+        assert_eq!(mismatch.computed.outstanding_balance, 150.0);
+    }
+
+    #[test]
+    fn outstanding_balance_and_invoices_are_checked() {
+        let invoices = vec![object_id(21)];
+        let stale_outstanding = Client {
+            outstanding_balance: 0.0,
+            ..client_with(invoices.clone(), 150.0, 50.0)
+        };
+        let missing_invoice = client_with(Vec::new(), 150.0, 50.0);
+
+        let totals = totals(invoices, 150.0, 50.0);
+        assert!(balance_mismatch(&stale_outstanding, &totals).is_some());
+        let mismatch = balance_mismatch(&missing_invoice, &totals).unwrap();
+        assert_eq!(mismatch.stored.invoice_count, 0);
+        assert_eq!(mismatch.computed.invoice_count, 1);
+    }
+
+    #[test]
+    fn clients_without_invoices_reconcile_to_zero() {
+        let client = client_with(vec![object_id(21)], 80.0, 0.0);
+        let totals = ClientTotals {
+            client_id: object_id(1),
+            ..Default::default()
+        };
+        let mismatch = balance_mismatch(&client, &totals).unwrap();
+        assert_eq!(mismatch.computed.total_owed, 0.0);
+        assert_eq!(mismatch.computed.invoice_count, 0);
+    }
+
+    #[test]
+    fn corrections_set_balances_and_drop_the_legacy_field() {
+        let update = balance_correction(&totals(vec![object_id(21)], 150.0, 50.0));
+        let set = update.get_document("$set").unwrap();
+        assert_eq!(set.get_f64("totalOwed").unwrap(), 150.0);
+        assert_eq!(set.get_f64("totalPaid").unwrap(), 50.0);
+        assert_eq!(set.get_f64("outstandingBalance").unwrap(), 150.0);
+        assert_eq!(
+            update.get_document("$unset").unwrap(),
+            &doc! { "outstanding_balance": "" }
+        );
+    }
+
+    #[test]
+    fn legacy_cleanup_targets_clients_with_the_old_field() {
+        let (filter, update) = legacy_balance_cleanup();
+        assert_eq!(filter, doc! { "outstanding_balance": { "$exists": true } });
+        assert_eq!(update, doc! { "$unset": { "outstanding_balance": "" } });
+    }
+}
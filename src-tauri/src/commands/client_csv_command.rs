@@ -0,0 +1,168 @@
+use futures::TryStreamExt;
+use mongodb::bson::{doc, oid::ObjectId};
+use tauri::{AppHandle, State};
+use tokio::sync::Mutex;
+
+use crate::{
+    client_csv::{build_clients_csv, build_error_report, check_rows, read_table, resolve_mapping},
+    commands::{
+        client_command::{load_match_candidates, new_client_record},
+        document_command::{load_document, save_document},
+    },
+    db::MongoDbState,
+    schema::{
+        client_import_schema::{
+            ClientCsvMapping, ClientImportPreview, ClientImportReport, ImportRowStatus,
+        },
+        client_schema::Client,
+        collections::Collection,
+        error::{AppResult, ErrorResponse},
+    },
+};
+
+// Rows shown in an import preview unless the caller asks for more
+const DEFAULT_PREVIEW_ROWS: usize = 20;
+
+// Read a CSV file and show how its first rows would be imported
+#[tauri::command]
+pub async fn preview_client_import(
+    path: String,
+    mapping: Option<ClientCsvMapping>,
+    limit: Option<usize>,
+    app: AppHandle,
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<ClientImportPreview> {
+    let db = db.lock().await;
+
+    let table = read_table(&load_document(&app, &path)?)?;
+    let (mapping, columns) = resolve_mapping(&table.headers, &mapping.unwrap_or_default())?;
+    let existing = load_match_candidates(&db).await?;
+
+    let mut rows = check_rows(&table, &columns, &existing);
+    rows.truncate(limit.unwrap_or(DEFAULT_PREVIEW_ROWS));
+
+    Ok(ClientImportPreview {
+        total_rows: table.rows.len(),
+        headers: table.headers,
+        mapping,
+        rows,
+    })
+}
+
+// Import the valid rows of a CSV file as new clients. Rows that fail validation
+// or look like an existing client are skipped and can be written to an error report.
+#[tauri::command]
+pub async fn import_clients_csv(
+    path: String,
+    mapping: Option<ClientCsvMapping>,
+    dry_run: Option<bool>,
+    error_report_path: Option<String>,
+    app: AppHandle,
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<ClientImportReport> {
+    let db = db.lock().await;
+    let dry_run = dry_run.unwrap_or(false);
+
+    let table = read_table(&load_document(&app, &path)?)?;
+    let (_, columns) = resolve_mapping(&table.headers, &mapping.unwrap_or_default())?;
+    let existing = load_match_candidates(&db).await?;
+    let results = check_rows(&table, &columns, &existing);
+
+    let (valid, rejected): (Vec<_>, Vec<_>) = results
+        .into_iter()
+        .partition(|row| row.status == ImportRowStatus::Valid);
+    let invalid = rejected
+        .iter()
+        .filter(|row| row.status == ImportRowStatus::Invalid)
+        .count();
+
+    let error_report_path = match error_report_path {
+        Some(report_path) if !rejected.is_empty() => {
+            save_document(&app, &report_path, &build_error_report(&table, &rejected)?)?;
+            Some(report_path)
+        }
+        _ => None,
+    };
+
+    let mut imported_ids = Vec::new();
+    if !dry_run && !valid.is_empty() {
+        let clients: Vec<Client> = valid
+            .iter()
+            .filter_map(|row| row.client.clone())
+            .map(new_client_record)
+            .collect();
+        let result = db
+            .get_collection::<Client>(Collection::Client)
+            .insert_many(clients)
+            .await
+            .map_err(|e| {
+                logger::log_error("Failed to import clients", 500, Some(&e.to_string()));
+                ErrorResponse::new(500, "Failed to import clients", Some(e.to_string()))
+            })?;
+        let mut inserted: Vec<_> = result.inserted_ids.into_iter().collect();
+        inserted.sort_by_key(|(index, _)| *index);
+        imported_ids = inserted
+            .into_iter()
+            .filter_map(|(_, id)| id.as_object_id())
+            .collect::<Vec<ObjectId>>();
+    }
+
+    logger::log_info(
+        &format!(
+            "{} clients from {}: {} valid, {} invalid, {} duplicates",
+            if dry_run { "Checked" } else { "Imported" },
+            path,
+            valid.len(),
+            invalid,
+            rejected.len() - invalid
+        ),
+        200,
+        None,
+    );
+    Ok(ClientImportReport {
+        dry_run,
+        total_rows: table.rows.len(),
+        imported: imported_ids.len(),
+        invalid,
+        duplicates: rejected.len() - invalid,
+        imported_ids,
+        rows: rejected,
+        error_report_path,
+    })
+}
+
+// Export all clients with their balances as CSV
+#[tauri::command]
+pub async fn export_clients_csv(
+    path: String,
+    app: AppHandle,
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<String> {
+    let db = db.lock().await;
+
+    let clients: Vec<Client> = db
+        .get_collection::<Client>(Collection::Client)
+        .find(doc! { "mergedInto": { "$exists": false } })
+        .sort(doc! { "username": 1 })
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to fetch clients", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to fetch clients", Some(e.to_string()))
+        })?
+        .try_collect()
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to parese client data", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to parese client data", Some(e.to_string()))
+        })?;
+
+    let csv = build_clients_csv(&clients)?;
+    save_document(&app, &path, &csv)?;
+
+    logger::log_info(
+        &format!("Exported {} clients to {}", clients.len(), path),
+        200,
+        None,
+    );
+    Ok(path)
+}
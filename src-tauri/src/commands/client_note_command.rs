@@ -0,0 +1,261 @@
+use std::cmp::Reverse;
+
+use futures::TryStreamExt;
+use mongodb::bson::{doc, oid::ObjectId, DateTime, Document};
+use tauri::State;
+use tokio::sync::Mutex;
+
+use crate::{
+    commands::statement_command::load_statement,
+    db::MongoDbState,
+    schema::{
+        audit_schema::{AuditAction, AuditEntry},
+        client_note_schema::{ClientNote, NewClientNote, TimelineEvent, TimelineEventKind},
+        collections::Collection,
+        error::{AppResult, ErrorResponse, FieldError},
+        statement_schema::StatementEntryKind,
+    },
+    utils::parse_object_id,
+};
+
+async fn load_client_notes(db: &MongoDbState, client_id: ObjectId) -> AppResult<Vec<ClientNote>> {
+    db.get_collection::<ClientNote>(Collection::ClientNote)
+        .find(doc! { "clientId": client_id })
+        .sort(doc! { "created_at": -1 })
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to fetch client notes", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to fetch client notes", Some(e.to_string()))
+        })?
+        .try_collect()
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to parse client notes", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to parse client notes", Some(e.to_string()))
+        })
+}
+
+#[tauri::command]
+pub async fn add_client_note(
+    client_id: String,
+    note: NewClientNote,
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<ClientNote> {
+    let db = db.lock().await;
+    let id = parse_object_id(&client_id, "Client")?;
+
+    let mut errors = Vec::new();
+    if note.author.trim().is_empty() {
+        errors.push(FieldError {
+            field: "author".to_string(),
+            message: "Author is required".to_string(),
+        });
+    }
+    if note.body.trim().is_empty() {
+        errors.push(FieldError {
+            field: "body".to_string(),
+            message: "Note cannot be empty".to_string(),
+        });
+    }
+    if !errors.is_empty() {
+        logger::log_error("Invalid client note", 400, None);
+        return Err(ErrorResponse::validation("Invalid client note", errors));
+    }
+
+    let client = db
+        .get_collection::<Document>(Collection::Client)
+        .find_one(doc! { "_id": id })
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to fetch client", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to fetch client", Some(e.to_string()))
+        })?
+        .ok_or_else(|| {
+            logger::log_error(&format!("Client not found for ID: {}", id), 404, None);
+            ErrorResponse::new(404, "Client not found", None)
+        })?;
+    if client.get_datetime("anonymizedAt").is_ok() {
+        logger::log_error(
+            &format!("Note added to anonymized client {}", id),
+            409,
+            None,
+        );
+        return Err(ErrorResponse::new(
+            409,
+            "Anonymized clients cannot be edited",
+            None,
+        ));
+    }
+
+    let mut client_note = ClientNote {
+        id: None,
+        client_id: id,
+        author: note.author.trim().to_string(),
+        body: note.body.trim().to_string(),
+        created_at: DateTime::now(),
+    };
+    let result = db
+        .get_collection::<ClientNote>(Collection::ClientNote)
+        .insert_one(&client_note)
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to save client note", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to save client note", Some(e.to_string()))
+        })?;
+    client_note.id = result.inserted_id.as_object_id();
+
+    logger::log_info(&format!("Added note to client {}", client_id), 201, None);
+    Ok(client_note)
+}
+
+// List a client's notes, newest first
+#[tauri::command]
+pub async fn list_client_notes(
+    client_id: String,
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<Vec<ClientNote>> {
+    let db = db.lock().await;
+    let id = parse_object_id(&client_id, "Client")?;
+
+    let notes = load_client_notes(&db, id).await?;
+    logger::log_info(
+        &format!("List {} notes for client {}", notes.len(), client_id),
+        200,
+        None,
+    );
+    Ok(notes)
+}
+
+#[tauri::command]
+pub async fn delete_client_note(
+    note_id: String,
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<String> {
+    let db = db.lock().await;
+    let id = parse_object_id(&note_id, "Note")?;
+
+    let result = db
+        .get_collection::<ClientNote>(Collection::ClientNote)
+        .delete_one(doc! { "_id": id })
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to delete client note", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to delete client note", Some(e.to_string()))
+        })?;
+    if result.deleted_count == 0 {
+        logger::log_error("Client note not found", 404, None);
+        return Err(ErrorResponse::new(404, "Client note not found", None));
+    }
+
+    logger::log_info(
+        &format!("Note with ID {} deleted successfully", note_id),
+        204,
+        None,
+    );
+    Ok(format!("Note with ID {} deleted successfully", note_id))
+}
+
+fn audit_event(entry: AuditEntry) -> TimelineEvent {
+    let (kind, summary) = match entry.action {
+        AuditAction::Updated => (
+            TimelineEventKind::Edit,
+            format!("Changed {}", entry.fields.join(", ")),
+        ),
+        AuditAction::Deactivated => (TimelineEventKind::StatusChange, "Deactivated".to_string()),
+        AuditAction::Activated => (TimelineEventKind::StatusChange, "Activated".to_string()),
+        AuditAction::Merged => (TimelineEventKind::Edit, "Merged".to_string()),
+        AuditAction::Anonymized => (
+            TimelineEventKind::StatusChange,
+            "Personal data erased".to_string(),
+        ),
+    };
+    TimelineEvent {
+        date: entry.created_at,
+        kind,
+        summary: match entry.reason {
+            Some(reason) => format!("{}: {}", summary, reason),
+            None => summary,
+        },
+        author: entry.performed_by,
+        reference_id: entry.id,
+        amount: None,
+    }
+}
+
+// One feed of everything that happened to a client, newest first: notes,
+// invoices and their changes, payments, status changes and edits
+#[tauri::command]
+pub async fn client_timeline(
+    client_id: String,
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<Vec<TimelineEvent>> {
+    let db = db.lock().await;
+    let id = parse_object_id(&client_id, "Client")?;
+
+    // Invoices, payments and invoice changes come from the statement of account
+    let statement = load_statement(&db, id, None, None).await?;
+    let mut events: Vec<TimelineEvent> = statement
+        .lines
+        .into_iter()
+        .map(|line| {
+            let (kind, amount) = match line.kind {
+                StatementEntryKind::Invoice => (TimelineEventKind::Invoice, line.debit),
+                StatementEntryKind::Adjustment => (TimelineEventKind::InvoiceChange, line.debit),
+                StatementEntryKind::Credit => (TimelineEventKind::InvoiceChange, -line.credit),
+                StatementEntryKind::Payment => {
+                    (TimelineEventKind::Payment, line.credit - line.debit)
+                }
+            };
+            TimelineEvent {
+                date: line.date,
+                kind,
+                summary: line.description,
+                author: None,
+                reference_id: Some(line.invoice_id),
+                amount: Some(amount),
+            }
+        })
+        .collect();
+
+    events.extend(
+        load_client_notes(&db, id)
+            .await?
+            .into_iter()
+            .map(|note| TimelineEvent {
+                date: note.created_at,
+                kind: TimelineEventKind::Note,
+                summary: note.body,
+                author: Some(note.author),
+                reference_id: note.id,
+                amount: None,
+            }),
+    );
+
+    let audit_entries: Vec<AuditEntry> = db
+        .get_collection::<AuditEntry>(Collection::AuditLog)
+        .find(doc! { "entityId": id })
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to fetch audit log", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to fetch audit log", Some(e.to_string()))
+        })?
+        .try_collect()
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to parse audit log", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to parse audit log", Some(e.to_string()))
+        })?;
+    events.extend(audit_entries.into_iter().map(audit_event));
+
+    events.sort_by_key(|event| Reverse(event.date));
+    logger::log_info(
+        &format!(
+            "Timeline with {} events for client {}",
+            events.len(),
+            client_id
+        ),
+        200,
+        None,
+    );
+    Ok(events)
+}
@@ -0,0 +1,101 @@
+use mongodb::{
+    bson::{self, doc, DateTime as MongoDateTime, Document},
+    options::ReturnDocument,
+};
+use tauri::State;
+use tokio::sync::Mutex;
+
+use crate::{
+    db::MongoDbState,
+    schema::{
+        collections::Collection,
+        company_schema::{Company, NewCompany},
+        error::{AppResult, ErrorResponse},
+    },
+};
+
+/// Loads the company settings document, if one has been saved.
+pub async fn load_company(db: &MongoDbState) -> AppResult<Option<Company>> {
+    let collection = db.get_collection::<Company>(Collection::Company);
+    collection.find_one(doc! {}).await.map_err(|e| {
+        logger::log_error(
+            "Failed to fetch company settings",
+            500,
+            Some(&e.to_string()),
+        );
+        ErrorResponse::new(500, "Failed to fetch company settings", Some(e.to_string()))
+    })
+}
+
+#[tauri::command]
+pub async fn get_company_settings(db: State<'_, Mutex<MongoDbState>>) -> AppResult<Company> {
+    let db = db.lock().await;
+
+    let company = load_company(&db).await?.ok_or_else(|| {
+        logger::log_error("Company settings not configured", 404, None);
+        ErrorResponse::new(404, "Company settings not configured", None)
+    })?;
+    logger::log_info("Return company settings", 200, None);
+
+    Ok(company)
+}
+
+// Create or replace the company settings document
+#[tauri::command]
+pub async fn update_company_settings(
+    company: NewCompany,
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<Company> {
+    let db = db.lock().await;
+    let collection = db.get_collection::<Document>(Collection::Company);
+
+    let mut fields = bson::to_document(&company).map_err(|e| {
+        logger::log_error(
+            "Failed to serialize company settings",
+            400,
+            Some(&e.to_string()),
+        );
+        ErrorResponse::new(
+            400,
+            "Failed to serialize company settings",
+            Some(e.to_string()),
+        )
+    })?;
+    fields.insert("updated_at", MongoDateTime::now());
+
+    // There is only ever one company document, so match any and upsert
+    let updated_doc = collection
+        .find_one_and_update(doc! {}, doc! { "$set": fields })
+        .upsert(true)
+        .return_document(ReturnDocument::After)
+        .await
+        .map_err(|e| {
+            logger::log_error(
+                "Failed to update company settings",
+                500,
+                Some(&e.to_string()),
+            );
+            ErrorResponse::new(
+                500,
+                "Failed to update company settings",
+                Some(e.to_string()),
+            )
+        })?
+        .ok_or_else(|| ErrorResponse::new(500, "Failed to update company settings", None))?;
+
+    let company = bson::from_document::<Company>(updated_doc).map_err(|e| {
+        logger::log_error(
+            "Failed to deserialize company settings",
+            500,
+            Some(&e.to_string()),
+        );
+        ErrorResponse::new(
+            500,
+            "Failed to deserialize company settings",
+            Some(e.to_string()),
+        )
+    })?;
+    logger::log_info("Updated company settings", 200, None);
+
+    Ok(company)
+}
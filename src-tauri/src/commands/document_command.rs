@@ -0,0 +1,286 @@
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+    path::PathBuf,
+};
+
+use futures::TryStreamExt;
+use mongodb::bson::{doc, oid::ObjectId};
+use tauri::{AppHandle, Manager, State};
+use tauri_plugin_fs::{FsExt, OpenOptions};
+use tokio::sync::Mutex;
+
+use crate::{
+    commands::company_command::load_company,
+    db::MongoDbState,
+    documents::{
+        eta::{build_eta_document, sign_document, LocalTestSigner},
+        invoice_pdf::build_invoice_pdf,
+        templates::{self, TemplateData},
+        ubl::{build_ubl_document, UblDocumentType},
+    },
+    schema::{
+        client_schema::Client,
+        collections::Collection,
+        company_schema::Company,
+        error::{AppResult, ErrorResponse},
+        invoice_schema::Invoice,
+        product_schema::Product,
+    },
+    utils::parse_object_id,
+};
+
+/// Loads an invoice together with the client it was issued to.
+pub async fn load_invoice_with_client(
+    db: &MongoDbState,
+    invoice_id: ObjectId,
+) -> AppResult<(Invoice, Client)> {
+    let invoice = db
+        .get_collection::<Invoice>(Collection::Invoice)
+        .find_one(doc! { "_id": invoice_id })
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to fetch invoice", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to fetch invoice", Some(e.to_string()))
+        })?
+        .ok_or_else(|| {
+            logger::log_error(&format!("No Invoice wiht this ID: {invoice_id}"), 404, None);
+            ErrorResponse::new(404, "Invoice not found", None)
+        })?;
+
+    let client = db
+        .get_collection::<Client>(Collection::Client)
+        .find_one(doc! { "_id": invoice.client_id })
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to fetch client", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to fetch client", Some(e.to_string()))
+        })?
+        .ok_or_else(|| {
+            logger::log_error(
+                &format!("Client not found for ID: {}", invoice.client_id),
+                404,
+                None,
+            );
+            ErrorResponse::new(
+                404,
+                &format!("Client not found for ID: {}", invoice.client_id),
+                None,
+            )
+        })?;
+
+    Ok((invoice, client))
+}
+
+/// Loads the company settings, which structured exports cannot do without.
+pub async fn require_company(db: &MongoDbState) -> AppResult<Company> {
+    load_company(db).await?.ok_or_else(|| {
+        logger::log_error("Company settings not configured", 400, None);
+        ErrorResponse::new(
+            400,
+            "Company settings not configured",
+            Some("Save the company settings before exporting invoices".to_string()),
+        )
+    })
+}
+
+/// Writes `contents` to `path` through the fs plugin, replacing any existing file.
+pub fn save_document(app: &AppHandle, path: &str, contents: &[u8]) -> AppResult<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+
+    app.fs()
+        .open(PathBuf::from(path), options)
+        .and_then(|mut file| file.write_all(contents))
+        .map_err(|e| {
+            logger::log_error(
+                &format!("Failed to save document to: {}", path),
+                500,
+                Some(&e.to_string()),
+            );
+            ErrorResponse::new(500, "Failed to save document", Some(e.to_string()))
+        })
+}
+
+/// Reads the file at `path` through the fs plugin.
+pub fn load_document(app: &AppHandle, path: &str) -> AppResult<Vec<u8>> {
+    let mut options = OpenOptions::new();
+    options.read(true);
+
+    let mut contents = Vec::new();
+    app.fs()
+        .open(PathBuf::from(path), options)
+        .and_then(|mut file| file.read_to_end(&mut contents))
+        .map_err(|e| {
+            logger::log_error(
+                &format!("Failed to read document from: {}", path),
+                500,
+                Some(&e.to_string()),
+            );
+            ErrorResponse::new(500, "Failed to read document", Some(e.to_string()))
+        })?;
+    Ok(contents)
+}
+
+/// Returns the directory holding invoice templates inside the app data directory.
+pub fn app_templates_dir(app: &AppHandle) -> AppResult<PathBuf> {
+    let app_data_dir = app.path().app_data_dir().map_err(|e| {
+        logger::log_error(
+            "Failed to resolve app data directory",
+            500,
+            Some(&e.to_string()),
+        );
+        ErrorResponse::new(
+            500,
+            "Failed to resolve app data directory",
+            Some(e.to_string()),
+        )
+    })?;
+    templates::templates_dir(&app_data_dir)
+}
+
+/// Renders `data` to PDF with the given template, or the default one.
+pub fn render_with_template(
+    app: &AppHandle,
+    template_id: Option<&str>,
+    data: &TemplateData,
+) -> AppResult<Vec<u8>> {
+    let dir = app_templates_dir(app)?;
+    let template_id = template_id
+        .map(str::to_string)
+        .unwrap_or_else(|| templates::default_template_id(&dir));
+    let template = templates::load_template(&dir, &template_id)?;
+    let rendered = templates::render_template(&dir, &template, data)?;
+    build_invoice_pdf(data, &rendered)
+}
+
+// Render an invoice to PDF and save it at the path chosen by the user
+#[tauri::command]
+pub async fn render_invoice_pdf(
+    invoice_id: String,
+    path: String,
+    template_id: Option<String>,
+    app: AppHandle,
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<String> {
+    let db = db.lock().await;
+    let id = parse_object_id(&invoice_id, "Invoice")?;
+
+    let (invoice, client) = load_invoice_with_client(&db, id).await?;
+    let company = load_company(&db).await?;
+
+    let data = TemplateData::new(&invoice, &client, company.as_ref());
+    let pdf = render_with_template(&app, template_id.as_deref(), &data)?;
+    save_document(&app, &path, &pdf)?;
+
+    logger::log_info(
+        &format!("Rendered invoice {} to {}", invoice_id, path),
+        200,
+        None,
+    );
+    Ok(path)
+}
+
+// Export an invoice, or a credit note for it, as UBL 2.1 XML
+#[tauri::command]
+pub async fn export_invoice_ubl(
+    invoice_id: String,
+    path: String,
+    document_type: Option<UblDocumentType>,
+    app: AppHandle,
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<String> {
+    let db = db.lock().await;
+    let id = parse_object_id(&invoice_id, "Invoice")?;
+
+    let (invoice, client) = load_invoice_with_client(&db, id).await?;
+    let company = require_company(&db).await?;
+
+    let xml = build_ubl_document(
+        document_type.unwrap_or_default(),
+        &invoice,
+        &client,
+        &company,
+    )?;
+    save_document(&app, &path, xml.as_bytes())?;
+
+    logger::log_info(
+        &format!("Exported invoice {} as UBL to {}", invoice_id, path),
+        200,
+        None,
+    );
+    Ok(path)
+}
+
+/// Maps the products on an invoice to their ETA item codes.
+async fn load_item_codes(
+    db: &MongoDbState,
+    invoice: &Invoice,
+) -> AppResult<HashMap<ObjectId, String>> {
+    let product_ids: Vec<ObjectId> = invoice.goods.iter().map(|g| g.product_id).collect();
+    let products: Vec<Product> = db
+        .get_collection::<Product>(Collection::Product)
+        .find(doc! { "_id": { "$in": product_ids } })
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to fetch products", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to fetch products", Some(e.to_string()))
+        })?
+        .try_collect()
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to parse product data", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to parse product data", Some(e.to_string()))
+        })?;
+
+    Ok(products
+        .into_iter()
+        .filter_map(|p| Some((p.id?, p.item_code?)))
+        .collect())
+}
+
+// Export an invoice as an ETA e-invoice submission file. With `sign` the
+// document is signed by the local test signer.
+#[tauri::command]
+pub async fn export_invoice_eta(
+    invoice_id: String,
+    path: String,
+    sign: Option<bool>,
+    app: AppHandle,
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<String> {
+    let db = db.lock().await;
+    let id = parse_object_id(&invoice_id, "Invoice")?;
+
+    let (invoice, client) = load_invoice_with_client(&db, id).await?;
+    let company = require_company(&db).await?;
+    let item_codes = load_item_codes(&db, &invoice).await?;
+
+    let mut document = build_eta_document(&invoice, &client, &company, &item_codes)?;
+    if sign.unwrap_or(false) {
+        sign_document(&mut document, &LocalTestSigner)?;
+    }
+
+    // Same shape as the body of the ETA document submission endpoint
+    let json = serde_json::to_vec_pretty(&serde_json::json!({ "documents": [document] })).map_err(
+        |e| {
+            logger::log_error(
+                "Failed to serialize ETA document",
+                500,
+                Some(&e.to_string()),
+            );
+            ErrorResponse::new(500, "Failed to serialize ETA document", Some(e.to_string()))
+        },
+    )?;
+    save_document(&app, &path, &json)?;
+
+    logger::log_info(
+        &format!(
+            "Exported invoice {} as ETA document to {}",
+            invoice_id, path
+        ),
+        200,
+        None,
+    );
+    Ok(path)
+}
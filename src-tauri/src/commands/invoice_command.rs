@@ -1,30 +1,113 @@
 use std::collections::HashMap;
 
-use futures::StreamExt;
-use mongodb::bson::{self, doc, oid::ObjectId, DateTime, Document};
-use tauri::State;
+use futures::{StreamExt, TryStreamExt};
+use mongodb::{
+    bson::{self, doc, oid::ObjectId, Bson, DateTime, Document},
+    options::ReturnDocument,
+};
+use tauri::{AppHandle, State};
 use tokio::sync::Mutex;
 
 use crate::{
+    commands::{
+        company_command::load_company,
+        price_list_command::load_client_pricing,
+        stock_command::{emit_low_stock, low_stock_alert, record_stock_movements},
+    },
     db::MongoDbState,
+    documents::format_date,
+    pricing::{price_line, PriceOverride},
     schema::{
         client_schema::Client,
         collections::Collection,
         error::{AppResult, ErrorResponse},
-        invoice_schema::{Goods, Invoice, NewInvoice, Status},
+        invoice_revision_schema::InvoiceRevision,
+        invoice_schema::{
+            BillingAddress, Goods, Invoice, InvoicePage, InvoiceQuery, InvoiceSortField,
+            NewInvoice, SortOrder, Status,
+        },
         product_schema::Product,
+        stock_schema::{StockMovement, StockMovementReason, StockSourceKind},
     },
     utils::parse_object_id,
 };
 
+/// Rejects an invoice change that would raise the client's outstanding balance
+/// above their credit limit, unless the limit is explicitly overridden.
+fn check_credit_limit(
+    client: &Client,
+    balance_increase: f64,
+    override_limit: bool,
+) -> AppResult<()> {
+    let (Some(limit), Some(available)) = (client.credit_limit, client.available_credit()) else {
+        return Ok(());
+    };
+    if balance_increase <= 0.0 || balance_increase <= available {
+        return Ok(());
+    }
+    let details = format!(
+        "Credit limit: {:.2}, outstanding balance: {:.2}, available credit: {:.2}, requested: {:.2}",
+        limit, client.outstanding_balance, available, balance_increase
+    );
+    if override_limit {
+        logger::log_warn("Credit limit overridden", 200, Some(&details));
+        return Ok(());
+    }
+    logger::log_error("Credit limit exceeded", 400, Some(&details));
+    Err(ErrorResponse::new(
+        400,
+        "Credit limit exceeded",
+        Some(details),
+    ))
+}
+
+fn inactive_details(client: &Client) -> String {
+    let since = client
+        .deactivated_at
+        .map(|date| format!(" since {}", format_date(&date)))
+        .unwrap_or_default();
+    match &client.deactivation_reason {
+        Some(reason) => format!(
+            "Client {} is inactive{}: {}",
+            client.username, since, reason
+        ),
+        None => format!("Client {} is inactive{}", client.username, since),
+    }
+}
+
+/// Rejects new invoices, and changes that raise what the client owes, for an
+/// inactive client. Payments against their existing invoices are still
+/// accepted, with a warning.
+fn check_client_active(client: &Client, balance_increase: Option<f64>) -> AppResult<()> {
+    if client.is_active() {
+        return Ok(());
+    }
+    let details = inactive_details(client);
+    if balance_increase.is_some_and(|increase| increase <= 0.0) {
+        logger::log_warn(
+            "Invoice changed for an inactive client",
+            200,
+            Some(&details),
+        );
+        return Ok(());
+    }
+    logger::log_error("Client is inactive", 400, Some(&details));
+    Err(ErrorResponse::new(400, "Client is inactive", Some(details)))
+}
+
 #[tauri::command]
 pub async fn create_invoice(
     new_invoice: NewInvoice,
+    override_credit_limit: Option<bool>,
+    app: AppHandle,
     db: State<'_, Mutex<MongoDbState>>,
 ) -> AppResult<()> {
     let db = db.lock().await;
     let invoice_collection = db.get_collection::<Document>(Collection::Invoice);
-    let product_collection = db.get_collection::<Document>(Collection::Product);
+    let overrides_locked = load_company(&db)
+        .await?
+        .map(|company| company.lock_price_overrides)
+        .unwrap_or(false);
 
     // Step 1: Start a transaction
     let mut session = db.start_session().await?;
@@ -41,37 +124,133 @@ pub async fn create_invoice(
         )
     })?;
 
+    let client_collection = db.get_collection::<Client>(Collection::Client);
+
+    // Fetch the client document
+    let client = match client_collection
+        .find_one(doc! { "_id": new_invoice.client_id })
+        .session(&mut session)
+        .await
+    {
+        Ok(Some(doc)) => doc, // Successfully found the client document
+        Ok(None) => {
+            session.abort_transaction().await.ok();
+            logger::log_error(
+                &format!(
+                    "Failed to find client with ID: {} for Invoice ID: {}",
+                    new_invoice.client_id,
+                    new_invoice.id.unwrap_or_default()
+                ),
+                404,
+                None,
+            );
+            return Err(ErrorResponse::new(
+                404,
+                &format!("Client not found for ID: {}", new_invoice.client_id),
+                None,
+            ));
+        }
+        Err(e) => {
+            session.abort_transaction().await.ok();
+            logger::log_error(
+                &format!(
+                    "Failed to fetch client for Invoice ID: {}: {}",
+                    new_invoice.id.unwrap_or_default(),
+                    e
+                ),
+                500,
+                Some(&e.to_string()),
+            );
+            return Err(ErrorResponse::new(
+                500,
+                &format!(
+                    "Failed to fetch client for Invoice ID: {}",
+                    new_invoice.id.unwrap_or_default()
+                ),
+                Some(e.to_string()),
+            ));
+        }
+    };
+
+    // Inactive clients cannot be invoiced, so reject before anything is written
+    if let Err(e) = check_client_active(&client, None) {
+        session.abort_transaction().await.ok();
+        return Err(e);
+    }
+
+    let pricing = match load_client_pricing(&db, &mut session, &client).await {
+        Ok(pricing) => pricing,
+        Err(e) => {
+            session.abort_transaction().await.ok();
+            return Err(e);
+        }
+    };
+
+    let product_collection = db.get_collection::<Product>(Collection::Product);
+    let mut priced = Vec::with_capacity(new_invoice.goods.len());
+    // Quantity of each product across all lines, which may repeat a product
+    let mut requested: HashMap<ObjectId, i64> = HashMap::new();
     let mut total_price = 0.0;
 
-    // Step 2: Validate and update stock for goods
-    for goods in &new_invoice.goods {
-        let product_id = &goods.product_id;
+    // Step 2: Price the goods through the client's price lists and check the stock
+    for line in &new_invoice.goods {
+        let product_id = &line.product_id;
 
         // Fetch the product
-        let product_doc = match product_collection
+        let product = match product_collection
             .find_one(doc! { "_id": product_id })
             .session(&mut session)
             .await
         {
-            Ok(doc) => Ok(doc.unwrap()),
-            Err(e) => {
+            Ok(Some(product)) => Ok(product),
+            Ok(None) => {
                 session.abort_transaction().await.ok();
-                logger::log_error("Product not found", 404, Some(&e.to_string()));
+                logger::log_error(
+                    &format!("Product not found with ID: {}", product_id),
+                    404,
+                    None,
+                );
                 Err(ErrorResponse::new(
                     404,
                     "Product not found",
+                    Some(format!("No product matches the ID: {}", product_id)),
+                ))
+            }
+            Err(e) => {
+                session.abort_transaction().await.ok();
+                logger::log_error("Failed to fetch product", 500, Some(&e.to_string()));
+                Err(ErrorResponse::new(
+                    500,
+                    "Failed to fetch product",
                     Some(e.to_string()),
                 ))
             }
         }?;
 
-        let stock: i64 = product_doc
-            .get_i64("stock")
-            .map_err(|e| ErrorResponse::new(500, "Invalid stock data.", Some(e.to_string())))?;
-        if stock < goods.quantity as i64 {
+        let price_override = line.override_price.map(|price| PriceOverride {
+            price,
+            reason: line.override_reason.clone(),
+        });
+        let goods = match price_line(
+            &product,
+            line.quantity,
+            &pricing,
+            price_override,
+            overrides_locked,
+        ) {
+            Ok(goods) => goods,
+            Err(e) => {
+                session.abort_transaction().await.ok();
+                return Err(e);
+            }
+        };
+
+        let quantity = requested.entry(goods.product_id).or_default();
+        *quantity += goods.quantity;
+        if product.stock < *quantity {
             session.abort_transaction().await.ok();
             logger::log_error(
-                &format!("Insufficient stock for product: {}", goods.name).as_str(),
+                format!("Insufficient stock for product: {}", goods.name).as_str(),
                 400,
                 None,
             );
@@ -82,11 +261,45 @@ pub async fn create_invoice(
             ));
         }
 
-        // Update stock
+        // Calculate total price
+        total_price += goods.price * goods.quantity as f64;
+        priced.push((product, goods));
+    }
+    // Check if total price greater than total paid
+    if total_price < new_invoice.total_paid {
+        session.abort_transaction().await.ok();
+        logger::log_error(
+            "Total paid must be less than or equial total price",
+            400,
+            None,
+        );
+        return Err(ErrorResponse::new(
+            400,
+            "Total paid must be less than or equial total price",
+            None,
+        ));
+    }
+
+    // Calculate financial values
+    let total_paid = new_invoice.total_paid; // Amount paid for this invoice
+    let total_owed = total_price - total_paid; // Remaining balance for this invoice
+
+    if let Err(e) = check_credit_limit(&client, total_owed, override_credit_limit.unwrap_or(false))
+    {
+        session.abort_transaction().await.ok();
+        return Err(e);
+    }
+
+    // Step 3: Take the goods out of stock, only once the invoice is known to be accepted
+    let mut goods_lines = Vec::with_capacity(priced.len());
+    let mut sold: Vec<(ObjectId, i64)> = Vec::new();
+    let mut low_stock = Vec::new();
+    for (product, goods) in priced {
+        let product_id = &goods.product_id;
         let update_result = product_collection
             .update_one(
                 doc! { "_id": product_id },
-                doc! { "$inc": { "stock": -(goods.quantity as i32) } },
+                doc! { "$inc": { "stock": -goods.quantity } },
             )
             .session(&mut session)
             .await;
@@ -107,28 +320,25 @@ pub async fn create_invoice(
                 )) // Ensure consistent `Result` type
             }
         }?;
-        // Calculate total price
-        total_price += goods.price * goods.quantity as f64;
-    }
-    // Check if total price greater than total paid
-    if total_price < new_invoice.total_paid {
-        session.abort_transaction().await.ok();
-        logger::log_error(
-            "Total paid must be less than or equial total price",
-            400,
-            None,
-        );
-        return Err(ErrorResponse::new(
-            400,
-            "Total paid must be less than or equial total price",
-            None,
+        let taken = sold
+            .iter()
+            .filter(|(id, _)| id == product_id)
+            .map(|(_, quantity)| quantity)
+            .sum::<i64>();
+        low_stock.extend(low_stock_alert(
+            &product,
+            product.stock - taken,
+            product.stock - taken - goods.quantity,
         ));
+        sold.push((goods.product_id, goods.quantity));
+        goods_lines.push(goods);
     }
-    // Step 3: Prepare the invoice
+
+    // Step 4: Prepare the invoice
     let invoice = Invoice {
         id: None,
-        client_id: new_invoice.client_id.clone(),
-        goods: new_invoice.goods.clone(),
+        client_id: new_invoice.client_id,
+        goods: goods_lines,
         total_paid: new_invoice.total_paid,
         status: if new_invoice.total_paid >= total_price {
             Status::Paid
@@ -138,6 +348,7 @@ pub async fn create_invoice(
             Status::UnPaid
         },
         total_price,
+        billing_address: Some(BillingAddress::from_client(&client)),
         created_at: DateTime::now(),
         updated_at: DateTime::now(),
     };
@@ -172,57 +383,23 @@ pub async fn create_invoice(
             ));
         }
     };
-    let client_collection = db.get_collection::<Client>(Collection::Client);
-
-    // Fetch the client document
-    let client = match client_collection
-        .find_one(doc! { "_id": new_invoice.client_id })
-        .await
-    {
-        Ok(Some(doc)) => doc, // Successfully found the client document
-        Ok(None) => {
-            session.abort_transaction().await.ok();
-            logger::log_error(
-                &format!(
-                    "Failed to find client with ID: {} for Invoice ID: {}",
-                    new_invoice.client_id,
-                    new_invoice.id.unwrap_or_default()
-                ),
-                404,
-                None,
-            );
-            return Err(ErrorResponse::new(
-                404,
-                &format!("Client not found for ID: {}", new_invoice.client_id),
-                None,
-            ));
-        }
-        Err(e) => {
-            session.abort_transaction().await.ok();
-            logger::log_error(
-                &format!(
-                    "Failed to fetch client for Invoice ID: {}: {}",
-                    new_invoice.id.unwrap_or_default(),
-                    e
-                ),
-                500,
-                Some(&e.to_string()),
-            );
-            return Err(ErrorResponse::new(
-                500,
-                &format!(
-                    "Failed to fetch client for Invoice ID: {}",
-                    new_invoice.id.unwrap_or_default()
-                ),
-                Some(e.to_string()),
-            ));
-        }
-    };
-
-    // Calculate financial values
-    let total_paid = new_invoice.total_paid; // Amount paid for this invoice
-    let total_owed = total_price - total_paid; // Remaining balance for this invoice
-    let new_outstanding_balance = client.outstanding_balance + total_owed; // Client's updated outstanding balance
+    // Record what the invoice took from stock
+    let movements = sold
+        .into_iter()
+        .map(|(product_id, quantity)| {
+            StockMovement::new(
+                product_id,
+                -quantity,
+                StockMovementReason::Sale,
+                StockSourceKind::Invoice,
+                inserted_invoice_id,
+            )
+        })
+        .collect();
+    if let Err(e) = record_stock_movements(&db, &mut session, movements).await {
+        session.abort_transaction().await.ok();
+        return Err(e);
+    }
 
     // Update the client document with the new totals
     if let Err(e) = client_collection
@@ -232,10 +409,10 @@ pub async fn create_invoice(
                 "$inc": {
                     "totalOwed": total_owed,
                     "totalPaid": total_paid,
+                    "outstandingBalance": total_owed,
                 },
                 "$push": { "invoices": inserted_invoice_id },
                 "$set": {
-                    "outstanding_balance": new_outstanding_balance,
                     "updated_at": DateTime::now(),
                 }
             },
@@ -273,6 +450,7 @@ pub async fn create_invoice(
             Some(e.to_string()),
         )
     })?;
+    emit_low_stock(&app, low_stock);
     logger::log_info("Create new invoice", 201, None);
     Ok(())
 }
@@ -369,13 +547,76 @@ pub async fn get_invoice_by_id(
     Ok(invoice)
 }
 
+/// Pairs each submitted line with the existing line it replaces, by line ID first
+/// and then by product and price for lines stored before lines had IDs.
+fn match_lines<'a>(existing: &'a [Goods], submitted: &[Goods]) -> Vec<Option<&'a Goods>> {
+    let mut matched: Vec<Option<&Goods>> = vec![None; submitted.len()];
+    let mut unmatched: Vec<&Goods> = existing.iter().collect();
+    for (i, line) in submitted.iter().enumerate() {
+        if let Some(pos) = unmatched
+            .iter()
+            .position(|g| line.line_id.is_some() && g.line_id == line.line_id)
+        {
+            matched[i] = Some(unmatched.remove(pos));
+        }
+    }
+    for (i, line) in submitted.iter().enumerate() {
+        if matched[i].is_some() {
+            continue;
+        }
+        if let Some(pos) = unmatched.iter().position(|g| same_pricing(g, line)) {
+            matched[i] = Some(unmatched.remove(pos));
+        }
+    }
+    matched
+}
+
+fn same_pricing(existing: &Goods, line: &Goods) -> bool {
+    existing.product_id == line.product_id
+        && existing.price == line.price
+        && existing.price_overridden == line.price_overridden
+}
+
+/// The submitted line when only its quantity changed, keeping the price it was
+/// issued with. `None` when the line has to be priced again.
+fn keep_line(line: &Goods, existing: Option<&Goods>) -> Option<Goods> {
+    let existing = existing.filter(|g| same_pricing(g, line))?;
+    Some(Goods {
+        // Lines stored before IDs were kept get one now
+        line_id: existing.line_id.or_else(|| Some(ObjectId::new())),
+        quantity: line.quantity,
+        ..existing.clone()
+    })
+}
+
+/// Net stock demand per product: quantities on the updated invoice minus the
+/// existing one, so repeated products on several lines are all accounted for.
+fn stock_demand(existing: &[Goods], updated: &[Goods]) -> HashMap<ObjectId, i64> {
+    let mut demand: HashMap<ObjectId, i64> = HashMap::new();
+    for goods in existing {
+        *demand.entry(goods.product_id).or_default() -= goods.quantity;
+    }
+    for goods in updated {
+        *demand.entry(goods.product_id).or_default() += goods.quantity;
+    }
+    demand
+}
+
 #[tauri::command]
 pub async fn update_invoice_by_id(
     invoice_id: String,
     mut updated_invoice_doc: Document, // Accept MongoDB's Document type
+    changed_by: Option<String>,
+    reason: Option<String>,
+    override_credit_limit: Option<bool>,
+    app: AppHandle,
     db: State<'_, Mutex<MongoDbState>>,
 ) -> AppResult<()> {
     let db = db.lock().await;
+    let overrides_locked = load_company(&db)
+        .await?
+        .map(|company| company.lock_price_overrides)
+        .unwrap_or(false);
 
     // Start a session
     let mut session = db.start_session().await?;
@@ -430,85 +671,126 @@ pub async fn update_invoice_by_id(
         }
     };
 
-    // Compare goods
-    let existing_goods_map: HashMap<ObjectId, Goods> = existing_invoice
-        .goods
-        .iter()
-        .map(|g| (g.product_id.clone(), g.clone()))
-        .collect::<HashMap<_, _>>();
-    let updated_goods_map = updated_invoice
-        .goods
-        .iter()
-        .map(|g| (g.product_id.clone(), g.clone()))
-        .collect::<HashMap<_, _>>();
+    let matched = match_lines(&existing_invoice.goods, &updated_invoice.goods);
 
-    // Update product stock
-    for (product_id, existing_good) in &existing_goods_map {
-        if !updated_goods_map.contains_key(product_id) {
-            // Removed product: Increase stock
-            match product_collection
-                .find_one_and_update(
-                    doc! { "_id": product_id },
-                    doc! { "$inc": { "stock": existing_good.quantity } },
-                )
-                .session(&mut session)
-                .await
-            {
-                Ok(_) => (),
-                Err(e) => {
-                    session.abort_transaction().await.ok();
-                    logger::log_error("Faild to update invoice", 500, Some(&e.to_string()));
+    let client_collection = db.get_collection::<Client>(Collection::Client);
+    let client = match client_collection
+        .find_one(doc! { "_id": updated_invoice.client_id })
+        .session(&mut session)
+        .await
+    {
+        Ok(Some(client)) => client,
+        Ok(None) => {
+            session.abort_transaction().await.ok();
+            logger::log_error(
+                &format!("Client not found for ID: {}", updated_invoice.client_id),
+                404,
+                None,
+            );
+            return Err(ErrorResponse::new(
+                404,
+                &format!("Client not found for ID: {}", updated_invoice.client_id),
+                None,
+            ));
+        }
+        Err(e) => {
+            session.abort_transaction().await.ok();
+            logger::log_error("Failed to fetch client", 500, Some(&e.to_string()));
+            return Err(ErrorResponse::new(
+                500,
+                "Failed to fetch client",
+                Some(e.to_string()),
+            ));
+        }
+    };
+    let pricing = match load_client_pricing(&db, &mut session, &client).await {
+        Ok(pricing) => pricing,
+        Err(e) => {
+            session.abort_transaction().await.ok();
+            return Err(e);
+        }
+    };
 
-                    return Err(ErrorResponse::new(
-                        500,
-                        "Faild to update invoice",
-                        Some(e.to_string()),
-                    ))?;
-                }
-            }
+    // Re-price the goods from the client's price lists and the catalog so edited
+    // lines can't bypass pricing
+    let mut priced_goods = Vec::with_capacity(updated_invoice.goods.len());
+    for (line, existing_line) in updated_invoice.goods.iter().zip(matched) {
+        if line.quantity <= 0 {
+            session.abort_transaction().await.ok();
+            logger::log_error(
+                &format!("Invalid quantity for product: {}", line.name),
+                400,
+                None,
+            );
+            return Err(ErrorResponse::new(
+                400,
+                &format!("Invalid quantity for product: {}", line.name),
+                Some("Quantity must be greater than zero".to_string()),
+            ));
         }
-    }
 
-    for (product_id, updated_good) in &updated_goods_map {
-        if let Some(existing_good) = existing_goods_map.get(product_id) {
-            // Updated product: Adjust stock
-            let quantity_diff = updated_good.quantity - existing_good.quantity;
-            match product_collection
-                .find_one_and_update(
-                    doc! { "_id": product_id },
-                    doc! { "$inc": { "stock": -quantity_diff } },
-                )
-                .session(&mut session)
-                .await
-            {
-                Ok(_) => (),
-                Err(e) => {
-                    session.abort_transaction().await.ok();
-                    logger::log_error(&e.to_string(), 500, None);
+        if let Some(kept) = keep_line(line, existing_line) {
+            priced_goods.push(kept);
+            continue;
+        }
 
-                    return Err(ErrorResponse::new(500, &e.to_string(), None))?;
-                }
+        let product = match product_collection
+            .find_one(doc! { "_id": line.product_id })
+            .session(&mut session)
+            .await
+        {
+            Ok(Some(product)) => product,
+            Ok(None) => {
+                session.abort_transaction().await.ok();
+                logger::log_error(
+                    &format!("Product not found with ID: {}", line.product_id),
+                    404,
+                    None,
+                );
+                return Err(ErrorResponse::new(
+                    404,
+                    "Product not found",
+                    Some(format!("No product matches the ID: {}", line.product_id)),
+                ));
             }
-        } else {
-            // New product: Decrease stock
-            match product_collection
-                .find_one_and_update(
-                    doc! { "_id": product_id },
-                    doc! { "$inc": { "stock": -updated_good.quantity } },
-                )
-                .session(&mut session)
-                .await
-            {
-                Ok(_) => (),
-                Err(e) => {
-                    session.abort_transaction().await.ok();
-                    logger::log_error(&e.to_string(), 500, None);
+            Err(e) => {
+                session.abort_transaction().await.ok();
+                logger::log_error("Failed to fetch product", 500, Some(&e.to_string()));
+                return Err(ErrorResponse::new(
+                    500,
+                    "Failed to fetch product",
+                    Some(e.to_string()),
+                ));
+            }
+        };
 
-                    return Err(ErrorResponse::new(500, &e.to_string(), None))?;
-                }
+        // Only lines explicitly flagged as overridden may carry their own price
+        let price_override = line.price_overridden.then(|| PriceOverride {
+            price: line.price,
+            reason: line.override_reason.clone(),
+        });
+        match price_line(
+            &product,
+            line.quantity,
+            &pricing,
+            price_override,
+            overrides_locked,
+        ) {
+            Ok(goods) => priced_goods.push(Goods {
+                // Edited lines keep their identity
+                line_id: existing_line.and_then(|g| g.line_id).or(goods.line_id),
+                ..goods
+            }),
+            Err(e) => {
+                session.abort_transaction().await.ok();
+                return Err(e);
             }
         }
     }
+    let updated_invoice = Invoice {
+        goods: priced_goods,
+        ..updated_invoice
+    };
 
     // Calculate total price
     let total_price: f64 = updated_invoice
@@ -547,9 +829,10 @@ pub async fn update_invoice_by_id(
         status,
         updated_at: DateTime::now(),
         created_at: existing_invoice.created_at,
+        // Edits never change who the invoice was billed to
+        billing_address: existing_invoice.billing_address.clone(),
         ..updated_invoice
     };
-    let client_collection = db.get_collection::<Client>(Collection::Client);
     // Calculate financial adjustments
     let original_total_paid = existing_invoice.total_paid;
     let original_total_owed = existing_invoice.total_price - original_total_paid;
@@ -560,6 +843,99 @@ pub async fn update_invoice_by_id(
     // Adjust outstanding balance
     let outstanding_balance_adjustment = updated_total_owed - original_total_owed;
 
+    // Only an increase in what the client owes is checked against the credit limit
+    if let Err(e) =
+        check_client_active(&client, Some(outstanding_balance_adjustment)).and_then(|_| {
+            check_credit_limit(
+                &client,
+                outstanding_balance_adjustment,
+                override_credit_limit.unwrap_or(false),
+            )
+        })
+    {
+        session.abort_transaction().await.ok();
+        return Err(e);
+    }
+
+    // Update product stock
+    let mut movements = Vec::new();
+    let mut low_stock = Vec::new();
+    for (product_id, demand) in stock_demand(&existing_invoice.goods, &updated_invoice.goods) {
+        if demand == 0 {
+            continue;
+        }
+        // Taking more stock requires enough of it to be on hand
+        let filter = if demand > 0 {
+            doc! { "_id": product_id, "stock": { "$gte": demand } }
+        } else {
+            doc! { "_id": product_id }
+        };
+        let product = match product_collection
+            .find_one_and_update(filter, doc! { "$inc": { "stock": -demand } })
+            .return_document(ReturnDocument::After)
+            .session(&mut session)
+            .await
+        {
+            Ok(product) => product,
+            Err(e) => {
+                session.abort_transaction().await.ok();
+                logger::log_error("Faild to update invoice", 500, Some(&e.to_string()));
+
+                return Err(ErrorResponse::new(
+                    500,
+                    "Faild to update invoice",
+                    Some(e.to_string()),
+                ));
+            }
+        };
+        match product {
+            Some(product) => {
+                low_stock.extend(low_stock_alert(
+                    &product,
+                    product.stock + demand,
+                    product.stock,
+                ));
+            }
+            None if demand > 0 => {
+                session.abort_transaction().await.ok();
+                let name = updated_invoice
+                    .goods
+                    .iter()
+                    .find(|g| g.product_id == product_id)
+                    .map(|g| g.name.clone())
+                    .unwrap_or_else(|| product_id.to_hex());
+                logger::log_error(
+                    &format!("Insufficient stock for product: {}", name),
+                    400,
+                    None,
+                );
+                return Err(ErrorResponse::new(
+                    400,
+                    &format!("Insufficient stock for product: {}", name),
+                    None,
+                ));
+            }
+            None => {}
+        }
+        // More of a product on the invoice is a further sale, less of it comes back
+        let reason = if demand > 0 {
+            StockMovementReason::Sale
+        } else {
+            StockMovementReason::Return
+        };
+        movements.push(StockMovement::new(
+            product_id,
+            -demand,
+            reason,
+            StockSourceKind::Invoice,
+            id,
+        ));
+    }
+    if let Err(e) = record_stock_movements(&db, &mut session, movements).await {
+        session.abort_transaction().await.ok();
+        return Err(e);
+    }
+
     // Update the client's financial records directly
     if let Err(e) = client_collection
         .update_one(
@@ -568,7 +944,7 @@ pub async fn update_invoice_by_id(
                 "$inc": {
                     "totalOwed": updated_total_owed - original_total_owed,
                     "totalPaid": updated_total_paid - original_total_paid,
-                    "outstanding_balance": outstanding_balance_adjustment,
+                    "outstandingBalance": outstanding_balance_adjustment,
                 },
                 "$set": {
                     "updated_at": DateTime::now(),
@@ -595,6 +971,51 @@ pub async fn update_invoice_by_id(
         ));
     }
 
+    // Keep the version being replaced in the revision history
+    let revision_collection = db.get_collection::<InvoiceRevision>(Collection::InvoiceRevision);
+    let previous_revisions = match revision_collection
+        .count_documents(doc! { "invoiceId": id })
+        .session(&mut session)
+        .await
+    {
+        Ok(count) => count,
+        Err(e) => {
+            session.abort_transaction().await.ok();
+            logger::log_error(
+                "Failed to count invoice revisions",
+                500,
+                Some(&e.to_string()),
+            );
+            return Err(ErrorResponse::new(
+                500,
+                "Failed to count invoice revisions",
+                Some(e.to_string()),
+            ));
+        }
+    };
+    let revision = InvoiceRevision {
+        id: None,
+        invoice_id: id,
+        revision: previous_revisions as i64 + 1,
+        invoice: existing_invoice,
+        changed_by,
+        reason,
+        created_at: DateTime::now(),
+    };
+    if let Err(e) = revision_collection
+        .insert_one(revision)
+        .session(&mut session)
+        .await
+    {
+        session.abort_transaction().await.ok();
+        logger::log_error("Failed to save invoice revision", 500, Some(&e.to_string()));
+        return Err(ErrorResponse::new(
+            500,
+            "Failed to save invoice revision",
+            Some(e.to_string()),
+        ));
+    }
+
     // Update the invoice
     match invoice_collection
         .find_one_and_replace(doc! { "_id": id }, updated_invoice)
@@ -615,6 +1036,7 @@ pub async fn update_invoice_by_id(
         ErrorResponse::new(500, &format!("Failed to commit transaction: {}", e), None)
     })?;
 
+    emit_low_stock(&app, low_stock);
     logger::log_info(
         &format!("Updated invoice with ID: {}", invoice_id),
         200,
@@ -680,3 +1102,488 @@ pub async fn list_all_invoices_with_client_id(
 
     Ok(invoices)
 }
+
+const DEFAULT_PAGE_SIZE: u64 = 50;
+const MAX_PAGE_SIZE: u64 = 500;
+
+fn invalid_query(message: &str) -> ErrorResponse {
+    logger::log_error(message, 400, None);
+    ErrorResponse::new(400, message, None)
+}
+
+/// Builds the MongoDB filter for the query's filters, ignoring pagination.
+fn invoice_filter(query: &InvoiceQuery) -> AppResult<Document> {
+    let mut filter = Document::new();
+
+    if !query.status.is_empty() {
+        let statuses: Vec<String> = query.status.iter().map(|s| s.to_string()).collect();
+        filter.insert("status", doc! { "$in": statuses });
+    }
+    if let Some(client_id) = query.client_id {
+        filter.insert("clientId", client_id);
+    }
+
+    if let (Some(from), Some(to)) = (query.created_from, query.created_to) {
+        if from > to {
+            return Err(invalid_query("Start date must not be after the end date"));
+        }
+    }
+    if let (Some(min_total), Some(max_total)) = (query.min_total, query.max_total) {
+        if min_total > max_total {
+            return Err(invalid_query(
+                "Minimum total must not be greater than the maximum total",
+            ));
+        }
+    }
+
+    let mut created_at = Document::new();
+    if let Some(from) = query.created_from {
+        created_at.insert("$gte", from);
+    }
+    if let Some(to) = query.created_to {
+        created_at.insert("$lte", to);
+    }
+    if !created_at.is_empty() {
+        filter.insert("created_at", created_at);
+    }
+
+    let mut total_price = Document::new();
+    if let Some(min_total) = query.min_total {
+        total_price.insert("$gte", min_total);
+    }
+    if let Some(max_total) = query.max_total {
+        total_price.insert("$lte", max_total);
+    }
+    if !total_price.is_empty() {
+        filter.insert("totalPrice", total_price);
+    }
+
+    // Invoice numbers are the hex form of the invoice ID
+    if let Some(number) = query.number.as_deref().map(str::trim) {
+        if !number.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(invalid_query("Invoice number must be hexadecimal"));
+        }
+        if !number.is_empty() {
+            filter.insert(
+                "$expr",
+                doc! {
+                    "$regexMatch": {
+                        "input": { "$toString": "$_id" },
+                        "regex": number.to_lowercase(),
+                    }
+                },
+            );
+        }
+    }
+
+    Ok(filter)
+}
+
+/// The page size and the number of invoices to skip for page-based pagination.
+fn page_window(query: &InvoiceQuery) -> (u64, u64) {
+    let page_size = query
+        .page_size
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+    let skip = query
+        .page
+        .unwrap_or(1)
+        .saturating_sub(1)
+        .saturating_mul(page_size);
+    (page_size, skip)
+}
+
+/// The sort value of an invoice, as stored in MongoDB.
+fn sort_value(sort_by: InvoiceSortField, invoice: &Invoice) -> String {
+    match sort_by {
+        InvoiceSortField::CreatedAt => invoice.created_at.timestamp_millis().to_string(),
+        InvoiceSortField::TotalPrice => invoice.total_price.to_string(),
+        InvoiceSortField::TotalPaid => invoice.total_paid.to_string(),
+        InvoiceSortField::Status => invoice.status.to_string(),
+    }
+}
+
+/// Cursors are `<last invoice ID>:<last sort value>`.
+fn encode_cursor(sort_by: InvoiceSortField, invoice: &Invoice) -> Option<String> {
+    invoice
+        .id
+        .map(|id| format!("{}:{}", id.to_hex(), sort_value(sort_by, invoice)))
+}
+
+/// Turns a cursor into a filter matching the invoices that come after it.
+fn cursor_filter(query: &InvoiceQuery, cursor: &str) -> AppResult<Document> {
+    let invalid = || invalid_query("Invalid pagination cursor");
+    let (id, value) = cursor.split_once(':').ok_or_else(invalid)?;
+    let id = ObjectId::parse_str(id).map_err(|_| invalid())?;
+    let value = match query.sort_by {
+        InvoiceSortField::CreatedAt => {
+            Bson::DateTime(DateTime::from_millis(value.parse().map_err(|_| invalid())?))
+        }
+        InvoiceSortField::TotalPrice | InvoiceSortField::TotalPaid => {
+            Bson::Double(value.parse().map_err(|_| invalid())?)
+        }
+        InvoiceSortField::Status => Bson::String(value.to_string()),
+    };
+
+    let field = query.sort_by.as_str();
+    let op = match query.sort_order {
+        SortOrder::Asc => "$gt",
+        SortOrder::Desc => "$lt",
+    };
+    Ok(doc! {
+        "$or": [
+            { field: { op: value.clone() } },
+            { field: value, "_id": { op: id } },
+        ]
+    })
+}
+
+// Filtered, sorted and paginated invoice listing, run in the database
+#[tauri::command]
+pub async fn query_invoices(
+    query: InvoiceQuery,
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<InvoicePage> {
+    let db = db.lock().await;
+    let invoice_collection = db.get_collection::<Invoice>(Collection::Invoice);
+
+    let filter = invoice_filter(&query)?;
+    let (page_size, skip) = page_window(&query);
+    let direction = match query.sort_order {
+        SortOrder::Asc => 1,
+        SortOrder::Desc => -1,
+    };
+    // `_id` breaks ties so pages never overlap
+    let sort = doc! { query.sort_by.as_str(): direction, "_id": direction };
+
+    let total = invoice_collection
+        .count_documents(filter.clone())
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to count invoices", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to count invoices", Some(e.to_string()))
+        })?;
+
+    let (page_filter, skip) = match &query.cursor {
+        Some(cursor) => (doc! { "$and": [filter, cursor_filter(&query, cursor)?] }, 0),
+        // Pages past the last invoice are empty, and their offset may not fit a query
+        None if skip >= total && skip > 0 => {
+            return Ok(InvoicePage {
+                invoices: Vec::new(),
+                total,
+                page_size,
+                next_cursor: None,
+            });
+        }
+        None => (filter, skip),
+    };
+
+    let invoices: Vec<Invoice> = invoice_collection
+        .find(page_filter)
+        .sort(sort)
+        .skip(skip)
+        .limit(page_size as i64)
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to query invoices", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to query invoices", Some(e.to_string()))
+        })?
+        .try_collect()
+        .await
+        .map_err(|e| {
+            logger::log_error(
+                "Failed to retrieve a document from the cursor",
+                500,
+                Some(&e.to_string()),
+            );
+            ErrorResponse::new(
+                500,
+                "Failed to retrieve a document from the cursor",
+                Some(e.to_string()),
+            )
+        })?;
+
+    let next_cursor = if invoices.len() as u64 == page_size {
+        invoices
+            .last()
+            .and_then(|invoice| encode_cursor(query.sort_by, invoice))
+    } else {
+        None
+    };
+
+    logger::log_info(
+        &format!("Query invoices returned {} of {}", invoices.len(), total),
+        200,
+        None,
+    );
+    Ok(InvoicePage {
+        invoices,
+        total,
+        page_size,
+        next_cursor,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{date, invoice, object_id};
+
+    /// Line `n` selling `quantity` of product `product` at `price`.
+    fn line(n: u8, product: u8, price: f64, quantity: i64) -> Goods {
+        Goods {
+            line_id: Some(object_id(n)),
+            name: format!("Product {}", product),
+            price,
+            quantity,
+            product_id: object_id(product),
+            list_price: Some(price),
+            price_overridden: false,
+            override_reason: None,
+            price_list_id: None,
+        }
+    }
+
+    fn without_id(goods: Goods) -> Goods {
+        Goods {
+            line_id: None,
+            ..goods
+        }
+    }
+
+    #[test]
+    fn lines_match_by_id_before_product() {
+        let existing = vec![line(31, 11, 10.0, 1), line(32, 11, 10.0, 2)];
+        // Same product on both lines, submitted in the other order
+        let submitted = vec![line(32, 11, 10.0, 5), line(31, 11, 10.0, 1)];
+
+        let matched = match_lines(&existing, &submitted);
+        let ids: Vec<_> = matched.iter().map(|g| g.unwrap().line_id).collect();
+        assert_eq!(ids, [Some(object_id(32)), Some(object_id(31))]);
+    }
+
+    #[test]
+    fn legacy_lines_match_by_product_and_price() {
+        let existing = vec![
+            without_id(line(31, 11, 10.0, 1)),
+            without_id(line(32, 12, 20.0, 1)),
+        ];
+        let submitted = vec![
+            without_id(line(31, 12, 20.0, 3)),
+            without_id(line(32, 11, 15.0, 1)),
+        ];
+
+        let matched = match_lines(&existing, &submitted);
+        assert_eq!(matched[0].unwrap().product_id, object_id(12));
+        // A changed price is a different line
+        assert!(matched[1].is_none());
+    }
+
+    #[test]
+    fn added_and_removed_lines_are_unmatched() {
+        let existing = vec![line(31, 11, 10.0, 1), line(32, 12, 20.0, 1)];
+        let submitted = vec![line(31, 11, 10.0, 1), line(33, 13, 30.0, 2)];
+
+        let matched = match_lines(&existing, &submitted);
+        assert_eq!(matched[0].unwrap().line_id, Some(object_id(31)));
+        assert!(matched[1].is_none());
+    }
+
+    #[test]
+    fn unchanged_lines_keep_their_price() {
+        let existing = Goods {
+            list_price: Some(12.0),
+            price_list_id: Some(object_id(50)),
+            ..line(31, 11, 10.0, 1)
+        };
+        let submitted = line(31, 11, 10.0, 4);
+
+        let kept = keep_line(&submitted, Some(&existing)).unwrap();
+        assert_eq!(kept.quantity, 4);
+        assert_eq!(kept.price, 10.0);
+        assert_eq!(kept.list_price, Some(12.0));
+        assert_eq!(kept.price_list_id, Some(object_id(50)));
+    }
+
+    #[test]
+    fn changed_or_new_lines_are_priced_again() {
+        let existing = line(31, 11, 10.0, 1);
+        let overridden = Goods {
+            price_overridden: true,
+            ..line(31, 11, 10.0, 1)
+        };
+
+        assert!(keep_line(&line(31, 11, 9.0, 1), Some(&existing)).is_none());
+        assert!(keep_line(&overridden, Some(&existing)).is_none());
+        assert!(keep_line(&line(32, 11, 10.0, 1), None).is_none());
+    }
+
+    #[test]
+    fn kept_legacy_lines_get_an_id() {
+        let existing = without_id(line(31, 11, 10.0, 1));
+        let kept = keep_line(&existing, Some(&existing)).unwrap();
+        assert!(kept.line_id.is_some());
+    }
+
+    #[test]
+    fn stock_demand_nets_quantities_per_product() {
+        let existing = vec![
+            line(31, 11, 10.0, 2),
+            line(32, 11, 10.0, 3),
+            line(33, 12, 20.0, 4),
+            line(34, 13, 30.0, 1),
+        ];
+        let updated = vec![
+            // Product 11 moves between lines, 5 in total before and after
+            line(31, 11, 10.0, 4),
+            line(32, 11, 10.0, 1),
+            // Product 12 changes quantity, 13 is removed and 14 added
+            line(33, 12, 20.0, 6),
+            line(35, 14, 40.0, 2),
+        ];
+
+        let demand = stock_demand(&existing, &updated);
+        assert_eq!(demand[&object_id(11)], 0);
+        assert_eq!(demand[&object_id(12)], 2);
+        assert_eq!(demand[&object_id(13)], -1);
+        assert_eq!(demand[&object_id(14)], 2);
+    }
+
+    fn sample_invoice(total_price: f64) -> Invoice {
+        Invoice {
+            total_price,
+            ..invoice(
+                21,
+                vec![line(31, 11, total_price, 1)],
+                0.0,
+                date(2024, 3, 1),
+            )
+        }
+    }
+
+    #[test]
+    fn filter_lists_statuses_and_skips_empty_ones() {
+        assert!(invoice_filter(&InvoiceQuery::default()).unwrap().is_empty());
+
+        let query = InvoiceQuery {
+            status: vec![Status::UnPaid, Status::PartialPaid],
+            ..Default::default()
+        };
+        assert_eq!(
+            invoice_filter(&query).unwrap(),
+            doc! { "status": { "$in": ["UnPaid", "PartialPaid"] } }
+        );
+    }
+
+    #[test]
+    fn filter_builds_date_and_total_ranges() {
+        let query = InvoiceQuery {
+            created_from: Some(date(2024, 1, 1)),
+            min_total: Some(100.0),
+            max_total: Some(100.0),
+            ..Default::default()
+        };
+        assert_eq!(
+            invoice_filter(&query).unwrap(),
+            doc! {
+                "created_at": { "$gte": date(2024, 1, 1) },
+                "totalPrice": { "$gte": 100.0, "$lte": 100.0 },
+            }
+        );
+    }
+
+    #[test]
+    fn filter_rejects_inverted_ranges_and_bad_numbers() {
+        let dates = InvoiceQuery {
+            created_from: Some(date(2024, 2, 1)),
+            created_to: Some(date(2024, 1, 1)),
+            ..Default::default()
+        };
+        let totals = InvoiceQuery {
+            min_total: Some(200.0),
+            max_total: Some(100.0),
+            ..Default::default()
+        };
+        let number = InvoiceQuery {
+            number: Some("12xz".to_string()),
+            ..Default::default()
+        };
+        for query in [dates, totals, number] {
+            assert_eq!(invoice_filter(&query).unwrap_err().code, 400);
+        }
+    }
+
+    #[test]
+    fn page_size_is_clamped_and_skip_saturates() {
+        let window = |page, page_size| {
+            page_window(&InvoiceQuery {
+                page,
+                page_size,
+                ..Default::default()
+            })
+        };
+        assert_eq!(window(None, None), (DEFAULT_PAGE_SIZE, 0));
+        assert_eq!(window(Some(0), Some(0)), (1, 0));
+        assert_eq!(
+            window(Some(3), Some(10_000)),
+            (MAX_PAGE_SIZE, 2 * MAX_PAGE_SIZE)
+        );
+        assert_eq!(window(Some(u64::MAX), Some(50)), (50, u64::MAX));
+    }
+
+    #[test]
+    fn malformed_cursors_are_rejected() {
+        let query = InvoiceQuery {
+            sort_by: InvoiceSortField::TotalPrice,
+            ..Default::default()
+        };
+        let id = object_id(21).to_hex();
+        for cursor in [
+            "".to_string(),
+            "no-separator".to_string(),
+            "not-an-id:10".to_string(),
+            format!("{}:ten", id),
+        ] {
+            assert_eq!(cursor_filter(&query, &cursor).unwrap_err().code, 400);
+        }
+    }
+
+    #[test]
+    fn cursor_breaks_ties_by_id() {
+        let query = InvoiceQuery {
+            sort_by: InvoiceSortField::TotalPrice,
+            sort_order: SortOrder::Asc,
+            ..Default::default()
+        };
+        let cursor = encode_cursor(query.sort_by, &sample_invoice(100.0)).unwrap();
+        assert_eq!(cursor, format!("{}:100", object_id(21).to_hex()));
+
+        // Invoices with the same total come after it only when their ID is greater
+        assert_eq!(
+            cursor_filter(&query, &cursor).unwrap(),
+            doc! {
+                "$or": [
+                    { "totalPrice": { "$gt": 100.0 } },
+                    { "totalPrice": 100.0, "_id": { "$gt": object_id(21) } },
+                ]
+            }
+        );
+    }
+
+    #[test]
+    fn created_at_cursor_round_trips() {
+        let query = InvoiceQuery::default();
+        let invoice = sample_invoice(100.0);
+        let cursor = encode_cursor(query.sort_by, &invoice).unwrap();
+
+        assert_eq!(
+            cursor_filter(&query, &cursor).unwrap(),
+            doc! {
+                "$or": [
+                    { "created_at": { "$lt": invoice.created_at } },
+                    { "created_at": invoice.created_at, "_id": { "$lt": object_id(21) } },
+                ]
+            }
+        );
+    }
+}
@@ -0,0 +1,146 @@
+use futures::TryStreamExt;
+use mongodb::bson::{doc, oid::ObjectId};
+use tauri::State;
+use tokio::sync::Mutex;
+
+use crate::{
+    db::MongoDbState,
+    diff::diff_invoices,
+    schema::{
+        collections::Collection,
+        error::{AppResult, ErrorResponse},
+        invoice_revision_schema::{FieldChange, InvoiceRevision},
+        invoice_schema::Invoice,
+    },
+    utils::parse_object_id,
+};
+
+async fn find_revision(
+    db: &MongoDbState,
+    invoice_id: ObjectId,
+    revision: i64,
+) -> AppResult<Invoice> {
+    let collection = db.get_collection::<InvoiceRevision>(Collection::InvoiceRevision);
+    let revision_doc = collection
+        .find_one(doc! { "invoiceId": invoice_id, "revision": revision })
+        .await
+        .map_err(|e| {
+            logger::log_error(
+                "Failed to fetch invoice revision",
+                500,
+                Some(&e.to_string()),
+            );
+            ErrorResponse::new(500, "Failed to fetch invoice revision", Some(e.to_string()))
+        })?
+        .ok_or_else(|| {
+            logger::log_error(
+                &format!(
+                    "Revision {} not found for invoice: {}",
+                    revision, invoice_id
+                ),
+                404,
+                None,
+            );
+            ErrorResponse::new(404, &format!("Revision {} not found", revision), None)
+        })?;
+    Ok(revision_doc.invoice)
+}
+
+async fn find_current_invoice(db: &MongoDbState, invoice_id: ObjectId) -> AppResult<Invoice> {
+    let collection = db.get_collection::<Invoice>(Collection::Invoice);
+    collection
+        .find_one(doc! { "_id": invoice_id })
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to fetch invoice", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to fetch invoice", Some(e.to_string()))
+        })?
+        .ok_or_else(|| {
+            logger::log_error(&format!("No Invoice wiht this ID: {invoice_id}"), 404, None);
+            ErrorResponse::new(404, "Invoice not found", None)
+        })
+}
+
+// List the saved versions of an invoice, oldest first
+#[tauri::command]
+pub async fn list_invoice_revisions(
+    invoice_id: String,
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<Vec<InvoiceRevision>> {
+    let db = db.lock().await;
+    let collection = db.get_collection::<InvoiceRevision>(Collection::InvoiceRevision);
+    let id = parse_object_id(&invoice_id, "Invoice")?;
+
+    let revisions: Vec<InvoiceRevision> = collection
+        .find(doc! { "invoiceId": id })
+        .sort(doc! { "revision": 1 })
+        .await
+        .map_err(|e| {
+            logger::log_error(
+                "Failed to fetch invoice revisions",
+                500,
+                Some(&e.to_string()),
+            );
+            ErrorResponse::new(
+                500,
+                "Failed to fetch invoice revisions",
+                Some(e.to_string()),
+            )
+        })?
+        .try_collect()
+        .await
+        .map_err(|e| {
+            logger::log_error(
+                "Failed to parse invoice revision data",
+                500,
+                Some(&e.to_string()),
+            );
+            ErrorResponse::new(
+                500,
+                "Failed to parse invoice revision data",
+                Some(e.to_string()),
+            )
+        })?;
+
+    logger::log_info(
+        &format!(
+            "List {} revisions for invoice ID: {}",
+            revisions.len(),
+            invoice_id
+        ),
+        200,
+        None,
+    );
+    Ok(revisions)
+}
+
+// Compare two revisions of an invoice. Without `to_revision` the current invoice is used.
+#[tauri::command]
+pub async fn diff_invoice_revisions(
+    invoice_id: String,
+    from_revision: i64,
+    to_revision: Option<i64>,
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<Vec<FieldChange>> {
+    let db = db.lock().await;
+    let id = parse_object_id(&invoice_id, "Invoice")?;
+
+    let before = find_revision(&db, id, from_revision).await?;
+    let after = match to_revision {
+        Some(revision) => find_revision(&db, id, revision).await?,
+        None => find_current_invoice(&db, id).await?,
+    };
+
+    let changes = diff_invoices(&before, &after)?;
+    logger::log_info(
+        &format!(
+            "Diff invoice {} from revision {} with {} changes",
+            invoice_id,
+            from_revision,
+            changes.len()
+        ),
+        200,
+        None,
+    );
+    Ok(changes)
+}
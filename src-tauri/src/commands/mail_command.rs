@@ -0,0 +1,386 @@
+use futures::TryStreamExt;
+use mongodb::{
+    bson::{self, doc, DateTime as MongoDateTime, Document},
+    options::ReturnDocument,
+};
+use tauri::{AppHandle, State};
+use tokio::sync::Mutex;
+
+use crate::{
+    commands::{
+        company_command::load_company,
+        document_command::{load_invoice_with_client, render_with_template},
+        statement_command::{load_client, load_statement},
+    },
+    db::MongoDbState,
+    documents::{
+        statement::{build_statement_pdf, StatementMailData},
+        templates::TemplateData,
+    },
+    mail::{encrypt_password, render_mail_text, send_mail, MailAttachment, OutgoingMail},
+    schema::{
+        client_schema::Client,
+        collections::Collection,
+        error::{AppResult, ErrorResponse},
+        invoice_schema::BillingAddress,
+        mail_schema::{DeliveryKind, DeliveryStatus, EmailDelivery, MailSettings, NewMailSettings},
+    },
+    utils::parse_object_id,
+};
+
+/// Loads the mail settings, failing when they have not been saved yet.
+pub async fn require_mail_settings(db: &MongoDbState) -> AppResult<MailSettings> {
+    let collection = db.get_collection::<MailSettings>(Collection::MailSettings);
+    collection
+        .find_one(doc! {})
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to fetch mail settings", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to fetch mail settings", Some(e.to_string()))
+        })?
+        .ok_or_else(|| {
+            logger::log_error("Mail settings not configured", 400, None);
+            ErrorResponse::new(
+                400,
+                "Mail settings not configured",
+                Some("Save the SMTP server settings before sending mail".to_string()),
+            )
+        })
+}
+
+/// Saves a delivery attempt to the log.
+pub async fn record_delivery(db: &MongoDbState, delivery: &mut EmailDelivery) -> AppResult<()> {
+    let collection = db.get_collection::<EmailDelivery>(Collection::EmailDelivery);
+    let result = collection.insert_one(&*delivery).await.map_err(|e| {
+        logger::log_error("Failed to record email delivery", 500, Some(&e.to_string()));
+        ErrorResponse::new(500, "Failed to record email delivery", Some(e.to_string()))
+    })?;
+    delivery.id = result.inserted_id.as_object_id();
+    Ok(())
+}
+
+// The password is never sent back to the frontend
+#[tauri::command]
+pub async fn get_mail_settings(db: State<'_, Mutex<MongoDbState>>) -> AppResult<MailSettings> {
+    let db = db.lock().await;
+
+    let mut settings = require_mail_settings(&db).await?;
+    settings.password = None;
+    logger::log_info("Return mail settings", 200, None);
+
+    Ok(settings)
+}
+
+// Create or replace the mail settings document. A missing password keeps the saved one.
+#[tauri::command]
+pub async fn update_mail_settings(
+    mut settings: NewMailSettings,
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<MailSettings> {
+    let db = db.lock().await;
+    let collection = db.get_collection::<Document>(Collection::MailSettings);
+
+    if let Some(password) = settings.password.as_mut() {
+        *password = encrypt_password(password)?;
+    }
+
+    let mut fields = bson::to_document(&settings).map_err(|e| {
+        logger::log_error(
+            "Failed to serialize mail settings",
+            400,
+            Some(&e.to_string()),
+        );
+        ErrorResponse::new(
+            400,
+            "Failed to serialize mail settings",
+            Some(e.to_string()),
+        )
+    })?;
+    fields.insert("updated_at", MongoDateTime::now());
+
+    let updated_doc = collection
+        .find_one_and_update(doc! {}, doc! { "$set": fields })
+        .upsert(true)
+        .return_document(ReturnDocument::After)
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to update mail settings", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to update mail settings", Some(e.to_string()))
+        })?
+        .ok_or_else(|| ErrorResponse::new(500, "Failed to update mail settings", None))?;
+
+    let mut settings = bson::from_document::<MailSettings>(updated_doc).map_err(|e| {
+        logger::log_error(
+            "Failed to deserialize mail settings",
+            500,
+            Some(&e.to_string()),
+        );
+        ErrorResponse::new(
+            500,
+            "Failed to deserialize mail settings",
+            Some(e.to_string()),
+        )
+    })?;
+    settings.password = None;
+    logger::log_info("Updated mail settings", 200, None);
+
+    Ok(settings)
+}
+
+/// Records the outcome of sending `delivery` and passes any error on.
+async fn finish_delivery(
+    db: &MongoDbState,
+    mut delivery: EmailDelivery,
+    result: AppResult<String>,
+) -> AppResult<EmailDelivery> {
+    match &result {
+        Ok(response) => delivery.response = Some(response.clone()),
+        Err(e) => {
+            delivery.status = DeliveryStatus::Failed;
+            delivery.error = Some(e.details.clone().unwrap_or_else(|| e.message.clone()));
+        }
+    }
+    record_delivery(db, &mut delivery).await?;
+    result?;
+    Ok(delivery)
+}
+
+/// The addresses given by the user, or the client's billing email.
+fn recipients(to: Option<Vec<String>>, client: &Client, document: &str) -> AppResult<Vec<String>> {
+    to.filter(|to| !to.is_empty())
+        .or_else(|| {
+            BillingAddress::from_client(client)
+                .email
+                .map(|email| vec![email])
+        })
+        .ok_or_else(|| {
+            logger::log_error(&format!("No recipient for {}", document), 400, None);
+            ErrorResponse::new(
+                400,
+                &format!("No recipient for {}", document),
+                Some("The client has no email address".to_string()),
+            )
+        })
+}
+
+// Email an invoice as a PDF attachment. Without `to` it goes to the client's billing
+// contact, or to the client's own email address when no contact has one.
+#[tauri::command]
+pub async fn send_invoice_email(
+    invoice_id: String,
+    to: Option<Vec<String>>,
+    template_id: Option<String>,
+    app: AppHandle,
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<EmailDelivery> {
+    let id = parse_object_id(&invoice_id, "Invoice")?;
+
+    // The database stays unlocked while talking to the SMTP server
+    let (settings, mail, delivery) = {
+        let db = db.lock().await;
+        let settings = require_mail_settings(&db).await?;
+        let (invoice, client) = load_invoice_with_client(&db, id).await?;
+        let company = load_company(&db).await?;
+        let to = recipients(to, &client, &format!("invoice {}", invoice_id))?;
+
+        let data = TemplateData::new(&invoice, &client, company.as_ref());
+        let subject = render_mail_text(&settings.invoice_subject, &data)?;
+        let body = render_mail_text(&settings.invoice_body, &data)?;
+        let pdf = render_with_template(&app, template_id.as_deref(), &data)?;
+
+        let mail = OutgoingMail {
+            to: to.clone(),
+            subject: subject.clone(),
+            body,
+            attachments: vec![MailAttachment {
+                filename: format!("invoice-{}.pdf", data.invoice.number),
+                content_type: "application/pdf".to_string(),
+                data: pdf,
+            }],
+        };
+        let delivery = EmailDelivery {
+            id: None,
+            kind: DeliveryKind::Invoice,
+            invoice_id: Some(id),
+            client_id: invoice.client_id,
+            to,
+            subject,
+            status: DeliveryStatus::Sent,
+            response: None,
+            error: None,
+            created_at: MongoDateTime::now(),
+        };
+        (settings, mail, delivery)
+    };
+    let result = send_mail(&settings, mail).await;
+    let delivery = finish_delivery(&*db.lock().await, delivery, result).await?;
+
+    logger::log_info(
+        &format!("Sent invoice {} to {}", invoice_id, delivery.to.join(", ")),
+        200,
+        None,
+    );
+    Ok(delivery)
+}
+
+// Email a client statement as a PDF attachment, to `to` or the client's billing email
+#[tauri::command]
+pub async fn send_statement_email(
+    client_id: String,
+    from: Option<MongoDateTime>,
+    to_date: Option<MongoDateTime>,
+    to: Option<Vec<String>>,
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<EmailDelivery> {
+    let id = parse_object_id(&client_id, "Client")?;
+
+    // The database stays unlocked while talking to the SMTP server
+    let (settings, mail, delivery) = {
+        let db = db.lock().await;
+        let settings = require_mail_settings(&db).await?;
+        let client = load_client(&db, id).await?;
+        let statement = load_statement(&db, id, from, to_date).await?;
+        let company = load_company(&db).await?;
+        let to = recipients(to, &client, &format!("statement of client {}", client_id))?;
+
+        let data = StatementMailData::new(&statement, &client, company.as_ref());
+        let subject = render_mail_text(&settings.statement_subject, &data)?;
+        let body = render_mail_text(&settings.statement_body, &data)?;
+        let pdf = build_statement_pdf(&statement, company.as_ref())?;
+
+        let mail = OutgoingMail {
+            to: to.clone(),
+            subject: subject.clone(),
+            body,
+            attachments: vec![MailAttachment {
+                filename: "statement.pdf".to_string(),
+                content_type: "application/pdf".to_string(),
+                data: pdf,
+            }],
+        };
+        let delivery = EmailDelivery {
+            id: None,
+            kind: DeliveryKind::Statement,
+            invoice_id: None,
+            client_id: id,
+            to,
+            subject,
+            status: DeliveryStatus::Sent,
+            response: None,
+            error: None,
+            created_at: MongoDateTime::now(),
+        };
+        (settings, mail, delivery)
+    };
+    let result = send_mail(&settings, mail).await;
+    let delivery = finish_delivery(&*db.lock().await, delivery, result).await?;
+
+    logger::log_info(
+        &format!(
+            "Sent statement of client {} to {}",
+            client_id,
+            delivery.to.join(", ")
+        ),
+        200,
+        None,
+    );
+    Ok(delivery)
+}
+
+// List every attempt to email an invoice, newest first
+#[tauri::command]
+pub async fn list_invoice_deliveries(
+    invoice_id: String,
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<Vec<EmailDelivery>> {
+    let db = db.lock().await;
+    let collection = db.get_collection::<EmailDelivery>(Collection::EmailDelivery);
+    let id = parse_object_id(&invoice_id, "Invoice")?;
+
+    let deliveries: Vec<EmailDelivery> = collection
+        .find(doc! { "invoiceId": id })
+        .sort(doc! { "created_at": -1 })
+        .await
+        .map_err(|e| {
+            logger::log_error(
+                "Failed to fetch email deliveries",
+                500,
+                Some(&e.to_string()),
+            );
+            ErrorResponse::new(500, "Failed to fetch email deliveries", Some(e.to_string()))
+        })?
+        .try_collect()
+        .await
+        .map_err(|e| {
+            logger::log_error(
+                "Failed to parse email delivery data",
+                500,
+                Some(&e.to_string()),
+            );
+            ErrorResponse::new(
+                500,
+                "Failed to parse email delivery data",
+                Some(e.to_string()),
+            )
+        })?;
+
+    logger::log_info(
+        &format!(
+            "List {} deliveries for invoice ID: {}",
+            deliveries.len(),
+            invoice_id
+        ),
+        200,
+        None,
+    );
+    Ok(deliveries)
+}
+
+// List every invoice and statement emailed to a client, newest first
+#[tauri::command]
+pub async fn list_client_deliveries(
+    client_id: String,
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<Vec<EmailDelivery>> {
+    let db = db.lock().await;
+    let collection = db.get_collection::<EmailDelivery>(Collection::EmailDelivery);
+    let id = parse_object_id(&client_id, "Client")?;
+
+    let deliveries: Vec<EmailDelivery> = collection
+        .find(doc! { "clientId": id })
+        .sort(doc! { "created_at": -1 })
+        .await
+        .map_err(|e| {
+            logger::log_error(
+                "Failed to fetch email deliveries",
+                500,
+                Some(&e.to_string()),
+            );
+            ErrorResponse::new(500, "Failed to fetch email deliveries", Some(e.to_string()))
+        })?
+        .try_collect()
+        .await
+        .map_err(|e| {
+            logger::log_error(
+                "Failed to parse email delivery data",
+                500,
+                Some(&e.to_string()),
+            );
+            ErrorResponse::new(
+                500,
+                "Failed to parse email delivery data",
+                Some(e.to_string()),
+            )
+        })?;
+
+    logger::log_info(
+        &format!(
+            "List {} deliveries for client ID: {}",
+            deliveries.len(),
+            client_id
+        ),
+        200,
+        None,
+    );
+    Ok(deliveries)
+}
@@ -1,3 +1,18 @@
+pub mod audit_command;
 pub mod client_command;
+pub mod client_csv_command;
+pub mod client_note_command;
+pub mod company_command;
+pub mod document_command;
 pub mod invoice_command;
+pub mod invoice_revision_command;
+pub mod mail_command;
+pub mod payable_command;
+pub mod price_list_command;
 pub mod product_command;
+pub mod purchase_order_command;
+pub mod report_command;
+pub mod statement_command;
+pub mod stock_command;
+pub mod supplier_command;
+pub mod template_command;
@@ -0,0 +1,630 @@
+use futures::TryStreamExt;
+use mongodb::{
+    bson::{doc, oid::ObjectId, DateTime as MongoDateTime, Document},
+    ClientSession,
+};
+use tauri::State;
+use tokio::sync::Mutex;
+
+use crate::{
+    db::MongoDbState,
+    schema::{
+        collections::Collection,
+        error::{AppResult, ErrorResponse, FieldError},
+        invoice_schema::Status,
+        payable_schema::{NewSupplierBill, NewSupplierPayment, SupplierBill, SupplierPayment},
+        purchase_order_schema::{PurchaseOrder, PurchaseOrderStatus},
+        supplier_schema::Supplier,
+    },
+    utils::parse_object_id,
+};
+
+// Balances below half a cent are rounding noise, not money owed
+const MIN_OPEN_BALANCE: f64 = 0.005;
+
+fn bill_status(total_amount: f64, total_paid: f64) -> Status {
+    if total_paid >= total_amount - MIN_OPEN_BALANCE {
+        Status::Paid
+    } else if total_paid > 0.0 {
+        Status::PartialPaid
+    } else {
+        Status::UnPaid
+    }
+}
+
+fn start_transaction_error(e: mongodb::error::Error) -> ErrorResponse {
+    logger::log_error(
+        "Failed to start MongoDB transaction.",
+        500,
+        Some(&e.to_string()),
+    );
+    ErrorResponse::new(
+        500,
+        "Failed to start MongoDB transaction.",
+        Some(e.to_string()),
+    )
+}
+
+fn commit_transaction_error(e: mongodb::error::Error) -> ErrorResponse {
+    logger::log_error(
+        "Failed to commit MongoDB transaction.",
+        500,
+        Some(&e.to_string()),
+    );
+    ErrorResponse::new(
+        500,
+        "Failed to commit MongoDB transaction.",
+        Some(e.to_string()),
+    )
+}
+
+/// The value of the stock received on `order` that `bills` don't cover yet.
+fn unbilled_amount(order: &PurchaseOrder, bills: &[SupplierBill]) -> f64 {
+    let received: f64 = order
+        .receipts
+        .iter()
+        .flat_map(|receipt| &receipt.lines)
+        .map(|line| line.unit_cost.unwrap_or(0.0) * line.quantity as f64)
+        .sum();
+    received - bills.iter().map(|bill| bill.total_amount).sum::<f64>()
+}
+
+async fn load_order_bills(
+    db: &MongoDbState,
+    session: &mut ClientSession,
+    order: &PurchaseOrder,
+) -> AppResult<Vec<SupplierBill>> {
+    db.get_collection::<SupplierBill>(Collection::SupplierBill)
+        .find(doc! { "purchaseOrderId": order.id })
+        .session(&mut *session)
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to fetch supplier bills", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to fetch supplier bills", Some(e.to_string()))
+        })?
+        .stream(session)
+        .try_collect()
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to parse supplier bills", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to parse supplier bills", Some(e.to_string()))
+        })
+}
+
+async fn create_bill_in_session(
+    db: &MongoDbState,
+    session: &mut ClientSession,
+    bill: NewSupplierBill,
+) -> AppResult<SupplierBill> {
+    let supplier_count = db
+        .get_collection::<Document>(Collection::Supplier)
+        .count_documents(doc! { "_id": bill.supplier_id })
+        .session(&mut *session)
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to fetch supplier", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to fetch supplier", Some(e.to_string()))
+        })?;
+    if supplier_count == 0 {
+        logger::log_error(
+            &format!("Supplier not found for ID: {}", bill.supplier_id),
+            404,
+            None,
+        );
+        return Err(ErrorResponse::new(404, "Supplier not found", None));
+    }
+
+    let bill_collection = db.get_collection::<SupplierBill>(Collection::SupplierBill);
+    let duplicates = bill_collection
+        .count_documents(
+            doc! { "supplierId": bill.supplier_id, "reference": bill.reference.trim() },
+        )
+        .session(&mut *session)
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to fetch supplier bills", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to fetch supplier bills", Some(e.to_string()))
+        })?;
+    if duplicates > 0 {
+        logger::log_error(
+            &format!("Supplier bill {} already recorded", bill.reference),
+            409,
+            None,
+        );
+        return Err(ErrorResponse::new(
+            409,
+            "A bill with this reference is already recorded for the supplier",
+            None,
+        ));
+    }
+
+    let mut total_amount = bill.total_amount;
+    let from_order = total_amount.is_none() && bill.purchase_order_id.is_some();
+    if let Some(order_id) = bill.purchase_order_id {
+        let order = db
+            .get_collection::<PurchaseOrder>(Collection::PurchaseOrder)
+            .find_one(doc! { "_id": order_id })
+            .session(&mut *session)
+            .await
+            .map_err(|e| {
+                logger::log_error("Failed to fetch purchase order", 500, Some(&e.to_string()));
+                ErrorResponse::new(500, "Failed to fetch purchase order", Some(e.to_string()))
+            })?
+            .ok_or_else(|| {
+                logger::log_error(
+                    &format!("Purchase order not found for ID: {}", order_id),
+                    404,
+                    None,
+                );
+                ErrorResponse::new(404, "Purchase order not found", None)
+            })?;
+        if order.supplier_id != bill.supplier_id {
+            logger::log_error("Purchase order of another supplier", 400, None);
+            return Err(ErrorResponse::validation(
+                "Invalid supplier bill",
+                vec![FieldError {
+                    field: "purchaseOrderId".to_string(),
+                    message: "The purchase order is for another supplier".to_string(),
+                }],
+            ));
+        }
+        if matches!(
+            order.status,
+            PurchaseOrderStatus::Draft | PurchaseOrderStatus::Cancelled
+        ) {
+            let details = format!("The order is {:?}", order.status);
+            logger::log_error("Bill for an unplaced order", 409, Some(&details));
+            return Err(ErrorResponse::new(
+                409,
+                "Only placed purchase orders can be billed",
+                Some(details),
+            ));
+        }
+        if total_amount.is_none() {
+            let bills = load_order_bills(db, session, &order).await?;
+            total_amount = Some(unbilled_amount(&order, &bills));
+        }
+    }
+
+    let total_amount = match total_amount {
+        Some(amount) if amount.is_finite() && amount >= MIN_OPEN_BALANCE => amount,
+        amount => {
+            let message = match amount {
+                Some(_) if from_order => "Everything received on the order is already billed",
+                Some(_) => "Amount must be more than zero",
+                None => "Amount is required",
+            };
+            logger::log_error("Invalid supplier bill", 400, Some(message));
+            return Err(ErrorResponse::validation(
+                "Invalid supplier bill",
+                vec![FieldError {
+                    field: "totalAmount".to_string(),
+                    message: message.to_string(),
+                }],
+            ));
+        }
+    };
+
+    let mut supplier_bill = SupplierBill {
+        id: None,
+        supplier_id: bill.supplier_id,
+        purchase_order_id: bill.purchase_order_id,
+        reference: bill.reference.trim().to_string(),
+        description: bill.description,
+        total_amount,
+        total_paid: 0.0,
+        status: Status::UnPaid,
+        bill_date: bill.bill_date.unwrap_or_else(MongoDateTime::now),
+        due_date: bill.due_date,
+        created_at: MongoDateTime::now(),
+        updated_at: MongoDateTime::now(),
+    };
+    let result = bill_collection
+        .insert_one(&supplier_bill)
+        .session(&mut *session)
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to save supplier bill", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to save supplier bill", Some(e.to_string()))
+        })?;
+    let bill_id = result.inserted_id.as_object_id().ok_or_else(|| {
+        logger::log_error("Failed to retrieve inserted bill ID", 500, None);
+        ErrorResponse::new(500, "Failed to retrieve inserted bill ID", None)
+    })?;
+    supplier_bill.id = Some(bill_id);
+
+    db.get_collection::<Supplier>(Collection::Supplier)
+        .update_one(
+            doc! { "_id": supplier_bill.supplier_id },
+            doc! {
+                "$inc": {
+                    "totalOwed": total_amount,
+                    "outstandingBalance": total_amount,
+                },
+                "$push": { "bills": bill_id },
+                "$set": { "updated_at": MongoDateTime::now() },
+            },
+        )
+        .session(&mut *session)
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to update supplier", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to update supplier", Some(e.to_string()))
+        })?;
+    Ok(supplier_bill)
+}
+
+// Record a bill received from a supplier and add it to what we owe them
+#[tauri::command]
+pub async fn create_supplier_bill(
+    bill: NewSupplierBill,
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<SupplierBill> {
+    let db = db.lock().await;
+
+    if bill.reference.trim().is_empty() {
+        logger::log_error("Supplier bill without a reference", 400, None);
+        return Err(ErrorResponse::validation(
+            "Invalid supplier bill",
+            vec![FieldError {
+                field: "reference".to_string(),
+                message: "Bill reference is required".to_string(),
+            }],
+        ));
+    }
+
+    let mut session = db.start_session().await?;
+    session
+        .start_transaction()
+        .await
+        .map_err(start_transaction_error)?;
+
+    let bill = match create_bill_in_session(&db, &mut session, bill).await {
+        Ok(bill) => bill,
+        Err(e) => {
+            session.abort_transaction().await.ok();
+            return Err(e);
+        }
+    };
+    session
+        .commit_transaction()
+        .await
+        .map_err(commit_transaction_error)?;
+
+    logger::log_info(
+        &format!(
+            "Recorded bill {} of {:.2} from supplier {}",
+            bill.reference, bill.total_amount, bill.supplier_id
+        ),
+        201,
+        None,
+    );
+    Ok(bill)
+}
+
+// List supplier bills, oldest first, optionally for one supplier or in one status
+#[tauri::command]
+pub async fn list_supplier_bills(
+    supplier_id: Option<String>,
+    status: Option<Status>,
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<Vec<SupplierBill>> {
+    let db = db.lock().await;
+
+    let mut filter = doc! {};
+    if let Some(supplier_id) = supplier_id {
+        filter.insert("supplierId", parse_object_id(&supplier_id, "Supplier")?);
+    }
+    if let Some(status) = status {
+        filter.insert("status", format!("{:?}", status));
+    }
+
+    let bills: Vec<SupplierBill> = db
+        .get_collection::<SupplierBill>(Collection::SupplierBill)
+        .find(filter)
+        .sort(doc! { "billDate": 1 })
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to fetch supplier bills", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to fetch supplier bills", Some(e.to_string()))
+        })?
+        .try_collect()
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to parse supplier bills", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to parse supplier bills", Some(e.to_string()))
+        })?;
+
+    logger::log_info(&format!("List {} supplier bills", bills.len()), 200, None);
+    Ok(bills)
+}
+
+async fn pay_in_session(
+    db: &MongoDbState,
+    session: &mut ClientSession,
+    payment: NewSupplierPayment,
+) -> AppResult<SupplierPayment> {
+    let bill_collection = db.get_collection::<SupplierBill>(Collection::SupplierBill);
+    let bill = bill_collection
+        .find_one(doc! { "_id": payment.bill_id })
+        .session(&mut *session)
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to fetch supplier bill", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to fetch supplier bill", Some(e.to_string()))
+        })?
+        .ok_or_else(|| {
+            logger::log_error(
+                &format!("Supplier bill not found for ID: {}", payment.bill_id),
+                404,
+                None,
+            );
+            ErrorResponse::new(404, "Supplier bill not found", None)
+        })?;
+
+    // Paying more than the bill is most likely a typo
+    if payment.amount > bill.balance() + MIN_OPEN_BALANCE {
+        let details = format!("The bill has {:.2} left to pay", bill.balance());
+        logger::log_error("Payment larger than the bill", 400, Some(&details));
+        return Err(ErrorResponse::validation(
+            "Invalid supplier payment",
+            vec![FieldError {
+                field: "amount".to_string(),
+                message: details,
+            }],
+        ));
+    }
+
+    let total_paid = bill.total_paid + payment.amount;
+    bill_collection
+        .update_one(
+            doc! { "_id": payment.bill_id },
+            doc! { "$set": {
+                "totalPaid": total_paid,
+                "status": bill_status(bill.total_amount, total_paid).to_string(),
+                "updated_at": MongoDateTime::now(),
+            } },
+        )
+        .session(&mut *session)
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to update supplier bill", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to update supplier bill", Some(e.to_string()))
+        })?;
+
+    db.get_collection::<Supplier>(Collection::Supplier)
+        .update_one(
+            doc! { "_id": bill.supplier_id },
+            doc! {
+                "$inc": {
+                    "totalOwed": -payment.amount,
+                    "totalPaid": payment.amount,
+                    "outstandingBalance": -payment.amount,
+                },
+                "$set": { "updated_at": MongoDateTime::now() },
+            },
+        )
+        .session(&mut *session)
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to update supplier", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to update supplier", Some(e.to_string()))
+        })?;
+
+    let mut supplier_payment = SupplierPayment {
+        id: None,
+        supplier_id: bill.supplier_id,
+        bill_id: payment.bill_id,
+        amount: payment.amount,
+        method: payment.method,
+        reference: payment.reference,
+        paid_at: payment.paid_at.unwrap_or_else(MongoDateTime::now),
+        created_at: MongoDateTime::now(),
+    };
+    let result = db
+        .get_collection::<SupplierPayment>(Collection::SupplierPayment)
+        .insert_one(&supplier_payment)
+        .session(&mut *session)
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to save supplier payment", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to save supplier payment", Some(e.to_string()))
+        })?;
+    supplier_payment.id = result.inserted_id.as_object_id();
+    Ok(supplier_payment)
+}
+
+// Pay all or part of a supplier bill
+#[tauri::command]
+pub async fn record_supplier_payment(
+    payment: NewSupplierPayment,
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<SupplierPayment> {
+    let db = db.lock().await;
+
+    if !payment.amount.is_finite() || payment.amount < MIN_OPEN_BALANCE {
+        logger::log_error("Invalid supplier payment amount", 400, None);
+        return Err(ErrorResponse::validation(
+            "Invalid supplier payment",
+            vec![FieldError {
+                field: "amount".to_string(),
+                message: "Amount must be more than zero".to_string(),
+            }],
+        ));
+    }
+
+    let mut session = db.start_session().await?;
+    session
+        .start_transaction()
+        .await
+        .map_err(start_transaction_error)?;
+
+    let payment = match pay_in_session(&db, &mut session, payment).await {
+        Ok(payment) => payment,
+        Err(e) => {
+            session.abort_transaction().await.ok();
+            return Err(e);
+        }
+    };
+    session
+        .commit_transaction()
+        .await
+        .map_err(commit_transaction_error)?;
+
+    logger::log_info(
+        &format!(
+            "Paid {:.2} on supplier bill {}",
+            payment.amount, payment.bill_id
+        ),
+        201,
+        None,
+    );
+    Ok(payment)
+}
+
+// List the payments made to a supplier, newest first
+#[tauri::command]
+pub async fn list_supplier_payments(
+    supplier_id: String,
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<Vec<SupplierPayment>> {
+    let db = db.lock().await;
+    let id: ObjectId = parse_object_id(&supplier_id, "Supplier")?;
+
+    let payments: Vec<SupplierPayment> = db
+        .get_collection::<SupplierPayment>(Collection::SupplierPayment)
+        .find(doc! { "supplierId": id })
+        .sort(doc! { "paidAt": -1 })
+        .await
+        .map_err(|e| {
+            logger::log_error(
+                "Failed to fetch supplier payments",
+                500,
+                Some(&e.to_string()),
+            );
+            ErrorResponse::new(
+                500,
+                "Failed to fetch supplier payments",
+                Some(e.to_string()),
+            )
+        })?
+        .try_collect()
+        .await
+        .map_err(|e| {
+            logger::log_error(
+                "Failed to parse supplier payments",
+                500,
+                Some(&e.to_string()),
+            );
+            ErrorResponse::new(
+                500,
+                "Failed to parse supplier payments",
+                Some(e.to_string()),
+            )
+        })?;
+
+    logger::log_info(
+        &format!(
+            "List {} payments to supplier {}",
+            payments.len(),
+            supplier_id
+        ),
+        200,
+        None,
+    );
+    Ok(payments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        schema::purchase_order_schema::{PurchaseOrderLine, PurchaseReceipt, ReceiptLine},
+        test_fixtures::{date, object_id},
+    };
+
+    fn order(receipts: Vec<Vec<ReceiptLine>>) -> PurchaseOrder {
+        PurchaseOrder {
+            id: Some(object_id(40)),
+            supplier_id: object_id(41),
+            lines: vec![PurchaseOrderLine {
+                product_id: object_id(11),
+                name: "Paper".to_string(),
+                quantity: 20,
+                received_quantity: 0,
+                unit_cost: 8.0,
+            }],
+            status: PurchaseOrderStatus::PartiallyReceived,
+            expected_date: None,
+            notes: None,
+            total_cost: 160.0,
+            receipts: receipts
+                .into_iter()
+                .map(|lines| PurchaseReceipt {
+                    id: ObjectId::new(),
+                    lines,
+                    received_by: None,
+                    created_at: date(2024, 5, 10),
+                })
+                .collect(),
+            created_at: date(2024, 5, 1),
+            updated_at: date(2024, 5, 10),
+        }
+    }
+
+    fn received(quantity: i64, unit_cost: f64) -> ReceiptLine {
+        ReceiptLine {
+            product_id: object_id(11),
+            quantity,
+            unit_cost: Some(unit_cost),
+        }
+    }
+
+    fn bill(total_amount: f64) -> SupplierBill {
+        SupplierBill {
+            id: None,
+            supplier_id: object_id(41),
+            purchase_order_id: Some(object_id(40)),
+            reference: "INV-7".to_string(),
+            description: None,
+            total_amount,
+            total_paid: 0.0,
+            status: Status::UnPaid,
+            bill_date: date(2024, 5, 12),
+            due_date: None,
+            created_at: date(2024, 5, 12),
+            updated_at: date(2024, 5, 12),
+        }
+    }
+
+    #[test]
+    fn bill_status_follows_payments() {
+        assert!(matches!(bill_status(100.0, 0.0), Status::UnPaid));
+        assert!(matches!(bill_status(100.0, 40.0), Status::PartialPaid));
+        assert!(matches!(bill_status(100.0, 100.0), Status::Paid));
+        // Less than half a cent left is rounding, not an open balance
+        assert!(matches!(bill_status(100.0, 99.996), Status::Paid));
+        assert!(matches!(bill_status(100.0, 99.99), Status::PartialPaid));
+    }
+
+    #[test]
+    fn unbilled_amount_covers_every_receipt() {
+        let order = order(vec![
+            vec![received(5, 8.0)],
+            vec![received(10, 8.0), received(2, 9.5)],
+        ]);
+        assert_eq!(unbilled_amount(&order, &[]), 139.0);
+    }
+
+    #[test]
+    fn unbilled_amount_excludes_earlier_bills() {
+        let order = order(vec![vec![received(10, 8.0)], vec![received(5, 8.0)]]);
+        assert_eq!(unbilled_amount(&order, &[bill(80.0)]), 40.0);
+        assert_eq!(unbilled_amount(&order, &[bill(80.0), bill(40.0)]), 0.0);
+    }
+
+    #[test]
+    fn nothing_is_unbilled_before_a_receipt() {
+        assert_eq!(unbilled_amount(&order(Vec::new()), &[]), 0.0);
+    }
+}
@@ -0,0 +1,447 @@
+use std::collections::HashSet;
+
+use futures::TryStreamExt;
+use mongodb::{
+    bson::{doc, oid::ObjectId, DateTime as MongoDateTime, Document},
+    options::ReturnDocument,
+    ClientSession,
+};
+use tauri::State;
+use tokio::sync::Mutex;
+
+use crate::{
+    db::MongoDbState,
+    pricing::ClientPricing,
+    schema::{
+        client_schema::Client,
+        collections::Collection,
+        error::{AppResult, ErrorResponse, FieldError},
+        price_list_schema::{ClientGroup, NewClientGroup, NewPriceList, PriceList},
+    },
+    utils::parse_object_id,
+};
+
+/// Loads the price lists that apply to `client`, reading inside the invoice transaction.
+pub async fn load_client_pricing(
+    db: &MongoDbState,
+    session: &mut ClientSession,
+    client: &Client,
+) -> AppResult<ClientPricing> {
+    let group_list_id = match client.group_id {
+        Some(group_id) => db
+            .get_collection::<ClientGroup>(Collection::ClientGroup)
+            .find_one(doc! { "_id": group_id })
+            .session(&mut *session)
+            .await
+            .map_err(|e| {
+                logger::log_error("Failed to fetch client group", 500, Some(&e.to_string()));
+                ErrorResponse::new(500, "Failed to fetch client group", Some(e.to_string()))
+            })?
+            .and_then(|group| group.price_list_id),
+        None => None,
+    };
+
+    let mut price_lists = Vec::new();
+    for list_id in [client.price_list_id, group_list_id].into_iter().flatten() {
+        let list = db
+            .get_collection::<PriceList>(Collection::PriceList)
+            .find_one(doc! { "_id": list_id })
+            .session(&mut *session)
+            .await
+            .map_err(|e| {
+                logger::log_error("Failed to fetch price list", 500, Some(&e.to_string()));
+                ErrorResponse::new(500, "Failed to fetch price list", Some(e.to_string()))
+            })?;
+        match list {
+            Some(list) => price_lists.push(list),
+            None => logger::log_warn(
+                &format!(
+                    "Price list {} assigned to a client no longer exists",
+                    list_id
+                ),
+                404,
+                None,
+            ),
+        }
+    }
+    Ok(ClientPricing { price_lists })
+}
+
+fn validate_price_list(list: &NewPriceList) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+    if list.name.trim().is_empty() {
+        errors.push(FieldError {
+            field: "name".to_string(),
+            message: "Name is required".to_string(),
+        });
+    }
+    let mut seen = HashSet::new();
+    for (i, item) in list.items.iter().enumerate() {
+        if !item.price.is_finite() || item.price < 0.0 {
+            errors.push(FieldError {
+                field: format!("items[{}].price", i),
+                message: "Price must be zero or more".to_string(),
+            });
+        }
+        if !seen.insert(item.product_id) {
+            errors.push(FieldError {
+                field: format!("items[{}].productId", i),
+                message: "Product is already on the list".to_string(),
+            });
+        }
+    }
+    errors
+}
+
+fn validate_client_group(group: &NewClientGroup) -> Vec<FieldError> {
+    if group.name.trim().is_empty() {
+        return vec![FieldError {
+            field: "name".to_string(),
+            message: "Name is required".to_string(),
+        }];
+    }
+    Vec::new()
+}
+
+/// Fails with 404 when no price list has `id`.
+async fn require_price_list(db: &MongoDbState, id: ObjectId) -> AppResult<()> {
+    let count = db
+        .get_collection::<PriceList>(Collection::PriceList)
+        .count_documents(doc! { "_id": id })
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to fetch price list", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to fetch price list", Some(e.to_string()))
+        })?;
+    if count == 0 {
+        logger::log_error(&format!("Price list not found for ID: {}", id), 404, None);
+        return Err(ErrorResponse::new(404, "Price list not found", None));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn create_price_list(
+    price_list: NewPriceList,
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<PriceList> {
+    let db = db.lock().await;
+
+    let errors = validate_price_list(&price_list);
+    if !errors.is_empty() {
+        logger::log_error("Invalid price list", 400, None);
+        return Err(ErrorResponse::validation("Invalid price list", errors));
+    }
+
+    let mut list = PriceList {
+        id: None,
+        name: price_list.name.trim().to_string(),
+        items: price_list.items,
+        created_at: MongoDateTime::now(),
+        updated_at: MongoDateTime::now(),
+    };
+    let result = db
+        .get_collection::<PriceList>(Collection::PriceList)
+        .insert_one(&list)
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to create price list", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to create price list", Some(e.to_string()))
+        })?;
+    list.id = result.inserted_id.as_object_id();
+
+    logger::log_info(&format!("Created price list {}", list.name), 201, None);
+    Ok(list)
+}
+
+// Replace the name and items of a price list
+#[tauri::command]
+pub async fn update_price_list(
+    price_list_id: String,
+    price_list: NewPriceList,
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<PriceList> {
+    let db = db.lock().await;
+    let id = parse_object_id(&price_list_id, "Price list")?;
+
+    let errors = validate_price_list(&price_list);
+    if !errors.is_empty() {
+        logger::log_error("Invalid price list", 400, None);
+        return Err(ErrorResponse::validation("Invalid price list", errors));
+    }
+
+    let items = mongodb::bson::to_bson(&price_list.items).map_err(|e| {
+        logger::log_error("Invalid price list", 400, Some(&e.to_string()));
+        ErrorResponse::new(400, "Invalid price list", Some(e.to_string()))
+    })?;
+    let updated = db
+        .get_collection::<PriceList>(Collection::PriceList)
+        .find_one_and_update(
+            doc! { "_id": id },
+            doc! { "$set": {
+                "name": price_list.name.trim(),
+                "items": items,
+                "updated_at": MongoDateTime::now(),
+            } },
+        )
+        .return_document(ReturnDocument::After)
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to update price list", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to update price list", Some(e.to_string()))
+        })?
+        .ok_or_else(|| {
+            logger::log_error(&format!("Price list not found for ID: {}", id), 404, None);
+            ErrorResponse::new(404, "Price list not found", None)
+        })?;
+
+    logger::log_info(&format!("Updated price list {}", price_list_id), 200, None);
+    Ok(updated)
+}
+
+#[tauri::command]
+pub async fn list_price_lists(db: State<'_, Mutex<MongoDbState>>) -> AppResult<Vec<PriceList>> {
+    let db = db.lock().await;
+
+    let lists: Vec<PriceList> = db
+        .get_collection::<PriceList>(Collection::PriceList)
+        .find(doc! {})
+        .sort(doc! { "name": 1 })
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to fetch price lists", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to fetch price lists", Some(e.to_string()))
+        })?
+        .try_collect()
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to parse price lists", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to parse price lists", Some(e.to_string()))
+        })?;
+
+    logger::log_info(&format!("List {} price lists", lists.len()), 200, None);
+    Ok(lists)
+}
+
+// Delete a price list no client or group uses any more
+#[tauri::command]
+pub async fn delete_price_list(
+    price_list_id: String,
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<String> {
+    let db = db.lock().await;
+    let id = parse_object_id(&price_list_id, "Price list")?;
+
+    let filter = doc! { "priceListId": id };
+    let count_error = |e: mongodb::error::Error| {
+        logger::log_error("Failed to check price list use", 500, Some(&e.to_string()));
+        ErrorResponse::new(500, "Failed to check price list use", Some(e.to_string()))
+    };
+    let clients = db
+        .get_collection::<Document>(Collection::Client)
+        .count_documents(filter.clone())
+        .await
+        .map_err(count_error)?;
+    let groups = db
+        .get_collection::<Document>(Collection::ClientGroup)
+        .count_documents(filter)
+        .await
+        .map_err(count_error)?;
+    if clients + groups > 0 {
+        let details = format!("Assigned to {} clients and {} groups", clients, groups);
+        logger::log_error("Price list is in use", 409, Some(&details));
+        return Err(ErrorResponse::new(
+            409,
+            "Price list is in use",
+            Some(details),
+        ));
+    }
+
+    let result = db
+        .get_collection::<PriceList>(Collection::PriceList)
+        .delete_one(doc! { "_id": id })
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to delete price list", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to delete price list", Some(e.to_string()))
+        })?;
+    if result.deleted_count == 0 {
+        logger::log_error("Price list not found", 404, None);
+        return Err(ErrorResponse::new(404, "Price list not found", None));
+    }
+
+    logger::log_info(
+        &format!("Price list with ID {} deleted successfully", price_list_id),
+        204,
+        None,
+    );
+    Ok(format!(
+        "Price list with ID {} deleted successfully",
+        price_list_id
+    ))
+}
+
+#[tauri::command]
+pub async fn create_client_group(
+    group: NewClientGroup,
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<ClientGroup> {
+    let db = db.lock().await;
+
+    let errors = validate_client_group(&group);
+    if !errors.is_empty() {
+        logger::log_error("Invalid client group", 400, None);
+        return Err(ErrorResponse::validation("Invalid client group", errors));
+    }
+    if let Some(list_id) = group.price_list_id {
+        require_price_list(&db, list_id).await?;
+    }
+
+    let mut client_group = ClientGroup {
+        id: None,
+        name: group.name.trim().to_string(),
+        description: group.description,
+        price_list_id: group.price_list_id,
+        created_at: MongoDateTime::now(),
+        updated_at: MongoDateTime::now(),
+    };
+    let result = db
+        .get_collection::<ClientGroup>(Collection::ClientGroup)
+        .insert_one(&client_group)
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to create client group", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to create client group", Some(e.to_string()))
+        })?;
+    client_group.id = result.inserted_id.as_object_id();
+
+    logger::log_info(
+        &format!("Created client group {}", client_group.name),
+        201,
+        None,
+    );
+    Ok(client_group)
+}
+
+#[tauri::command]
+pub async fn update_client_group(
+    group_id: String,
+    group: NewClientGroup,
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<ClientGroup> {
+    let db = db.lock().await;
+    let id = parse_object_id(&group_id, "Client group")?;
+
+    let errors = validate_client_group(&group);
+    if !errors.is_empty() {
+        logger::log_error("Invalid client group", 400, None);
+        return Err(ErrorResponse::validation("Invalid client group", errors));
+    }
+    if let Some(list_id) = group.price_list_id {
+        require_price_list(&db, list_id).await?;
+    }
+
+    let updated = db
+        .get_collection::<ClientGroup>(Collection::ClientGroup)
+        .find_one_and_update(
+            doc! { "_id": id },
+            doc! { "$set": {
+                "name": group.name.trim(),
+                "description": group.description,
+                "priceListId": group.price_list_id,
+                "updated_at": MongoDateTime::now(),
+            } },
+        )
+        .return_document(ReturnDocument::After)
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to update client group", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to update client group", Some(e.to_string()))
+        })?
+        .ok_or_else(|| {
+            logger::log_error(&format!("Client group not found for ID: {}", id), 404, None);
+            ErrorResponse::new(404, "Client group not found", None)
+        })?;
+
+    logger::log_info(&format!("Updated client group {}", group_id), 200, None);
+    Ok(updated)
+}
+
+#[tauri::command]
+pub async fn list_client_groups(db: State<'_, Mutex<MongoDbState>>) -> AppResult<Vec<ClientGroup>> {
+    let db = db.lock().await;
+
+    let groups: Vec<ClientGroup> = db
+        .get_collection::<ClientGroup>(Collection::ClientGroup)
+        .find(doc! {})
+        .sort(doc! { "name": 1 })
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to fetch client groups", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to fetch client groups", Some(e.to_string()))
+        })?
+        .try_collect()
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to parse client groups", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to parse client groups", Some(e.to_string()))
+        })?;
+
+    logger::log_info(&format!("List {} client groups", groups.len()), 200, None);
+    Ok(groups)
+}
+
+// Delete a client group that has no clients left in it
+#[tauri::command]
+pub async fn delete_client_group(
+    group_id: String,
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<String> {
+    let db = db.lock().await;
+    let id = parse_object_id(&group_id, "Client group")?;
+
+    let members = db
+        .get_collection::<Document>(Collection::Client)
+        .count_documents(doc! { "groupId": id })
+        .await
+        .map_err(|e| {
+            logger::log_error(
+                "Failed to check client group use",
+                500,
+                Some(&e.to_string()),
+            );
+            ErrorResponse::new(500, "Failed to check client group use", Some(e.to_string()))
+        })?;
+    if members > 0 {
+        let details = format!("{} clients are in the group", members);
+        logger::log_error("Client group is in use", 409, Some(&details));
+        return Err(ErrorResponse::new(
+            409,
+            "Client group is in use",
+            Some(details),
+        ));
+    }
+
+    let result = db
+        .get_collection::<ClientGroup>(Collection::ClientGroup)
+        .delete_one(doc! { "_id": id })
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to delete client group", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to delete client group", Some(e.to_string()))
+        })?;
+    if result.deleted_count == 0 {
+        logger::log_error("Client group not found", 404, None);
+        return Err(ErrorResponse::new(404, "Client group not found", None));
+    }
+
+    logger::log_info(
+        &format!("Client group with ID {} deleted successfully", group_id),
+        204,
+        None,
+    );
+    Ok(format!(
+        "Client group with ID {} deleted successfully",
+        group_id
+    ))
+}
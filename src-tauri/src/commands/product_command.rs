@@ -1,46 +1,128 @@
 use futures::TryStreamExt;
-use mongodb::bson::{self, datetime::DateTime as MongoDateTime, doc, Bson, Document};
+use mongodb::{
+    bson::{self, datetime::DateTime as MongoDateTime, doc, oid::ObjectId, Bson, Document},
+    ClientSession,
+};
 use tauri::State;
 use tokio::sync::Mutex;
 
 use crate::{
+    commands::stock_command::record_stock_movements,
     db::MongoDbState,
     schema::{
         collections::Collection,
         error::{AppResult, ErrorResponse},
         product_schema::{NewProduct, Product},
+        stock_schema::{StockMovement, StockMovementReason, StockSourceKind},
     },
     utils::parse_object_id,
 };
 
+fn start_transaction_error(e: mongodb::error::Error) -> ErrorResponse {
+    logger::log_error(
+        "Failed to start MongoDB transaction.",
+        500,
+        Some(&e.to_string()),
+    );
+    ErrorResponse::new(
+        500,
+        "Failed to start MongoDB transaction.",
+        Some(e.to_string()),
+    )
+}
+
+fn commit_transaction_error(e: mongodb::error::Error) -> ErrorResponse {
+    logger::log_error(
+        "Failed to commit MongoDB transaction.",
+        500,
+        Some(&e.to_string()),
+    );
+    ErrorResponse::new(
+        500,
+        "Failed to commit MongoDB transaction.",
+        Some(e.to_string()),
+    )
+}
+
+/// Records a stock edit made on the product itself.
+fn product_adjustment(product_id: ObjectId, quantity: i64, note: &str) -> StockMovement {
+    StockMovement {
+        note: Some(note.to_string()),
+        ..StockMovement::new(
+            product_id,
+            quantity,
+            StockMovementReason::Adjustment,
+            StockSourceKind::Product,
+            product_id,
+        )
+    }
+}
+
+async fn insert_in_session(
+    db: &MongoDbState,
+    session: &mut ClientSession,
+    product: &Product,
+) -> AppResult<ObjectId> {
+    let doc = bson::to_document(product).map_err(|e| {
+        ErrorResponse::new(400, "Failed to serialize product data", Some(e.to_string()))
+    })?;
+
+    let result = db
+        .get_collection::<Document>(Collection::Product)
+        .insert_one(doc)
+        .session(&mut *session)
+        .await
+        .map_err(|e| {
+            ErrorResponse::new(500, "Failed to insert into Database", Some(e.to_string()))
+        })?;
+
+    let inserted_id = match result.inserted_id {
+        Bson::ObjectId(id) => id,
+        _ => return Err(ErrorResponse::new(500, "Invalid inserted ID", None)),
+    };
+    if product.stock != 0 {
+        let movement = product_adjustment(inserted_id, product.stock, "Opening stock");
+        record_stock_movements(db, session, vec![movement]).await?;
+    }
+    Ok(inserted_id)
+}
+
 #[tauri::command]
 pub async fn create_product(
     new_product: NewProduct,
     db: State<'_, Mutex<MongoDbState>>,
 ) -> AppResult<Product> {
     let db = db.lock().await;
-    let collection = db.get_collection::<Document>(Collection::Product);
     let mut product = Product {
         id: None,
         name: new_product.name,
         discription: new_product.discription,
         price: new_product.price,
+        cost: new_product.cost,
         stock: new_product.stock,
+        item_code: new_product.item_code,
+        reorder_point: new_product.reorder_point,
+        reorder_quantity: new_product.reorder_quantity,
         created_at: MongoDateTime::now(),
         updated_at: MongoDateTime::now(),
     };
-    let doc = bson::to_document(&product).map_err(|e| {
-        ErrorResponse::new(400, "Failed to serialize product data", Some(e.to_string()))
-    })?;
-
-    let result = collection.insert_one(doc).await.map_err(|e| {
-        ErrorResponse::new(500, "Failed to insert into Database", Some(e.to_string()))
-    })?;
+    let mut session = db.start_session().await?;
+    session
+        .start_transaction()
+        .await
+        .map_err(start_transaction_error)?;
 
-    let inserted_id = match result.inserted_id {
-        Bson::ObjectId(id) => id,
-        _ => return Err(ErrorResponse::new(500, "Invalid inserted ID", None)),
+    let inserted_id = match insert_in_session(&db, &mut session, &product).await {
+        Ok(id) => id,
+        Err(e) => {
+            session.abort_transaction().await.ok();
+            return Err(e);
+        }
     };
+    session
+        .commit_transaction()
+        .await
+        .map_err(commit_transaction_error)?;
     product.id = Some(inserted_id);
     Ok(product)
 }
@@ -136,28 +218,41 @@ pub async fn get_product_by_id(
     );
     Ok(product)
 }
-#[tauri::command]
-pub async fn update_product(
-    product_id: String,
-    updated_fields: Document, // Use a `Document` for flexibility in updating fields
-    db: State<'_, Mutex<MongoDbState>>,
+async fn update_in_session(
+    db: &MongoDbState,
+    session: &mut ClientSession,
+    object_id: ObjectId,
+    updated_fields: Document,
 ) -> AppResult<Product> {
-    let db = db.lock().await;
     let collection = db.get_collection::<Product>(Collection::Product);
-
-    // Convert `product_id` to ObjectId
-    let object_id = parse_object_id(&product_id, "Product")?;
-    // Add `updated_at` to the update document
-    let mut updated_fields = updated_fields.clone();
-    updated_fields.insert("updated_at", MongoDateTime::now());
-    // Prepare the update document with `$set` for partial updates
-    let update_doc = doc! {
-        "$set": updated_fields
+    let not_found = || {
+        logger::log_info(
+            "Product not found",
+            404,
+            Some("No product matches the given ID"),
+        );
+        ErrorResponse::new(
+            404,
+            "Product not found",
+            Some("No product matches the given ID".to_string()),
+        )
     };
 
-    // Perform the update
-    let result = collection
-        .update_one(doc! { "_id": object_id }, update_doc)
+    // The stock before the edit, to record how much it changed
+    let previous_product = collection
+        .find_one(doc! { "_id": object_id })
+        .session(&mut *session)
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to fetch product", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to fetch product", Some(e.to_string()))
+        })?
+        .ok_or_else(not_found)?;
+
+    // Prepare the update document with `$set` for partial updates
+    collection
+        .update_one(doc! { "_id": object_id }, doc! { "$set": updated_fields })
+        .session(&mut *session)
         .await
         .map_err(|e| {
             logger::log_info(
@@ -172,40 +267,57 @@ pub async fn update_product(
             )
         })?;
 
-    // Check if the product was updated
-    if result.matched_count == 0 {
-        logger::log_info(
-            "Product not found",
-            404,
-            Some("No product matches the given ID"),
-        );
-        return Err(ErrorResponse::new(
-            404,
-            "Product not found",
-            Some("No product matches the given ID".to_string()),
-        ));
-    }
-
-    // Fetch and return the updated product
+    // Fetch the updated product
     let updated_product = collection
         .find_one(doc! { "_id": object_id })
+        .session(&mut *session)
         .await
         .map_err(|e| {
             logger::log_info("Failed to fetch updated product", 500, Some(&e.to_string()));
             ErrorResponse::new(500, "Failed to fetch updated product", Some(e.to_string()))
         })?
-        .ok_or_else(|| {
-            logger::log_info(
-                "Product not found",
-                404,
-                Some("No product found after update"),
-            );
-            ErrorResponse::new(
-                404,
-                "Product not found",
-                Some("No product found after update".to_string()),
-            )
-        })?;
+        .ok_or_else(not_found)?;
+
+    let delta = updated_product.stock - previous_product.stock;
+    if delta != 0 {
+        let movement = product_adjustment(object_id, delta, "Stock edited on the product");
+        record_stock_movements(db, session, vec![movement]).await?;
+    }
+    Ok(updated_product)
+}
+
+#[tauri::command]
+pub async fn update_product(
+    product_id: String,
+    updated_fields: Document, // Use a `Document` for flexibility in updating fields
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<Product> {
+    let db = db.lock().await;
+
+    // Convert `product_id` to ObjectId
+    let object_id = parse_object_id(&product_id, "Product")?;
+    // Add `updated_at` to the update document
+    let mut updated_fields = updated_fields.clone();
+    updated_fields.insert("updated_at", MongoDateTime::now());
+
+    let mut session = db.start_session().await?;
+    session
+        .start_transaction()
+        .await
+        .map_err(start_transaction_error)?;
+
+    let updated_product =
+        match update_in_session(&db, &mut session, object_id, updated_fields).await {
+            Ok(product) => product,
+            Err(e) => {
+                session.abort_transaction().await.ok();
+                return Err(e);
+            }
+        };
+    session
+        .commit_transaction()
+        .await
+        .map_err(commit_transaction_error)?;
     logger::log_info("Update product", 200, None);
     Ok(updated_product)
 }
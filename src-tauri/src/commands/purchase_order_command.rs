@@ -0,0 +1,672 @@
+use std::collections::{HashMap, HashSet};
+
+use futures::TryStreamExt;
+use mongodb::{
+    bson::{doc, oid::ObjectId, DateTime as MongoDateTime},
+    options::ReturnDocument,
+    ClientSession,
+};
+use tauri::State;
+use tokio::sync::Mutex;
+
+use crate::{
+    commands::{stock_command::record_stock_movements, supplier_command::load_supplier},
+    db::MongoDbState,
+    schema::{
+        collections::Collection,
+        error::{AppResult, ErrorResponse, FieldError},
+        product_schema::Product,
+        purchase_order_schema::{
+            NewPurchaseOrder, PurchaseOrder, PurchaseOrderLine, PurchaseOrderStatus,
+            PurchaseReceipt, ReceiptLine,
+        },
+        stock_schema::{StockMovement, StockMovementReason, StockSourceKind},
+    },
+    utils::parse_object_id,
+};
+
+fn validate_purchase_order(order: &NewPurchaseOrder) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+    if order.lines.is_empty() {
+        errors.push(FieldError {
+            field: "lines".to_string(),
+            message: "A purchase order needs at least one line".to_string(),
+        });
+    }
+    let mut seen = HashSet::new();
+    for (i, line) in order.lines.iter().enumerate() {
+        if line.quantity <= 0 {
+            errors.push(FieldError {
+                field: format!("lines[{}].quantity", i),
+                message: "Quantity must be more than zero".to_string(),
+            });
+        }
+        if !line.unit_cost.is_finite() || line.unit_cost < 0.0 {
+            errors.push(FieldError {
+                field: format!("lines[{}].unitCost", i),
+                message: "Unit cost must be zero or more".to_string(),
+            });
+        }
+        if !seen.insert(line.product_id) {
+            errors.push(FieldError {
+                field: format!("lines[{}].productId", i),
+                message: "Product is already on the order".to_string(),
+            });
+        }
+    }
+    errors
+}
+
+/// Checks a receipt against what is still outstanding on `order`.
+fn validate_receipt(order: &PurchaseOrder, lines: &[ReceiptLine]) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+    if lines.is_empty() {
+        errors.push(FieldError {
+            field: "lines".to_string(),
+            message: "Nothing to receive".to_string(),
+        });
+    }
+    let mut outstanding: HashMap<ObjectId, i64> = order
+        .lines
+        .iter()
+        .map(|line| (line.product_id, line.outstanding()))
+        .collect();
+    for (i, line) in lines.iter().enumerate() {
+        if let Some(cost) = line.unit_cost {
+            if !cost.is_finite() || cost < 0.0 {
+                errors.push(FieldError {
+                    field: format!("lines[{}].unitCost", i),
+                    message: "Unit cost must be zero or more".to_string(),
+                });
+            }
+        }
+        if line.quantity <= 0 {
+            errors.push(FieldError {
+                field: format!("lines[{}].quantity", i),
+                message: "Quantity must be more than zero".to_string(),
+            });
+            continue;
+        }
+        match outstanding.get_mut(&line.product_id) {
+            None => errors.push(FieldError {
+                field: format!("lines[{}].productId", i),
+                message: "Product is not on the order".to_string(),
+            }),
+            Some(left) if line.quantity > *left => {
+                errors.push(FieldError {
+                    field: format!("lines[{}].quantity", i),
+                    message: format!("Only {} left to receive", left),
+                });
+                *left = 0;
+            }
+            Some(left) => *left -= line.quantity,
+        }
+    }
+    errors
+}
+
+/// Status of an order after a receipt, from what is still outstanding.
+fn received_status(lines: &[PurchaseOrderLine]) -> PurchaseOrderStatus {
+    if lines.iter().all(|line| line.outstanding() <= 0) {
+        PurchaseOrderStatus::Received
+    } else {
+        PurchaseOrderStatus::PartiallyReceived
+    }
+}
+
+/// Adds a receipt line to the order line for its product. The line is returned
+/// with the order's unit cost when the receipt gave none, or `None` when the
+/// product is not on the order.
+fn receive_line(
+    order_lines: &mut [PurchaseOrderLine],
+    line: ReceiptLine,
+) -> Option<(&PurchaseOrderLine, ReceiptLine)> {
+    let order_line = order_lines
+        .iter_mut()
+        .find(|order_line| order_line.product_id == line.product_id)?;
+    order_line.received_quantity += line.quantity;
+    let line = ReceiptLine {
+        unit_cost: Some(line.unit_cost.unwrap_or(order_line.unit_cost)),
+        ..line
+    };
+    Some((order_line, line))
+}
+
+async fn load_purchase_order(
+    db: &MongoDbState,
+    session: Option<&mut ClientSession>,
+    id: ObjectId,
+) -> AppResult<PurchaseOrder> {
+    let collection = db.get_collection::<PurchaseOrder>(Collection::PurchaseOrder);
+    let find = collection.find_one(doc! { "_id": id });
+    let result = match session {
+        Some(session) => find.session(session).await,
+        None => find.await,
+    };
+    result
+        .map_err(|e| {
+            logger::log_error("Failed to fetch purchase order", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to fetch purchase order", Some(e.to_string()))
+        })?
+        .ok_or_else(|| {
+            logger::log_error(
+                &format!("Purchase order not found for ID: {}", id),
+                404,
+                None,
+            );
+            ErrorResponse::new(404, "Purchase order not found", None)
+        })
+}
+
+#[tauri::command]
+pub async fn create_purchase_order(
+    order: NewPurchaseOrder,
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<PurchaseOrder> {
+    let db = db.lock().await;
+
+    let errors = validate_purchase_order(&order);
+    if !errors.is_empty() {
+        logger::log_error("Invalid purchase order", 400, None);
+        return Err(ErrorResponse::validation("Invalid purchase order", errors));
+    }
+    load_supplier(&db, order.supplier_id).await?;
+
+    let product_ids: Vec<ObjectId> = order.lines.iter().map(|line| line.product_id).collect();
+    let products: Vec<Product> = db
+        .get_collection::<Product>(Collection::Product)
+        .find(doc! { "_id": { "$in": &product_ids } })
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to fetch products", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to fetch products", Some(e.to_string()))
+        })?
+        .try_collect()
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to parse product data", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to parse product data", Some(e.to_string()))
+        })?;
+    let names: HashMap<ObjectId, String> = products
+        .into_iter()
+        .filter_map(|product| Some((product.id?, product.name)))
+        .collect();
+
+    let mut lines = Vec::new();
+    let mut errors = Vec::new();
+    for (i, line) in order.lines.into_iter().enumerate() {
+        match names.get(&line.product_id) {
+            Some(name) => lines.push(PurchaseOrderLine {
+                product_id: line.product_id,
+                name: name.clone(),
+                quantity: line.quantity,
+                received_quantity: 0,
+                unit_cost: line.unit_cost,
+            }),
+            None => errors.push(FieldError {
+                field: format!("lines[{}].productId", i),
+                message: "Product not found".to_string(),
+            }),
+        }
+    }
+    if !errors.is_empty() {
+        logger::log_error("Purchase order for unknown products", 400, None);
+        return Err(ErrorResponse::validation("Invalid purchase order", errors));
+    }
+
+    let mut purchase_order = PurchaseOrder {
+        id: None,
+        supplier_id: order.supplier_id,
+        total_cost: lines
+            .iter()
+            .map(|line| line.unit_cost * line.quantity as f64)
+            .sum(),
+        lines,
+        status: if order.ordered {
+            PurchaseOrderStatus::Ordered
+        } else {
+            PurchaseOrderStatus::Draft
+        },
+        expected_date: order.expected_date,
+        notes: order.notes,
+        receipts: Vec::new(),
+        created_at: MongoDateTime::now(),
+        updated_at: MongoDateTime::now(),
+    };
+    let result = db
+        .get_collection::<PurchaseOrder>(Collection::PurchaseOrder)
+        .insert_one(&purchase_order)
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to create purchase order", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to create purchase order", Some(e.to_string()))
+        })?;
+    purchase_order.id = result.inserted_id.as_object_id();
+
+    logger::log_info(
+        &format!(
+            "Created purchase order for supplier {}",
+            purchase_order.supplier_id
+        ),
+        201,
+        None,
+    );
+    Ok(purchase_order)
+}
+
+// List purchase orders, newest first, optionally for one supplier or in one status
+#[tauri::command]
+pub async fn list_purchase_orders(
+    supplier_id: Option<String>,
+    status: Option<PurchaseOrderStatus>,
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<Vec<PurchaseOrder>> {
+    let db = db.lock().await;
+
+    let mut filter = doc! {};
+    if let Some(supplier_id) = supplier_id {
+        filter.insert("supplierId", parse_object_id(&supplier_id, "Supplier")?);
+    }
+    if let Some(status) = status {
+        filter.insert("status", format!("{:?}", status));
+    }
+
+    let orders: Vec<PurchaseOrder> = db
+        .get_collection::<PurchaseOrder>(Collection::PurchaseOrder)
+        .find(filter)
+        .sort(doc! { "created_at": -1 })
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to fetch purchase orders", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to fetch purchase orders", Some(e.to_string()))
+        })?
+        .try_collect()
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to parse purchase orders", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to parse purchase orders", Some(e.to_string()))
+        })?;
+
+    logger::log_info(&format!("List {} purchase orders", orders.len()), 200, None);
+    Ok(orders)
+}
+
+#[tauri::command]
+pub async fn get_purchase_order_by_id(
+    purchase_order_id: String,
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<PurchaseOrder> {
+    let db = db.lock().await;
+    let id = parse_object_id(&purchase_order_id, "Purchase order")?;
+
+    let order = load_purchase_order(&db, None, id).await?;
+    logger::log_info(
+        &format!("Found purchase order with ID: {}", purchase_order_id),
+        200,
+        None,
+    );
+    Ok(order)
+}
+
+// Place a draft order with the supplier, or cancel an order nothing was received on yet
+#[tauri::command]
+pub async fn set_purchase_order_status(
+    purchase_order_id: String,
+    status: PurchaseOrderStatus,
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<PurchaseOrder> {
+    let db = db.lock().await;
+    let id = parse_object_id(&purchase_order_id, "Purchase order")?;
+
+    // Receipts set the other statuses
+    let from = match status {
+        PurchaseOrderStatus::Ordered => vec!["Draft"],
+        PurchaseOrderStatus::Cancelled => vec!["Draft", "Ordered"],
+        _ => {
+            logger::log_error(
+                &format!("Cannot set purchase order status to {:?}", status),
+                400,
+                None,
+            );
+            return Err(ErrorResponse::new(
+                400,
+                "Purchase orders can only be ordered or cancelled",
+                Some("Receive the order to mark it received".to_string()),
+            ));
+        }
+    };
+
+    let updated = db
+        .get_collection::<PurchaseOrder>(Collection::PurchaseOrder)
+        .find_one_and_update(
+            doc! { "_id": id, "status": { "$in": &from } },
+            doc! { "$set": {
+                "status": format!("{:?}", status),
+                "updated_at": MongoDateTime::now(),
+            } },
+        )
+        .return_document(ReturnDocument::After)
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to update purchase order", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to update purchase order", Some(e.to_string()))
+        })?;
+
+    match updated {
+        Some(order) => {
+            logger::log_info(
+                &format!("Purchase order {} is now {:?}", purchase_order_id, status),
+                200,
+                None,
+            );
+            Ok(order)
+        }
+        None => {
+            // Tell a missing order apart from one in the wrong status
+            let order = load_purchase_order(&db, None, id).await?;
+            let details = format!("The order is {:?}", order.status);
+            logger::log_error(
+                &format!("Cannot change purchase order {} to {:?}", id, status),
+                409,
+                Some(&details),
+            );
+            Err(ErrorResponse::new(
+                409,
+                &format!("Purchase order cannot be changed to {:?}", status),
+                Some(details),
+            ))
+        }
+    }
+}
+
+async fn receive_in_session(
+    db: &MongoDbState,
+    session: &mut ClientSession,
+    id: ObjectId,
+    lines: Vec<ReceiptLine>,
+    received_by: Option<String>,
+) -> AppResult<PurchaseOrder> {
+    let mut order = load_purchase_order(db, Some(&mut *session), id).await?;
+    if !matches!(
+        order.status,
+        PurchaseOrderStatus::Ordered | PurchaseOrderStatus::PartiallyReceived
+    ) {
+        let details = format!("The order is {:?}", order.status);
+        logger::log_error(
+            &format!("Cannot receive purchase order {}", id),
+            409,
+            Some(&details),
+        );
+        return Err(ErrorResponse::new(
+            409,
+            "Only placed purchase orders can be received",
+            Some(details),
+        ));
+    }
+    let errors = validate_receipt(&order, &lines);
+    if !errors.is_empty() {
+        logger::log_error("Invalid purchase receipt", 400, None);
+        return Err(ErrorResponse::validation(
+            "Invalid purchase receipt",
+            errors,
+        ));
+    }
+
+    let product_collection = db.get_collection::<Product>(Collection::Product);
+    let mut received = Vec::new();
+    let mut movements = Vec::new();
+    for line in lines {
+        let (order_line, line) = receive_line(&mut order.lines, line)
+            .ok_or_else(|| ErrorResponse::new(400, "Product is not on the order", None))?;
+        let unit_cost = line.unit_cost.unwrap_or(order_line.unit_cost);
+
+        // The product remembers the last cost it was bought at
+        let result = product_collection
+            .update_one(
+                doc! { "_id": line.product_id },
+                doc! {
+                    "$inc": { "stock": line.quantity },
+                    "$set": { "cost": unit_cost, "updated_at": MongoDateTime::now() },
+                },
+            )
+            .session(&mut *session)
+            .await
+            .map_err(|e| {
+                logger::log_error("Failed to receive stock", 500, Some(&e.to_string()));
+                ErrorResponse::new(500, "Failed to receive stock", Some(e.to_string()))
+            })?;
+        if result.matched_count == 0 {
+            logger::log_error(
+                &format!("Product {} on the order no longer exists", order_line.name),
+                404,
+                None,
+            );
+            return Err(ErrorResponse::new(
+                404,
+                &format!("Product {} no longer exists", order_line.name),
+                None,
+            ));
+        }
+
+        movements.push(StockMovement::new(
+            line.product_id,
+            line.quantity,
+            StockMovementReason::Purchase,
+            StockSourceKind::PurchaseOrder,
+            id,
+        ));
+        received.push(line);
+    }
+    record_stock_movements(db, session, movements).await?;
+
+    order.status = received_status(&order.lines);
+    order.receipts.push(PurchaseReceipt {
+        id: ObjectId::new(),
+        lines: received,
+        received_by,
+        created_at: MongoDateTime::now(),
+    });
+    order.updated_at = MongoDateTime::now();
+    db.get_collection::<PurchaseOrder>(Collection::PurchaseOrder)
+        .replace_one(doc! { "_id": id }, &order)
+        .session(&mut *session)
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to update purchase order", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to update purchase order", Some(e.to_string()))
+        })?;
+    Ok(order)
+}
+
+// Receive some or all of the outstanding stock on a purchase order. Lines
+// without a unit cost were delivered at the cost on the order.
+#[tauri::command]
+pub async fn receive_purchase_order(
+    purchase_order_id: String,
+    lines: Vec<ReceiptLine>,
+    received_by: Option<String>,
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<PurchaseOrder> {
+    let db = db.lock().await;
+    let id = parse_object_id(&purchase_order_id, "Purchase order")?;
+
+    let mut session = db.start_session().await?;
+    session.start_transaction().await.map_err(|e| {
+        logger::log_error(
+            "Failed to start MongoDB transaction.",
+            500,
+            Some(&e.to_string()),
+        );
+        ErrorResponse::new(
+            500,
+            "Failed to start MongoDB transaction.",
+            Some(e.to_string()),
+        )
+    })?;
+
+    let order = match receive_in_session(&db, &mut session, id, lines, received_by).await {
+        Ok(order) => order,
+        Err(e) => {
+            session.abort_transaction().await.ok();
+            return Err(e);
+        }
+    };
+
+    session.commit_transaction().await.map_err(|e| {
+        logger::log_error(
+            "Failed to commit MongoDB transaction.",
+            500,
+            Some(&e.to_string()),
+        );
+        ErrorResponse::new(
+            500,
+            "Failed to commit MongoDB transaction.",
+            Some(e.to_string()),
+        )
+    })?;
+    logger::log_info(
+        &format!(
+            "Received stock on purchase order {}, now {:?}",
+            purchase_order_id, order.status
+        ),
+        200,
+        None,
+    );
+    Ok(order)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        schema::purchase_order_schema::NewPurchaseOrderLine,
+        test_fixtures::{date, object_id},
+    };
+
+    fn order_line(product: u8, quantity: i64, received_quantity: i64) -> PurchaseOrderLine {
+        PurchaseOrderLine {
+            product_id: object_id(product),
+            name: format!("Product {}", product),
+            quantity,
+            received_quantity,
+            unit_cost: 8.0,
+        }
+    }
+
+    fn order(lines: Vec<PurchaseOrderLine>) -> PurchaseOrder {
+        PurchaseOrder {
+            id: Some(object_id(40)),
+            supplier_id: object_id(41),
+            lines,
+            status: PurchaseOrderStatus::Ordered,
+            expected_date: None,
+            notes: None,
+            total_cost: 0.0,
+            receipts: Vec::new(),
+            created_at: date(2024, 5, 1),
+            updated_at: date(2024, 5, 1),
+        }
+    }
+
+    fn receipt(product: u8, quantity: i64, unit_cost: Option<f64>) -> ReceiptLine {
+        ReceiptLine {
+            product_id: object_id(product),
+            quantity,
+            unit_cost,
+        }
+    }
+
+    fn fields(errors: &[FieldError]) -> Vec<&str> {
+        errors.iter().map(|e| e.field.as_str()).collect()
+    }
+
+    #[test]
+    fn order_rejects_bad_lines_and_repeated_products() {
+        let line = |product, quantity, unit_cost| NewPurchaseOrderLine {
+            product_id: object_id(product),
+            quantity,
+            unit_cost,
+        };
+        let new_order = NewPurchaseOrder {
+            supplier_id: object_id(41),
+            lines: vec![line(11, 5, 8.0), line(12, 0, -1.0), line(11, 2, 8.0)],
+            expected_date: None,
+            notes: None,
+            ordered: true,
+        };
+
+        let errors = validate_purchase_order(&new_order);
+        assert_eq!(
+            fields(&errors),
+            [
+                "lines[1].quantity",
+                "lines[1].unitCost",
+                "lines[2].productId"
+            ]
+        );
+    }
+
+    #[test]
+    fn receipt_within_outstanding_is_valid() {
+        let order = order(vec![order_line(11, 10, 4), order_line(12, 5, 0)]);
+        let lines = [receipt(11, 6, None), receipt(12, 2, Some(9.0))];
+        assert!(validate_receipt(&order, &lines).is_empty());
+    }
+
+    #[test]
+    fn over_receiving_is_rejected() {
+        let order = order(vec![order_line(11, 10, 4)]);
+        let errors = validate_receipt(&order, &[receipt(11, 7, None)]);
+        assert_eq!(fields(&errors), ["lines[0].quantity"]);
+        assert_eq!(errors[0].message, "Only 6 left to receive");
+    }
+
+    #[test]
+    fn product_not_on_the_order_is_rejected() {
+        let order = order(vec![order_line(11, 10, 0)]);
+        let errors = validate_receipt(&order, &[receipt(12, 1, None)]);
+        assert_eq!(fields(&errors), ["lines[0].productId"]);
+    }
+
+    #[test]
+    fn repeated_receipt_lines_share_the_outstanding_quantity() {
+        let order = order(vec![order_line(11, 10, 4)]);
+        // 4 + 3 is more than the 6 still outstanding
+        let errors = validate_receipt(&order, &[receipt(11, 4, None), receipt(11, 3, None)]);
+        assert_eq!(fields(&errors), ["lines[1].quantity"]);
+        assert_eq!(errors[0].message, "Only 2 left to receive");
+    }
+
+    #[test]
+    fn empty_receipt_is_rejected() {
+        let order = order(vec![order_line(11, 10, 0)]);
+        assert_eq!(fields(&validate_receipt(&order, &[])), ["lines"]);
+    }
+
+    #[test]
+    fn order_is_received_once_every_line_is_in() {
+        let mut lines = vec![order_line(11, 10, 0), order_line(12, 5, 0)];
+
+        receive_line(&mut lines, receipt(11, 10, None)).unwrap();
+        assert_eq!(
+            received_status(&lines),
+            PurchaseOrderStatus::PartiallyReceived
+        );
+
+        receive_line(&mut lines, receipt(12, 5, None)).unwrap();
+        assert_eq!(received_status(&lines), PurchaseOrderStatus::Received);
+    }
+
+    #[test]
+    fn receipt_cost_falls_back_to_the_order() {
+        let mut lines = vec![order_line(11, 10, 0)];
+
+        let (_, line) = receive_line(&mut lines, receipt(11, 2, None)).unwrap();
+        assert_eq!(line.unit_cost, Some(8.0));
+        let (_, line) = receive_line(&mut lines, receipt(11, 2, Some(9.5))).unwrap();
+        assert_eq!(line.unit_cost, Some(9.5));
+
+        assert_eq!(lines[0].received_quantity, 4);
+        assert!(receive_line(&mut lines, receipt(12, 1, None)).is_none());
+    }
+}
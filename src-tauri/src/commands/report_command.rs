@@ -0,0 +1,341 @@
+use std::collections::HashMap;
+
+use futures::TryStreamExt;
+use mongodb::bson::{self, doc, oid::ObjectId, DateTime, Document};
+use serde::Deserialize;
+use tauri::State;
+use tokio::sync::Mutex;
+
+use crate::{
+    db::MongoDbState,
+    schema::{
+        collections::Collection,
+        error::{AppResult, ErrorResponse},
+        report_schema::{
+            AgingBuckets, PayableAgingReport, PayableAgingRow, ReceivableAgingReport,
+            ReceivableAgingRow,
+        },
+    },
+};
+
+const DAY_MILLIS: i64 = 86_400_000;
+
+// Balances below half a cent are rounding noise, not money owed
+const MIN_OPEN_BALANCE: f64 = 0.005;
+
+/// An open invoice or bill read from an aging pipeline.
+#[derive(Deserialize)]
+struct AgingItem {
+    #[serde(rename = "ownerId")]
+    owner_id: ObjectId,
+    #[serde(rename = "ownerName")]
+    owner_name: String,
+    balance: f64,
+    age: f64, // Whole days, as computed by `age_in_days`
+}
+
+/// The open balances of one client or supplier.
+#[derive(Debug)]
+struct AgingGroup {
+    owner_id: ObjectId,
+    owner_name: String,
+    count: i64,
+    buckets: AgingBuckets,
+}
+
+/// Groups open balances by client or supplier into aging buckets, largest
+/// total first.
+fn group_by_age(items: Vec<AgingItem>) -> Vec<AgingGroup> {
+    let mut groups: Vec<AgingGroup> = Vec::new();
+    let mut positions: HashMap<ObjectId, usize> = HashMap::new();
+    for item in items {
+        let position = *positions.entry(item.owner_id).or_insert_with(|| {
+            groups.push(AgingGroup {
+                owner_id: item.owner_id,
+                owner_name: item.owner_name.clone(),
+                count: 0,
+                buckets: AgingBuckets::default(),
+            });
+            groups.len() - 1
+        });
+        let group = &mut groups[position];
+        group.count += 1;
+        group.buckets.add_balance(item.age as i64, item.balance);
+    }
+    groups.sort_by(|a, b| b.buckets.total.total_cmp(&a.buckets.total));
+    groups
+}
+
+/// Reads the items of an aging pipeline and groups them by age.
+async fn run_aging_pipeline(
+    collection: Collection,
+    pipeline: Vec<Document>,
+    db: &MongoDbState,
+    report: &str,
+) -> AppResult<Vec<AgingGroup>> {
+    let documents: Vec<Document> = db
+        .get_collection::<Document>(collection)
+        .aggregate(pipeline)
+        .await
+        .map_err(|e| {
+            let message = format!("Failed to build {} aging report", report);
+            logger::log_error(&message, 500, Some(&e.to_string()));
+            ErrorResponse::new(500, &message, Some(e.to_string()))
+        })?
+        .try_collect()
+        .await
+        .map_err(|e| {
+            let message = format!("Failed to read {} aging report", report);
+            logger::log_error(&message, 500, Some(&e.to_string()));
+            ErrorResponse::new(500, &message, Some(e.to_string()))
+        })?;
+    let items = documents
+        .into_iter()
+        .map(bson::from_document::<AgingItem>)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| {
+            let message = format!("Failed to parse {} aging report", report);
+            logger::log_error(&message, 500, Some(&e.to_string()));
+            ErrorResponse::new(500, &message, Some(e.to_string()))
+        })?;
+    Ok(group_by_age(items))
+}
+
+/// Age in whole days between `field` and `as_of`.
+pub fn age_in_days(field: &str, as_of: DateTime) -> Document {
+    doc! { "$floor": { "$divide": [{ "$subtract": [as_of, field] }, DAY_MILLIS] } }
+}
+
+// Open client balances bucketed by invoice age as of a date, today by default
+#[tauri::command]
+pub async fn receivable_aging_report(
+    as_of: Option<DateTime>,
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<ReceivableAgingReport> {
+    let db = db.lock().await;
+    let as_of = as_of.unwrap_or_else(DateTime::now);
+
+    let pipeline = vec![
+        doc! { "$match": { "created_at": { "$lte": as_of } } },
+        // Invoices changed after `as_of` were in effect as the version saved by
+        // the first later revision, the same history the statement is built from
+        doc! { "$lookup": {
+            "from": Collection::InvoiceRevision.as_str(),
+            "let": { "invoiceId": "$_id" },
+            "pipeline": [
+                { "$match": { "$expr": { "$and": [
+                    { "$eq": ["$invoiceId", "$$invoiceId"] },
+                    { "$gt": ["$created_at", as_of] },
+                ] } } },
+                { "$sort": { "revision": 1 } },
+                { "$limit": 1 },
+                { "$replaceWith": "$invoice" },
+            ],
+            "as": "versionAsOf",
+        } },
+        doc! { "$project": {
+            "clientId": 1,
+            "balance": { "$let": {
+                "vars": { "version": { "$ifNull": [{ "$first": "$versionAsOf" }, "$$ROOT"] } },
+                "in": { "$subtract": ["$$version.totalPrice", "$$version.totalPaid"] },
+            } },
+            "age": age_in_days("$created_at", as_of),
+        } },
+        doc! { "$match": { "balance": { "$gte": MIN_OPEN_BALANCE } } },
+        doc! { "$lookup": {
+            "from": Collection::Client.as_str(),
+            "localField": "clientId",
+            "foreignField": "_id",
+            "as": "client",
+        } },
+        doc! { "$project": {
+            "_id": 0,
+            "ownerId": "$clientId",
+            // Company name, or the username for clients without one
+            "ownerName": { "$let": {
+                "vars": { "client": { "$first": "$client" } },
+                "in": { "$cond": [
+                    { "$gt": [{ "$strLenCP": { "$ifNull": ["$$client.company_name", ""] } }, 0] },
+                    "$$client.company_name",
+                    { "$ifNull": ["$$client.username", ""] },
+                ] },
+            } },
+            "balance": 1,
+            "age": 1,
+        } },
+    ];
+    let groups = run_aging_pipeline(Collection::Invoice, pipeline, &db, "receivable").await?;
+
+    let mut totals = AgingBuckets::default();
+    let rows: Vec<ReceivableAgingRow> = groups
+        .into_iter()
+        .map(|group| {
+            totals.add(&group.buckets);
+            ReceivableAgingRow {
+                client_id: group.owner_id,
+                client_name: group.owner_name,
+                invoice_count: group.count,
+                buckets: group.buckets,
+            }
+        })
+        .collect();
+
+    logger::log_info(
+        &format!(
+            "Receivable aging report with {} clients owing {:.2}",
+            rows.len(),
+            totals.total
+        ),
+        200,
+        None,
+    );
+    Ok(ReceivableAgingReport {
+        as_of,
+        rows,
+        totals,
+    })
+}
+
+// Open supplier bills bucketed by bill age as of a date, today by default
+#[tauri::command]
+pub async fn payable_aging_report(
+    as_of: Option<DateTime>,
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<PayableAgingReport> {
+    let db = db.lock().await;
+    let as_of = as_of.unwrap_or_else(DateTime::now);
+
+    let pipeline = vec![
+        doc! { "$match": { "billDate": { "$lte": as_of } } },
+        // Only payments made by `as_of` count, so bills paid later are still open
+        doc! { "$lookup": {
+            "from": Collection::SupplierPayment.as_str(),
+            "let": { "billId": "$_id" },
+            "pipeline": [
+                { "$match": { "$expr": { "$and": [
+                    { "$eq": ["$billId", "$$billId"] },
+                    { "$lte": ["$paidAt", as_of] },
+                ] } } },
+                { "$group": { "_id": null, "amount": { "$sum": "$amount" } } },
+            ],
+            "as": "paidAsOf",
+        } },
+        doc! { "$project": {
+            "supplierId": 1,
+            "balance": { "$subtract": [
+                "$totalAmount",
+                { "$ifNull": [{ "$first": "$paidAsOf.amount" }, 0.0] },
+            ] },
+            "age": age_in_days("$billDate", as_of),
+        } },
+        doc! { "$match": { "balance": { "$gte": MIN_OPEN_BALANCE } } },
+        doc! { "$lookup": {
+            "from": Collection::Supplier.as_str(),
+            "localField": "supplierId",
+            "foreignField": "_id",
+            "as": "supplier",
+        } },
+        doc! { "$project": {
+            "_id": 0,
+            "ownerId": "$supplierId",
+            "ownerName": { "$ifNull": [{ "$first": "$supplier.name" }, ""] },
+            "balance": 1,
+            "age": 1,
+        } },
+    ];
+    let groups = run_aging_pipeline(Collection::SupplierBill, pipeline, &db, "payable").await?;
+
+    let mut totals = AgingBuckets::default();
+    let rows: Vec<PayableAgingRow> = groups
+        .into_iter()
+        .map(|group| {
+            totals.add(&group.buckets);
+            PayableAgingRow {
+                supplier_id: group.owner_id,
+                supplier_name: group.owner_name,
+                bill_count: group.count,
+                buckets: group.buckets,
+            }
+        })
+        .collect();
+
+    logger::log_info(
+        &format!(
+            "Payable aging report with {} suppliers owed {:.2}",
+            rows.len(),
+            totals.total
+        ),
+        200,
+        None,
+    );
+    Ok(PayableAgingReport {
+        as_of,
+        rows,
+        totals,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::object_id;
+
+    fn item(owner: u8, balance: f64, age: f64) -> AgingItem {
+        AgingItem {
+            owner_id: object_id(owner),
+            owner_name: format!("Owner {}", owner),
+            balance,
+            age,
+        }
+    }
+
+    #[test]
+    fn balances_fall_in_buckets_by_age() {
+        let mut buckets = AgingBuckets::default();
+        for (age, balance) in [
+            (0, 1.0),
+            (30, 2.0),
+            (31, 4.0),
+            (60, 8.0),
+            (61, 16.0),
+            (90, 32.0),
+            (91, 64.0),
+            (120, 128.0),
+            (121, 256.0),
+            (400, 512.0),
+        ] {
+            buckets.add_balance(age, balance);
+        }
+        assert_eq!(buckets.current, 3.0);
+        assert_eq!(buckets.days_30, 12.0);
+        assert_eq!(buckets.days_60, 48.0);
+        assert_eq!(buckets.days_90, 192.0);
+        assert_eq!(buckets.days_120_plus, 768.0);
+        assert_eq!(buckets.total, 1023.0);
+    }
+
+    #[test]
+    fn items_are_grouped_by_owner_largest_total_first() {
+        let groups = group_by_age(vec![
+            item(1, 100.0, 10.0),
+            item(2, 50.0, 45.0),
+            item(1, 25.0, 130.0),
+            item(2, 200.0, 121.0),
+        ]);
+
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].owner_id, object_id(2));
+        assert_eq!(groups[0].count, 2);
+        assert_eq!(groups[0].buckets.days_30, 50.0);
+        assert_eq!(groups[0].buckets.days_120_plus, 200.0);
+        assert_eq!(groups[1].owner_name, "Owner 1");
+        assert_eq!(groups[1].buckets.current, 100.0);
+        assert_eq!(groups[1].buckets.days_120_plus, 25.0);
+        assert_eq!(groups[1].buckets.total, 125.0);
+    }
+
+    #[test]
+    fn no_items_make_no_rows() {
+        assert!(group_by_age(Vec::new()).is_empty());
+    }
+}
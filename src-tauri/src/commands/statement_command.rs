@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+
+use futures::TryStreamExt;
+use mongodb::bson::{doc, oid::ObjectId, DateTime};
+use tauri::{AppHandle, State};
+use tokio::sync::Mutex;
+
+use crate::{
+    commands::{company_command::load_company, document_command::save_document},
+    db::MongoDbState,
+    documents::statement::{build_statement_csv, build_statement_pdf},
+    schema::{
+        client_schema::Client,
+        collections::Collection,
+        error::{AppResult, ErrorResponse},
+        invoice_revision_schema::InvoiceRevision,
+        invoice_schema::Invoice,
+        statement_schema::ClientStatement,
+    },
+    statement::{build_statement, InvoiceHistory},
+    utils::parse_object_id,
+};
+
+/// Loads a client, failing with 404 when there is none with `client_id`.
+pub async fn load_client(db: &MongoDbState, client_id: ObjectId) -> AppResult<Client> {
+    db.get_collection::<Client>(Collection::Client)
+        .find_one(doc! { "_id": client_id })
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to fetch client", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to fetch client", Some(e.to_string()))
+        })?
+        .ok_or_else(|| {
+            logger::log_error(
+                &format!("Client not found for ID: {}", client_id),
+                404,
+                None,
+            );
+            ErrorResponse::new(
+                404,
+                &format!("Client not found for ID: {}", client_id),
+                None,
+            )
+        })
+}
+
+/// Builds the statement of account for a client from its invoices and their revisions.
+pub async fn load_statement(
+    db: &MongoDbState,
+    client_id: ObjectId,
+    from: Option<DateTime>,
+    to: Option<DateTime>,
+) -> AppResult<ClientStatement> {
+    if let (Some(from), Some(to)) = (from, to) {
+        if from > to {
+            logger::log_error("Invalid statement date range", 400, None);
+            return Err(ErrorResponse::new(
+                400,
+                "Invalid statement date range",
+                Some("The start date is after the end date".to_string()),
+            ));
+        }
+    }
+
+    let client = load_client(db, client_id).await?;
+
+    // Invoices issued after the range cannot affect it
+    let mut filter = doc! { "clientId": client_id };
+    if let Some(to) = to {
+        filter.insert("created_at", doc! { "$lte": to });
+    }
+    let invoices: Vec<Invoice> = db
+        .get_collection::<Invoice>(Collection::Invoice)
+        .find(filter)
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to fetch invoices", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to fetch invoices", Some(e.to_string()))
+        })?
+        .try_collect()
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to parse invoice data", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to parse invoice data", Some(e.to_string()))
+        })?;
+
+    let invoice_ids: Vec<ObjectId> = invoices.iter().filter_map(|i| i.id).collect();
+    let revisions: Vec<InvoiceRevision> = db
+        .get_collection::<InvoiceRevision>(Collection::InvoiceRevision)
+        .find(doc! { "invoiceId": { "$in": invoice_ids } })
+        .sort(doc! { "invoiceId": 1, "revision": 1 })
+        .await
+        .map_err(|e| {
+            logger::log_error(
+                "Failed to fetch invoice revisions",
+                500,
+                Some(&e.to_string()),
+            );
+            ErrorResponse::new(
+                500,
+                "Failed to fetch invoice revisions",
+                Some(e.to_string()),
+            )
+        })?
+        .try_collect()
+        .await
+        .map_err(|e| {
+            logger::log_error(
+                "Failed to parse invoice revision data",
+                500,
+                Some(&e.to_string()),
+            );
+            ErrorResponse::new(
+                500,
+                "Failed to parse invoice revision data",
+                Some(e.to_string()),
+            )
+        })?;
+
+    let mut revisions_by_invoice: HashMap<ObjectId, Vec<InvoiceRevision>> = HashMap::new();
+    for revision in revisions {
+        revisions_by_invoice
+            .entry(revision.invoice_id)
+            .or_default()
+            .push(revision);
+    }
+    let histories: Vec<InvoiceHistory> = invoices
+        .into_iter()
+        .map(|invoice| InvoiceHistory {
+            revisions: invoice
+                .id
+                .and_then(|id| revisions_by_invoice.remove(&id))
+                .unwrap_or_default(),
+            invoice,
+        })
+        .collect();
+
+    Ok(build_statement(&client, &histories, from, to))
+}
+
+// Statement of account for a client. Either end of the date range may be left open.
+#[tauri::command]
+pub async fn client_statement(
+    client_id: String,
+    from: Option<DateTime>,
+    to: Option<DateTime>,
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<ClientStatement> {
+    let db = db.lock().await;
+    let id = parse_object_id(&client_id, "Client")?;
+
+    let statement = load_statement(&db, id, from, to).await?;
+    logger::log_info(
+        &format!(
+            "Statement for client {} with {} lines",
+            client_id,
+            statement.lines.len()
+        ),
+        200,
+        None,
+    );
+    Ok(statement)
+}
+
+// Render a client statement to PDF and save it at the path chosen by the user
+#[tauri::command]
+pub async fn export_client_statement_pdf(
+    client_id: String,
+    from: Option<DateTime>,
+    to: Option<DateTime>,
+    path: String,
+    app: AppHandle,
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<String> {
+    let db = db.lock().await;
+    let id = parse_object_id(&client_id, "Client")?;
+
+    let statement = load_statement(&db, id, from, to).await?;
+    let company = load_company(&db).await?;
+    let pdf = build_statement_pdf(&statement, company.as_ref())?;
+    save_document(&app, &path, &pdf)?;
+
+    logger::log_info(
+        &format!("Exported statement for client {} to {}", client_id, path),
+        200,
+        None,
+    );
+    Ok(path)
+}
+
+// Export a client statement as CSV
+#[tauri::command]
+pub async fn export_client_statement_csv(
+    client_id: String,
+    from: Option<DateTime>,
+    to: Option<DateTime>,
+    path: String,
+    app: AppHandle,
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<String> {
+    let db = db.lock().await;
+    let id = parse_object_id(&client_id, "Client")?;
+
+    let statement = load_statement(&db, id, from, to).await?;
+    let csv = build_statement_csv(&statement)?;
+    save_document(&app, &path, &csv)?;
+
+    logger::log_info(
+        &format!("Exported statement for client {} to {}", client_id, path),
+        200,
+        None,
+    );
+    Ok(path)
+}
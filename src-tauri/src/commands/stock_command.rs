@@ -0,0 +1,338 @@
+use futures::TryStreamExt;
+use mongodb::{
+    bson::{doc, oid::ObjectId, DateTime, Document},
+    options::ReturnDocument,
+    ClientSession,
+};
+use tauri::{AppHandle, Emitter, State};
+use tokio::sync::Mutex;
+
+use crate::{
+    db::MongoDbState,
+    schema::{
+        collections::Collection,
+        error::{AppResult, ErrorResponse},
+        product_schema::Product,
+        stock_schema::{
+            LowStockAlert, StockHistory, StockMovement, StockMovementReason, StockSourceKind,
+        },
+    },
+    utils::parse_object_id,
+};
+
+/// Writes stock movements as part of the transaction changing the stock.
+pub async fn record_stock_movements(
+    db: &MongoDbState,
+    session: &mut ClientSession,
+    movements: Vec<StockMovement>,
+) -> AppResult<()> {
+    if movements.is_empty() {
+        return Ok(());
+    }
+    db.get_collection::<StockMovement>(Collection::StockMovement)
+        .insert_many(movements)
+        .session(session)
+        .await
+        .map_err(|e| {
+            logger::log_error(
+                "Failed to record stock movements",
+                500,
+                Some(&e.to_string()),
+            );
+            ErrorResponse::new(500, "Failed to record stock movements", Some(e.to_string()))
+        })?;
+    Ok(())
+}
+
+/// Builds the alert for a sale taking a product from above its reorder point
+/// to `new_stock`, or nothing when it does not cross it.
+pub fn low_stock_alert(
+    product: &Product,
+    previous_stock: i64,
+    new_stock: i64,
+) -> Option<LowStockAlert> {
+    if product.needs_reorder(previous_stock) || !product.needs_reorder(new_stock) {
+        return None;
+    }
+    Some(LowStockAlert {
+        product_id: product.id?,
+        name: product.name.clone(),
+        stock: new_stock,
+        reorder_point: product.reorder_point?,
+        reorder_quantity: product.reorder_quantity,
+    })
+}
+
+/// Tells the frontend about products that need reordering. Only sent once the
+/// change that caused them is committed; failing to send never fails the sale.
+pub fn emit_low_stock(app: &AppHandle, alerts: Vec<LowStockAlert>) {
+    for alert in alerts {
+        logger::log_warn(
+            &format!(
+                "Product {} is down to {} (reorder point {})",
+                alert.name, alert.stock, alert.reorder_point
+            ),
+            200,
+            None,
+        );
+        if let Err(e) = app.emit("low-stock", alert) {
+            logger::log_error("Failed to emit low stock event", 500, Some(&e.to_string()));
+        }
+    }
+}
+
+async fn adjust_in_session(
+    db: &MongoDbState,
+    session: &mut ClientSession,
+    id: ObjectId,
+    quantity: i64,
+    reason: StockMovementReason,
+    note: Option<String>,
+) -> AppResult<Product> {
+    // Stock can only be taken out while enough of it is on hand
+    let mut filter = doc! { "_id": id };
+    if quantity < 0 {
+        filter.insert("stock", doc! { "$gte": -quantity });
+    }
+    let collection = db.get_collection::<Product>(Collection::Product);
+    let product = collection
+        .find_one_and_update(
+            filter,
+            doc! {
+                "$inc": { "stock": quantity },
+                "$set": { "updated_at": DateTime::now() },
+            },
+        )
+        .return_document(ReturnDocument::After)
+        .session(&mut *session)
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to adjust stock", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to adjust stock", Some(e.to_string()))
+        })?;
+    let Some(product) = product else {
+        // Nothing was updated: tell a missing product from a short one
+        let product = collection
+            .find_one(doc! { "_id": id })
+            .session(&mut *session)
+            .await
+            .map_err(|e| {
+                logger::log_error("Failed to fetch product", 500, Some(&e.to_string()));
+                ErrorResponse::new(500, "Failed to fetch product", Some(e.to_string()))
+            })?
+            .ok_or_else(|| {
+                logger::log_error(&format!("Product not found with ID: {}", id), 404, None);
+                ErrorResponse::new(404, "Product not found", None)
+            })?;
+        let message = format!("Insufficient stock for product: {}", product.name);
+        let details = format!("{} in stock, {} requested", product.stock, -quantity);
+        logger::log_error(&message, 400, Some(&details));
+        return Err(ErrorResponse::new(400, &message, Some(details)));
+    };
+
+    let movement = StockMovement {
+        note,
+        ..StockMovement::new(id, quantity, reason, StockSourceKind::Product, id)
+    };
+    record_stock_movements(db, session, vec![movement]).await?;
+    Ok(product)
+}
+
+// Add or remove stock by hand, e.g. after a stock count or a return outside an invoice
+#[tauri::command]
+pub async fn adjust_product_stock(
+    product_id: String,
+    quantity: i64,
+    reason: Option<StockMovementReason>,
+    note: Option<String>,
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<Product> {
+    let db = db.lock().await;
+    let id = parse_object_id(&product_id, "Product")?;
+    let reason = reason.unwrap_or(StockMovementReason::Adjustment);
+
+    if quantity == 0 {
+        logger::log_error("Stock adjustment of zero", 400, None);
+        return Err(ErrorResponse::new(400, "Quantity must not be zero", None));
+    }
+    if reason == StockMovementReason::Sale {
+        logger::log_error("Manual stock adjustment recorded as a sale", 400, None);
+        return Err(ErrorResponse::new(
+            400,
+            "Sales are recorded by invoices",
+            None,
+        ));
+    }
+
+    let mut session = db.start_session().await?;
+    session.start_transaction().await.map_err(|e| {
+        logger::log_error(
+            "Failed to start MongoDB transaction.",
+            500,
+            Some(&e.to_string()),
+        );
+        ErrorResponse::new(
+            500,
+            "Failed to start MongoDB transaction.",
+            Some(e.to_string()),
+        )
+    })?;
+
+    let product = match adjust_in_session(&db, &mut session, id, quantity, reason, note).await {
+        Ok(product) => product,
+        Err(e) => {
+            session.abort_transaction().await.ok();
+            return Err(e);
+        }
+    };
+
+    session.commit_transaction().await.map_err(|e| {
+        logger::log_error(
+            "Failed to commit MongoDB transaction.",
+            500,
+            Some(&e.to_string()),
+        );
+        ErrorResponse::new(
+            500,
+            "Failed to commit MongoDB transaction.",
+            Some(e.to_string()),
+        )
+    })?;
+    logger::log_info(
+        &format!(
+            "Adjusted stock of product {} by {} ({:?})",
+            product_id, quantity, reason
+        ),
+        200,
+        None,
+    );
+    Ok(product)
+}
+
+// A product's stock movements, newest first, and its stock on hand as of a
+// date, now by default
+#[tauri::command]
+pub async fn product_stock_history(
+    product_id: String,
+    as_of: Option<DateTime>,
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<StockHistory> {
+    let db = db.lock().await;
+    let id = parse_object_id(&product_id, "Product")?;
+    let as_of = as_of.unwrap_or_else(DateTime::now);
+
+    let product = db
+        .get_collection::<Product>(Collection::Product)
+        .find_one(doc! { "_id": id })
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to fetch product", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to fetch product", Some(e.to_string()))
+        })?
+        .ok_or_else(|| {
+            logger::log_error("Product not found", 404, None);
+            ErrorResponse::new(404, "Product not found", None)
+        })?;
+
+    let collection = db.get_collection::<StockMovement>(Collection::StockMovement);
+    // Products created before the ledger have no opening movement, so the stock
+    // on a past date is worked back from the current stock
+    let later: Vec<Document> = collection
+        .aggregate(vec![
+            doc! { "$match": { "productId": id, "created_at": { "$gt": as_of } } },
+            doc! { "$group": { "_id": null, "quantity": { "$sum": "$quantity" } } },
+        ])
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to sum stock movements", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to sum stock movements", Some(e.to_string()))
+        })?
+        .try_collect()
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to sum stock movements", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to sum stock movements", Some(e.to_string()))
+        })?;
+    let later_quantity = later
+        .first()
+        .and_then(|total| {
+            total
+                .get_i64("quantity")
+                .ok()
+                .or_else(|| total.get_i32("quantity").ok().map(i64::from))
+        })
+        .unwrap_or(0);
+
+    let movements: Vec<StockMovement> = collection
+        .find(doc! { "productId": id, "created_at": { "$lte": as_of } })
+        .sort(doc! { "created_at": -1 })
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to fetch stock movements", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to fetch stock movements", Some(e.to_string()))
+        })?
+        .try_collect()
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to parse stock movements", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to parse stock movements", Some(e.to_string()))
+        })?;
+
+    logger::log_info(
+        &format!(
+            "Stock history of product {} with {} movements",
+            product_id,
+            movements.len()
+        ),
+        200,
+        None,
+    );
+    Ok(StockHistory {
+        product_id: id,
+        as_of,
+        stock_on_hand: product.stock - later_quantity,
+        movements,
+    })
+}
+
+// Products at or below their reorder point, lowest stock first
+#[tauri::command]
+pub async fn list_low_stock_products(
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<Vec<Product>> {
+    let db = db.lock().await;
+
+    let products: Vec<Product> = db
+        .get_collection::<Product>(Collection::Product)
+        .find(doc! {
+            "reorderPoint": { "$ne": null },
+            "$expr": { "$lte": ["$stock", "$reorderPoint"] },
+        })
+        .sort(doc! { "stock": 1 })
+        .await
+        .map_err(|e| {
+            logger::log_error(
+                "Failed to fetch low stock products",
+                500,
+                Some(&e.to_string()),
+            );
+            ErrorResponse::new(
+                500,
+                "Failed to fetch low stock products",
+                Some(e.to_string()),
+            )
+        })?
+        .try_collect()
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to parse product data", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to parse product data", Some(e.to_string()))
+        })?;
+
+    logger::log_info(
+        &format!("List {} products at or below reorder point", products.len()),
+        200,
+        None,
+    );
+    Ok(products)
+}
@@ -0,0 +1,167 @@
+use futures::TryStreamExt;
+use mongodb::{
+    bson::{doc, oid::ObjectId, DateTime as MongoDateTime},
+    options::ReturnDocument,
+};
+use tauri::State;
+use tokio::sync::Mutex;
+
+use crate::{
+    db::MongoDbState,
+    schema::{
+        collections::Collection,
+        error::{AppResult, ErrorResponse},
+        supplier_schema::{NewSupplier, Supplier},
+    },
+    utils::parse_object_id,
+    validation::validate_supplier,
+};
+
+/// Loads a supplier, failing with 404 when there is none with `id`.
+pub async fn load_supplier(db: &MongoDbState, id: ObjectId) -> AppResult<Supplier> {
+    db.get_collection::<Supplier>(Collection::Supplier)
+        .find_one(doc! { "_id": id })
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to fetch supplier", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to fetch supplier", Some(e.to_string()))
+        })?
+        .ok_or_else(|| {
+            logger::log_error(&format!("Supplier not found for ID: {}", id), 404, None);
+            ErrorResponse::new(404, "Supplier not found", None)
+        })
+}
+
+#[tauri::command]
+pub async fn create_supplier(
+    supplier: NewSupplier,
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<Supplier> {
+    let db = db.lock().await;
+
+    let errors = validate_supplier(&supplier);
+    if !errors.is_empty() {
+        logger::log_error("Invalid supplier", 400, None);
+        return Err(ErrorResponse::validation("Invalid supplier", errors));
+    }
+
+    let mut new_supplier = Supplier {
+        id: None,
+        name: supplier.name.trim().to_string(),
+        contact_name: supplier.contact_name,
+        email: supplier.email,
+        phone: supplier.phone,
+        address: supplier.address,
+        city: supplier.city,
+        tax_number: supplier.tax_number,
+        bills: Vec::new(),
+        total_owed: 0.0,
+        total_paid: 0.0,
+        outstanding_balance: 0.0,
+        created_at: MongoDateTime::now(),
+        updated_at: MongoDateTime::now(),
+    };
+    let result = db
+        .get_collection::<Supplier>(Collection::Supplier)
+        .insert_one(&new_supplier)
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to create supplier", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to create supplier", Some(e.to_string()))
+        })?;
+    new_supplier.id = result.inserted_id.as_object_id();
+
+    logger::log_info(
+        &format!("Created supplier {}", new_supplier.name),
+        201,
+        None,
+    );
+    Ok(new_supplier)
+}
+
+// Replace the details of a supplier
+#[tauri::command]
+pub async fn update_supplier(
+    supplier_id: String,
+    supplier: NewSupplier,
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<Supplier> {
+    let db = db.lock().await;
+    let id = parse_object_id(&supplier_id, "Supplier")?;
+
+    let errors = validate_supplier(&supplier);
+    if !errors.is_empty() {
+        logger::log_error("Invalid supplier", 400, None);
+        return Err(ErrorResponse::validation("Invalid supplier", errors));
+    }
+
+    let updated = db
+        .get_collection::<Supplier>(Collection::Supplier)
+        .find_one_and_update(
+            doc! { "_id": id },
+            doc! { "$set": {
+                "name": supplier.name.trim(),
+                "contactName": supplier.contact_name,
+                "email": supplier.email,
+                "phone": supplier.phone,
+                "address": supplier.address,
+                "city": supplier.city,
+                "taxNumber": supplier.tax_number,
+                "updated_at": MongoDateTime::now(),
+            } },
+        )
+        .return_document(ReturnDocument::After)
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to update supplier", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to update supplier", Some(e.to_string()))
+        })?
+        .ok_or_else(|| {
+            logger::log_error(&format!("Supplier not found for ID: {}", id), 404, None);
+            ErrorResponse::new(404, "Supplier not found", None)
+        })?;
+
+    logger::log_info(&format!("Updated supplier {}", supplier_id), 200, None);
+    Ok(updated)
+}
+
+#[tauri::command]
+pub async fn list_suppliers(db: State<'_, Mutex<MongoDbState>>) -> AppResult<Vec<Supplier>> {
+    let db = db.lock().await;
+
+    let suppliers: Vec<Supplier> = db
+        .get_collection::<Supplier>(Collection::Supplier)
+        .find(doc! {})
+        .sort(doc! { "name": 1 })
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to fetch suppliers", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to fetch suppliers", Some(e.to_string()))
+        })?
+        .try_collect()
+        .await
+        .map_err(|e| {
+            logger::log_error("Failed to parse supplier data", 500, Some(&e.to_string()));
+            ErrorResponse::new(500, "Failed to parse supplier data", Some(e.to_string()))
+        })?;
+
+    logger::log_info(&format!("List {} suppliers", suppliers.len()), 200, None);
+    Ok(suppliers)
+}
+
+#[tauri::command]
+pub async fn get_supplier_by_id(
+    supplier_id: String,
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<Supplier> {
+    let db = db.lock().await;
+    let id = parse_object_id(&supplier_id, "Supplier")?;
+
+    let supplier = load_supplier(&db, id).await?;
+    logger::log_info(
+        &format!("Found supplier with ID: {}", supplier_id),
+        200,
+        None,
+    );
+    Ok(supplier)
+}
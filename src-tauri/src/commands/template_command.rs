@@ -0,0 +1,69 @@
+use tauri::{AppHandle, State};
+use tokio::sync::Mutex;
+
+use crate::{
+    commands::{
+        company_command::load_company,
+        document_command::{app_templates_dir, load_invoice_with_client, render_with_template},
+    },
+    db::MongoDbState,
+    documents::templates::{self, TemplateData, TemplateInfo},
+    schema::error::AppResult,
+    utils::parse_object_id,
+};
+
+#[tauri::command]
+pub async fn list_invoice_templates(app: AppHandle) -> AppResult<Vec<TemplateInfo>> {
+    let dir = app_templates_dir(&app)?;
+    let templates = templates::list_templates(&dir)?;
+    logger::log_info(
+        &format!("List invoice templates with count: {}", templates.len()),
+        200,
+        None,
+    );
+    Ok(templates)
+}
+
+// Render a template to PDF bytes, using a real invoice if one is given or sample data otherwise
+#[tauri::command]
+pub async fn preview_invoice_template(
+    template_id: String,
+    invoice_id: Option<String>,
+    app: AppHandle,
+    db: State<'_, Mutex<MongoDbState>>,
+) -> AppResult<Vec<u8>> {
+    let db = db.lock().await;
+    let company = load_company(&db).await?;
+
+    let data = match invoice_id {
+        Some(invoice_id) => {
+            let id = parse_object_id(&invoice_id, "Invoice")?;
+            let (invoice, client) = load_invoice_with_client(&db, id).await?;
+            TemplateData::new(&invoice, &client, company.as_ref())
+        }
+        None => TemplateData::sample(company.as_ref()),
+    };
+
+    let pdf = render_with_template(&app, Some(&template_id), &data)?;
+    logger::log_info(
+        &format!("Preview invoice template: {}", template_id),
+        200,
+        None,
+    );
+    Ok(pdf)
+}
+
+#[tauri::command]
+pub async fn set_default_invoice_template(
+    template_id: String,
+    app: AppHandle,
+) -> AppResult<Vec<TemplateInfo>> {
+    let dir = app_templates_dir(&app)?;
+    templates::set_default_template_id(&dir, &template_id)?;
+    logger::log_info(
+        &format!("Set default invoice template: {}", template_id),
+        200,
+        None,
+    );
+    templates::list_templates(&dir)
+}
@@ -1,5 +1,7 @@
 use mongodb::{
-    options::ClientOptions, Client, ClientSession, Collection as MongoCollection, Database,
+    bson::{doc, Document},
+    options::ClientOptions,
+    Client, ClientSession, Collection as MongoCollection, Database, IndexModel,
 };
 use std::env;
 
@@ -41,8 +43,11 @@ pub async fn init_db() -> Result<MongoDbState, ErrorResponse> {
     // Access the "accounting" database
     let database = client.database("accounting");
 
+    let state = MongoDbState { database, client };
+    state.ensure_indexes().await?;
+
     // Return the initialized MongoDbState
-    Ok(MongoDbState { database, client })
+    Ok(state)
 }
 
 impl MongoDbState {
@@ -51,6 +56,33 @@ impl MongoDbState {
         self.database.collection(collection.as_str())
     }
 
+    /// Create the indexes used by filtered and sorted queries
+    async fn ensure_indexes(&self) -> Result<(), ErrorResponse> {
+        let invoices = vec![
+            IndexModel::builder()
+                .keys(doc! { "created_at": -1, "_id": -1 })
+                .build(),
+            IndexModel::builder()
+                .keys(doc! { "clientId": 1, "created_at": -1 })
+                .build(),
+            IndexModel::builder()
+                .keys(doc! { "status": 1, "created_at": -1 })
+                .build(),
+            IndexModel::builder().keys(doc! { "totalPrice": 1 }).build(),
+        ];
+        self.get_collection::<Document>(Collection::Invoice)
+            .create_indexes(invoices)
+            .await
+            .map_err(|e| {
+                ErrorResponse::new(
+                    500,
+                    "Failed to create invoice indexes.",
+                    Some(e.to_string()),
+                )
+            })?;
+        Ok(())
+    }
+
     /// Start a new session
     pub async fn start_session(&self) -> Result<ClientSession, ErrorResponse> {
         self.client.start_session().await.map_err(|e| {
@@ -0,0 +1,188 @@
+use std::collections::BTreeSet;
+
+use mongodb::bson::{self, Bson, Document};
+
+use crate::schema::{
+    error::{AppResult, ErrorResponse},
+    invoice_revision_schema::FieldChange,
+    invoice_schema::Invoice,
+};
+
+// Bookkeeping fields that change on every save
+const IGNORED_FIELDS: [&str; 2] = ["_id", "updated_at"];
+
+/// Lists the fields that differ between two versions of an invoice.
+pub fn diff_invoices(before: &Invoice, after: &Invoice) -> AppResult<Vec<FieldChange>> {
+    let to_document = |invoice: &Invoice| {
+        bson::to_document(invoice).map_err(|e| {
+            logger::log_error(
+                "Failed to serialize invoice data",
+                500,
+                Some(&e.to_string()),
+            );
+            ErrorResponse::new(500, "Failed to serialize invoice data", Some(e.to_string()))
+        })
+    };
+    let mut before = to_document(before)?;
+    let mut after = to_document(after)?;
+    for field in IGNORED_FIELDS {
+        before.remove(field);
+        after.remove(field);
+    }
+
+    // Key goods by line ID so reordering lines is not reported as a change. Lines
+    // stored before IDs were kept are keyed by position
+    for document in [&mut before, &mut after] {
+        if let Some(Bson::Array(goods)) = document.remove("goods") {
+            let mut lines = Document::new();
+            for line in goods {
+                if let Bson::Document(line) = line {
+                    let key = match line.get("lineId") {
+                        Some(Bson::ObjectId(id)) => id.to_hex(),
+                        _ => lines.len().to_string(),
+                    };
+                    lines.insert(key, line);
+                }
+            }
+            document.insert("goods", lines);
+        }
+    }
+
+    let mut changes = Vec::new();
+    diff_documents("", &before, &after, &mut changes);
+    Ok(changes)
+}
+
+fn diff_documents(prefix: &str, before: &Document, after: &Document, out: &mut Vec<FieldChange>) {
+    let keys: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+    for key in keys {
+        let field = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        match (before.get(key), after.get(key)) {
+            (Some(Bson::Document(b)), Some(Bson::Document(a))) => diff_documents(&field, b, a, out),
+            (b, a) if b != a => out.push(FieldChange {
+                field,
+                before: b.cloned(),
+                after: a.cloned(),
+            }),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mongodb::bson::oid::ObjectId;
+
+    use super::*;
+    use crate::{
+        schema::invoice_schema::Goods,
+        test_fixtures::{date, invoice, object_id},
+    };
+
+    fn line(n: u8, price: f64, quantity: i64) -> Goods {
+        Goods {
+            line_id: Some(object_id(n)),
+            name: format!("Line {}", n),
+            price,
+            quantity,
+            product_id: object_id(10),
+            list_price: Some(price),
+            price_overridden: false,
+            override_reason: None,
+            price_list_id: None,
+        }
+    }
+
+    fn sample() -> Invoice {
+        invoice(
+            21,
+            vec![line(31, 10.0, 1), line(32, 20.0, 2)],
+            0.0,
+            date(2024, 3, 1),
+        )
+    }
+
+    fn fields(changes: &[FieldChange]) -> Vec<&str> {
+        changes.iter().map(|c| c.field.as_str()).collect()
+    }
+
+    #[test]
+    fn reordered_lines_are_not_a_change() {
+        let before = sample();
+        let mut after = before.clone();
+        after.goods.reverse();
+
+        assert!(diff_invoices(&before, &after).unwrap().is_empty());
+    }
+
+    #[test]
+    fn changed_quantity_is_keyed_by_line() {
+        let before = sample();
+        let mut after = before.clone();
+        after.goods[1].quantity = 5;
+
+        let changes = diff_invoices(&before, &after).unwrap();
+        let field = format!("goods.{}.quantity", object_id(32).to_hex());
+        assert_eq!(fields(&changes), [field.as_str()]);
+        assert_eq!(changes[0].before, Some(Bson::Int64(2)));
+        assert_eq!(changes[0].after, Some(Bson::Int64(5)));
+    }
+
+    #[test]
+    fn added_and_removed_lines() {
+        let before = sample();
+        let mut after = before.clone();
+        after.goods.remove(0);
+        after.goods.push(line(33, 30.0, 1));
+
+        let changes = diff_invoices(&before, &after).unwrap();
+        let removed = format!("goods.{}", object_id(31).to_hex());
+        let added = format!("goods.{}", object_id(33).to_hex());
+        assert_eq!(fields(&changes), [removed.as_str(), added.as_str()]);
+        assert!(changes[0].after.is_none());
+        assert!(changes[1].before.is_none());
+    }
+
+    #[test]
+    fn changed_total_paid() {
+        let before = sample();
+        let after = Invoice {
+            total_paid: 25.0,
+            ..before.clone()
+        };
+
+        let changes = diff_invoices(&before, &after).unwrap();
+        assert_eq!(fields(&changes), ["totalPaid"]);
+        assert_eq!(changes[0].before, Some(Bson::Double(0.0)));
+        assert_eq!(changes[0].after, Some(Bson::Double(25.0)));
+    }
+
+    #[test]
+    fn bookkeeping_fields_are_ignored() {
+        let before = sample();
+        let after = Invoice {
+            id: Some(ObjectId::new()),
+            updated_at: date(2024, 4, 1),
+            ..before.clone()
+        };
+
+        assert!(diff_invoices(&before, &after).unwrap().is_empty());
+    }
+
+    #[test]
+    fn legacy_lines_are_keyed_by_position() {
+        let mut before = sample();
+        for goods in &mut before.goods {
+            goods.line_id = None;
+        }
+        let mut after = before.clone();
+        after.goods[1].quantity = 3;
+
+        let changes = diff_invoices(&before, &after).unwrap();
+        assert_eq!(fields(&changes), ["goods.1.quantity"]);
+    }
+}
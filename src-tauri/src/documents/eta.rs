@@ -0,0 +1,594 @@
+//! Egyptian Tax Authority (ETA) e-invoice documents.
+//!
+//! Maps invoices onto the ETA document structure and produces the canonical
+//! serialization the signature is computed over. Submission to the ETA API is
+//! out of scope; signing goes through [`EtaSigner`] so a hardware token signer
+//! can replace [`LocalTestSigner`] later.
+//!
+//! Invoice prices are treated as net amounts and the company VAT rate is
+//! applied on top of every line as tax type `T1`.
+
+use std::collections::HashMap;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+use serde_json::{Number, Value};
+use sha2::{Digest, Sha256};
+
+use crate::schema::{
+    client_schema::Client,
+    company_schema::{Company, EtaSettings},
+    error::{AppResult, ErrorResponse},
+    invoice_schema::{BillingAddress, Invoice},
+};
+
+// Unsigned documents are only accepted with version 0.9
+const UNSIGNED_VERSION: &str = "0.9";
+const SIGNED_VERSION: &str = "1.0";
+const CURRENCY: &str = "EGP";
+const COUNTRY: &str = "EG";
+const VAT_TAX_TYPE: &str = "T1";
+const VAT_SUB_TYPE: &str = "V009"; // General item sales
+const UNIT_TYPE: &str = "EA";
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct EtaAddress {
+    #[serde(rename = "branchID", skip_serializing_if = "Option::is_none")]
+    pub branch_id: Option<String>,
+    pub country: String,
+    pub governate: String,
+    pub region_city: String,
+    pub street: String,
+    pub building_number: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct EtaParty {
+    pub address: EtaAddress,
+    #[serde(rename = "type")]
+    pub party_type: String, // "B" business, "P" person, "F" foreigner
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct EtaUnitValue {
+    pub currency_sold: String,
+    #[serde(rename = "amountEGP")]
+    pub amount_egp: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct EtaDiscount {
+    pub rate: f64,
+    pub amount: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct EtaTaxableItem {
+    pub tax_type: String,
+    pub amount: f64,
+    pub sub_type: String,
+    pub rate: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct EtaInvoiceLine {
+    pub description: String,
+    pub item_type: String,
+    pub item_code: String,
+    pub unit_type: String,
+    pub quantity: f64,
+    pub internal_code: String,
+    pub sales_total: f64,
+    pub total: f64,
+    pub value_difference: f64,
+    pub total_taxable_fees: f64,
+    pub net_total: f64,
+    pub items_discount: f64,
+    pub unit_value: EtaUnitValue,
+    pub discount: EtaDiscount,
+    pub taxable_items: Vec<EtaTaxableItem>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct EtaTaxTotal {
+    pub tax_type: String,
+    pub amount: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct EtaSignature {
+    pub signature_type: String, // "I" issuer, "S" service provider
+    pub value: String,
+}
+
+/// An invoice in the ETA document structure. Field order matters: the
+/// canonical serialization follows it.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct EtaDocument {
+    pub issuer: EtaParty,
+    pub receiver: EtaParty,
+    pub document_type: String,
+    pub document_type_version: String,
+    pub date_time_issued: String,
+    pub taxpayer_activity_code: String,
+    #[serde(rename = "internalID")]
+    pub internal_id: String,
+    pub invoice_lines: Vec<EtaInvoiceLine>,
+    pub total_discount_amount: f64,
+    pub total_sales_amount: f64,
+    pub net_amount: f64,
+    pub tax_totals: Vec<EtaTaxTotal>,
+    pub total_amount: f64,
+    pub extra_discount_amount: f64,
+    pub total_items_discount_amount: f64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub signatures: Vec<EtaSignature>,
+}
+
+/// Signs the canonical serialization of an ETA document.
+pub trait EtaSigner {
+    /// The signature type recorded on the document.
+    fn signature_type(&self) -> &str {
+        "I"
+    }
+
+    /// Returns the base64 encoded signature of `canonical`.
+    fn sign(&self, canonical: &[u8]) -> AppResult<String>;
+}
+
+/// Stands in for a real signing token during development. The "signature" is
+/// the base64 encoded SHA-256 digest of the canonical document, which the ETA
+/// will reject.
+pub struct LocalTestSigner;
+
+impl EtaSigner for LocalTestSigner {
+    fn sign(&self, canonical: &[u8]) -> AppResult<String> {
+        Ok(STANDARD.encode(Sha256::digest(canonical)))
+    }
+}
+
+fn eta_error(message: &str, details: Option<String>) -> ErrorResponse {
+    logger::log_error(message, 400, details.as_deref());
+    ErrorResponse::new(400, message, details)
+}
+
+// The ETA accepts at most five decimal places
+fn round5(amount: f64) -> f64 {
+    (amount * 100_000.0).round() / 100_000.0
+}
+
+// `YYYY-MM-DDTHH:MM:SSZ`, without fractional seconds
+fn format_date_time(date: &DateTime) -> String {
+    let rfc3339 = date.try_to_rfc3339_string().unwrap_or_default();
+    format!("{}Z", rfc3339.chars().take(19).collect::<String>())
+}
+
+fn receiver(billed_to: &BillingAddress) -> EtaParty {
+    let tax_number = billed_to
+        .tax_number
+        .as_deref()
+        .map(str::trim)
+        .filter(|t| !t.is_empty());
+    // Clients only have a city, which stands in for the governate as well
+    EtaParty {
+        address: EtaAddress {
+            branch_id: None,
+            country: COUNTRY.to_string(),
+            governate: billed_to.city.clone(),
+            region_city: billed_to.city.clone(),
+            street: billed_to.address.clone(),
+            building_number: String::new(),
+        },
+        party_type: if tax_number.is_some() { "B" } else { "P" }.to_string(),
+        id: tax_number.unwrap_or_default().to_string(),
+        name: billed_to.name.clone(),
+    }
+}
+
+/// Maps an invoice onto an unsigned ETA document.
+///
+/// `item_codes` maps product IDs to their registered item codes; every line
+/// needs one.
+pub fn build_eta_document(
+    invoice: &Invoice,
+    client: &Client,
+    company: &Company,
+    item_codes: &HashMap<ObjectId, String>,
+) -> AppResult<EtaDocument> {
+    let settings: &EtaSettings = company.eta.as_ref().ok_or_else(|| {
+        eta_error(
+            "ETA settings not configured",
+            Some("Add the ETA registration details to the company settings".to_string()),
+        )
+    })?;
+    let tax_number = company
+        .tax_number
+        .as_deref()
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .ok_or_else(|| eta_error("Company tax number is required for ETA invoices", None))?;
+    if company.currency != CURRENCY {
+        return Err(eta_error(
+            "ETA invoices must be issued in EGP",
+            Some(format!("Company currency is {}", company.currency)),
+        ));
+    }
+
+    let mut invoice_lines = Vec::with_capacity(invoice.goods.len());
+    let mut total_sales = 0.0;
+    let mut total_tax = 0.0;
+    for goods in &invoice.goods {
+        let item_code = item_codes
+            .get(&goods.product_id)
+            .filter(|code| !code.trim().is_empty())
+            .ok_or_else(|| {
+                eta_error(
+                    &format!("Product {} has no ETA item code", goods.name),
+                    Some(goods.product_id.to_hex()),
+                )
+            })?;
+
+        let sales_total = round5(goods.price * goods.quantity as f64);
+        let tax = round5(sales_total * settings.vat_rate / 100.0);
+        total_sales += sales_total;
+        total_tax += tax;
+
+        invoice_lines.push(EtaInvoiceLine {
+            description: goods.name.clone(),
+            item_type: settings.item_type.clone(),
+            item_code: item_code.clone(),
+            unit_type: UNIT_TYPE.to_string(),
+            quantity: goods.quantity as f64,
+            internal_code: goods.product_id.to_hex(),
+            sales_total,
+            total: round5(sales_total + tax),
+            value_difference: 0.0,
+            total_taxable_fees: 0.0,
+            net_total: sales_total,
+            items_discount: 0.0,
+            unit_value: EtaUnitValue {
+                currency_sold: CURRENCY.to_string(),
+                amount_egp: round5(goods.price),
+            },
+            discount: EtaDiscount {
+                rate: 0.0,
+                amount: 0.0,
+            },
+            taxable_items: vec![EtaTaxableItem {
+                tax_type: VAT_TAX_TYPE.to_string(),
+                amount: tax,
+                sub_type: VAT_SUB_TYPE.to_string(),
+                rate: settings.vat_rate,
+            }],
+        });
+    }
+    let total_sales = round5(total_sales);
+    let total_tax = round5(total_tax);
+
+    Ok(EtaDocument {
+        issuer: EtaParty {
+            address: EtaAddress {
+                branch_id: Some(settings.branch_id.clone()),
+                country: company.country.clone().unwrap_or(COUNTRY.to_string()),
+                governate: settings.governate.clone(),
+                region_city: settings.region_city.clone(),
+                street: company.address.clone(),
+                building_number: settings.building_number.clone(),
+            },
+            party_type: "B".to_string(),
+            id: tax_number.to_string(),
+            name: company.name.clone(),
+        },
+        receiver: receiver(&invoice.billed_to(client)),
+        document_type: "I".to_string(),
+        document_type_version: UNSIGNED_VERSION.to_string(),
+        date_time_issued: format_date_time(&invoice.created_at),
+        taxpayer_activity_code: settings.activity_code.clone(),
+        internal_id: invoice.id.map(|id| id.to_hex()).unwrap_or_default(),
+        invoice_lines,
+        total_discount_amount: 0.0,
+        total_sales_amount: total_sales,
+        net_amount: total_sales,
+        tax_totals: vec![EtaTaxTotal {
+            tax_type: VAT_TAX_TYPE.to_string(),
+            amount: total_tax,
+        }],
+        total_amount: round5(total_sales + total_tax),
+        extra_discount_amount: 0.0,
+        total_items_discount_amount: 0.0,
+        signatures: Vec::new(),
+    })
+}
+
+// Quotes and backslashes inside a value are escaped, so values cannot run
+// into each other
+fn quoted(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// Whole amounts without a fraction, e.g. `14` rather than `14.0`, as the
+// ETA reference serializer writes them
+fn format_number(number: &Number) -> String {
+    if let Some(integer) = number.as_i64() {
+        return integer.to_string();
+    }
+    if let Some(integer) = number.as_u64() {
+        return integer.to_string();
+    }
+    let amount = number.as_f64().unwrap_or_default();
+    // No "-0" for amounts rounded to zero
+    if amount == 0.0 {
+        "0".to_string()
+    } else {
+        amount.to_string()
+    }
+}
+
+fn canonicalize_value(value: &Value, out: &mut String) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                let name = quoted(&key.to_uppercase());
+                out.push_str(&name);
+                match value {
+                    // Every array element is preceded by the array name again
+                    Value::Array(items) => {
+                        for item in items {
+                            out.push_str(&name);
+                            canonicalize_value(item, out);
+                        }
+                    }
+                    value => canonicalize_value(value, out),
+                }
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                canonicalize_value(item, out);
+            }
+        }
+        Value::String(s) => out.push_str(&quoted(s)),
+        Value::Number(number) => out.push_str(&quoted(&format_number(number))),
+        Value::Bool(value) => out.push_str(&quoted(&value.to_string())),
+        Value::Null => out.push_str("\"\""),
+    }
+}
+
+/// Produces the ETA canonical serialization of a document, excluding its
+/// signatures.
+pub fn canonicalize(document: &EtaDocument) -> AppResult<String> {
+    let mut value = serde_json::to_value(document).map_err(|e| {
+        logger::log_error(
+            "Failed to serialize ETA document",
+            500,
+            Some(&e.to_string()),
+        );
+        ErrorResponse::new(500, "Failed to serialize ETA document", Some(e.to_string()))
+    })?;
+    if let Value::Object(map) = &mut value {
+        map.shift_remove("signatures");
+    }
+
+    let mut out = String::new();
+    canonicalize_value(&value, &mut out);
+    Ok(out)
+}
+
+/// Signs the document, replacing any earlier signature of the same type.
+pub fn sign_document(document: &mut EtaDocument, signer: &dyn EtaSigner) -> AppResult<()> {
+    document.document_type_version = SIGNED_VERSION.to_string();
+    let canonical = canonicalize(document)?;
+    let value = signer.sign(canonical.as_bytes())?;
+
+    let signature_type = signer.signature_type().to_string();
+    document
+        .signatures
+        .retain(|s| s.signature_type != signature_type);
+    document.signatures.push(EtaSignature {
+        signature_type,
+        value,
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        schema::{invoice_schema::Goods, product_schema::Product},
+        test_fixtures::{client, company, date, invoice, object_id, product},
+    };
+
+    /// Line `n` selling `quantity` of `product` at its catalog price.
+    fn line(n: u8, product: &Product, quantity: i64) -> Goods {
+        Goods {
+            line_id: Some(object_id(n)),
+            name: product.name.clone(),
+            price: product.price,
+            quantity,
+            product_id: product.id.unwrap(),
+            list_price: Some(product.price),
+            price_overridden: false,
+            override_reason: None,
+            price_list_id: None,
+        }
+    }
+
+    fn eta_company() -> Company {
+        Company {
+            eta: Some(EtaSettings {
+                branch_id: "0".to_string(),
+                governate: "Alexandria".to_string(),
+                region_city: "Smouha".to_string(),
+                building_number: "5".to_string(),
+                activity_code: "4620".to_string(),
+                item_type: "EGS".to_string(),
+                vat_rate: 14.0,
+            }),
+            ..company()
+        }
+    }
+
+    fn sample_document() -> EtaDocument {
+        let pens = product(11, "Ball pens", 12.5);
+        let paper = product(12, "A4 paper \"80g\"", 100.0);
+        let invoice = invoice(
+            21,
+            vec![line(31, &pens, 4), line(32, &paper, 2)],
+            0.0,
+            date(2024, 3, 5),
+        );
+        let item_codes = HashMap::from([
+            (pens.id.unwrap(), "EG-100-11".to_string()),
+            (paper.id.unwrap(), "EG-100-12".to_string()),
+        ]);
+        build_eta_document(&invoice, &client(), &eta_company(), &item_codes).unwrap()
+    }
+
+    fn canonical(value: Value) -> String {
+        let mut out = String::new();
+        canonicalize_value(&value, &mut out);
+        out
+    }
+
+    // The issuer block of the ETA SDK serialization example
+    #[test]
+    fn canonicalizes_nested_objects() {
+        let issuer = json!({
+            "issuer": {
+                "address": {
+                    "branchID": "0",
+                    "country": "EG",
+                    "governate": "Cairo",
+                    "regionCity": "Nasr City",
+                    "street": "580 Clementina Key",
+                    "buildingNumber": "Bldg. 0",
+                },
+                "type": "B",
+                "id": "113317713",
+                "name": "Issuer Company",
+            }
+        });
+        assert_eq!(
+            canonical(issuer),
+            "\"ISSUER\"\"ADDRESS\"\"BRANCHID\"\"0\"\"COUNTRY\"\"EG\"\"GOVERNATE\"\"Cairo\"\
+             \"REGIONCITY\"\"Nasr City\"\"STREET\"\"580 Clementina Key\"\
+             \"BUILDINGNUMBER\"\"Bldg. 0\"\"TYPE\"\"B\"\"ID\"\"113317713\"\
+             \"NAME\"\"Issuer Company\""
+        );
+    }
+
+    #[test]
+    fn repeats_array_name_before_each_element() {
+        let value = json!({
+            "taxTotals": [
+                { "taxType": "T1", "amount": 14.0 },
+                { "taxType": "T2", "amount": 1.5 },
+            ],
+            "signatures": [],
+        });
+        assert_eq!(
+            canonical(value),
+            "\"TAXTOTALS\"\"TAXTOTALS\"\"TAXTYPE\"\"T1\"\"AMOUNT\"\"14\"\
+             \"TAXTOTALS\"\"TAXTYPE\"\"T2\"\"AMOUNT\"\"1.5\"\"SIGNATURES\""
+        );
+    }
+
+    #[test]
+    fn formats_numbers_and_escapes_strings() {
+        let value = json!({
+            "quantity": 3,
+            "rate": 0.0,
+            "discount": -0.0,
+            "amount": 1234.56789,
+            "description": "Cable 2\" \\ 5m",
+            "branchID": null,
+        });
+        assert_eq!(
+            canonical(value),
+            "\"QUANTITY\"\"3\"\"RATE\"\"0\"\"DISCOUNT\"\"0\"\"AMOUNT\"\"1234.56789\"\
+             \"DESCRIPTION\"\"Cable 2\\\" \\\\ 5m\"\"BRANCHID\"\"\""
+        );
+    }
+
+    #[test]
+    fn builds_document_with_vat() {
+        let document = sample_document();
+
+        assert_eq!(document.issuer.id, "100-200-300");
+        assert_eq!(document.issuer.address.branch_id.as_deref(), Some("0"));
+        assert_eq!(document.receiver.party_type, "B");
+        assert_eq!(document.receiver.id, "200-300-400");
+        assert_eq!(document.receiver.name, "Nile Traders");
+        assert_eq!(document.date_time_issued, "2024-03-05T00:00:00Z");
+        assert_eq!(document.document_type_version, UNSIGNED_VERSION);
+
+        let line = &document.invoice_lines[0];
+        assert_eq!(line.item_code, "EG-100-11");
+        assert_eq!(line.sales_total, 50.0);
+        assert_eq!(line.taxable_items[0].amount, 7.0);
+        assert_eq!(line.total, 57.0);
+
+        assert_eq!(document.total_sales_amount, 250.0);
+        assert_eq!(document.tax_totals[0].amount, 35.0);
+        assert_eq!(document.total_amount, 285.0);
+
+        let canonical = canonicalize(&document).unwrap();
+        assert!(canonical.starts_with("\"ISSUER\"\"ADDRESS\"\"BRANCHID\"\"0\"\"COUNTRY\"\"EG\""));
+        assert!(canonical.contains("\"DESCRIPTION\"\"A4 paper \\\"80g\\\"\""));
+        assert!(canonical.contains("\"QUANTITY\"\"4\"\"INTERNALCODE\""));
+        assert!(canonical.ends_with(
+            "\"TOTALAMOUNT\"\"285\"\"EXTRADISCOUNTAMOUNT\"\"0\"\"TOTALITEMSDISCOUNTAMOUNT\"\"0\""
+        ));
+    }
+
+    #[test]
+    fn requires_item_codes_and_egp() {
+        let pens = product(11, "Ball pens", 12.5);
+        let invoice = invoice(21, vec![line(31, &pens, 4)], 0.0, date(2024, 3, 5));
+
+        let error =
+            build_eta_document(&invoice, &client(), &eta_company(), &HashMap::new()).unwrap_err();
+        assert_eq!(error.message, "Product Ball pens has no ETA item code");
+
+        let item_codes = HashMap::from([(pens.id.unwrap(), "EG-100-11".to_string())]);
+        let dollars = Company {
+            currency: "USD".to_string(),
+            ..eta_company()
+        };
+        let error = build_eta_document(&invoice, &client(), &dollars, &item_codes).unwrap_err();
+        assert_eq!(error.message, "ETA invoices must be issued in EGP");
+
+        let error = build_eta_document(&invoice, &client(), &company(), &item_codes).unwrap_err();
+        assert_eq!(error.message, "ETA settings not configured");
+    }
+
+    #[test]
+    fn signs_canonical_form_without_signatures() {
+        let mut document = sample_document();
+        sign_document(&mut document, &LocalTestSigner).unwrap();
+        sign_document(&mut document, &LocalTestSigner).unwrap();
+
+        assert_eq!(document.document_type_version, SIGNED_VERSION);
+        assert_eq!(document.signatures.len(), 1);
+        assert_eq!(document.signatures[0].signature_type, "I");
+        let canonical = canonicalize(&document).unwrap();
+        assert!(!canonical.contains("SIGNATURES"));
+        assert_eq!(
+            document.signatures[0].value,
+            STANDARD.encode(Sha256::digest(canonical.as_bytes()))
+        );
+    }
+}
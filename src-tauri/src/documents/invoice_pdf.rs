@@ -0,0 +1,171 @@
+use std::io::Cursor;
+
+use printpdf::{image_crate, BuiltinFont, Image, ImageTransform, Mm, PdfDocumentReference};
+
+use crate::schema::error::{AppResult, ErrorResponse};
+
+use super::{
+    pdf::{Canvas, Fonts, LINE_HEIGHT, MARGIN, PAGE_WIDTH, ROW_HEIGHT},
+    templates::{RenderedTemplate, TemplateData},
+};
+
+const LOGO_MAX_HEIGHT: f32 = 20.0;
+const LOGO_MAX_WIDTH: f32 = 60.0;
+
+// Column anchors for the line item table
+const COL_ITEM: f32 = MARGIN;
+const COL_QTY: f32 = 120.0;
+const COL_PRICE: f32 = 155.0;
+const COL_AMOUNT: f32 = PAGE_WIDTH - MARGIN;
+
+fn pdf_error(e: impl ToString) -> ErrorResponse {
+    super::pdf::pdf_error("Failed to render invoice PDF", e)
+}
+
+fn load_fonts(doc: &PdfDocumentReference, template: &RenderedTemplate) -> AppResult<Fonts> {
+    let regular = match &template.font {
+        Some(font) => doc.add_external_font(Cursor::new(font)),
+        None => doc.add_builtin_font(BuiltinFont::Helvetica),
+    }
+    .map_err(pdf_error)?;
+    let bold = match (&template.bold_font, &template.font) {
+        (Some(font), _) => doc
+            .add_external_font(Cursor::new(font))
+            .map_err(pdf_error)?,
+        // A custom regular font without a bold variant is used for both
+        (None, Some(_)) => regular.clone(),
+        (None, None) => doc
+            .add_builtin_font(BuiltinFont::HelveticaBold)
+            .map_err(pdf_error)?,
+    };
+    Ok(Fonts { regular, bold })
+}
+
+/// Draws the logo in the top-left corner and returns the height it takes up.
+fn draw_logo(canvas: &Canvas, logo: &[u8], top: f32) -> AppResult<f32> {
+    let image = image_crate::load_from_memory(logo).map_err(pdf_error)?;
+    let (width, height) = (image.width() as f32, image.height() as f32);
+    // Pick the resolution that fits the logo inside its box
+    let dpi = (height * 25.4 / LOGO_MAX_HEIGHT).max(width * 25.4 / LOGO_MAX_WIDTH);
+    let logo_height = height * 25.4 / dpi;
+
+    Image::from_dynamic_image(&image_crate::DynamicImage::ImageRgb8(image.to_rgb8())).add_to_layer(
+        canvas.layer.clone(),
+        ImageTransform {
+            translate_x: Some(Mm(MARGIN)),
+            translate_y: Some(Mm(top - logo_height)),
+            dpi: Some(dpi),
+            ..Default::default()
+        },
+    );
+    Ok(logo_height)
+}
+
+/// Renders an invoice as a printable A4 PDF using a template.
+///
+/// The document is built entirely in memory from the template's own assets or
+/// the standard PDF fonts, so no network access or installed fonts are required.
+pub fn build_invoice_pdf(data: &TemplateData, template: &RenderedTemplate) -> AppResult<Vec<u8>> {
+    let labels = &template.labels;
+
+    let mut canvas = Canvas::new(format!("{} {}", labels.title, data.invoice.number), |doc| {
+        load_fonts(doc, template)
+    })?;
+
+    // Company header
+    let top = canvas.y;
+    let mut header_top = top;
+    if let Some(logo) = &template.logo {
+        header_top -= draw_logo(&canvas, logo, top)? + 3.0;
+    }
+    canvas.text(&data.company.name, 18.0, MARGIN, header_top - 5.0, true);
+    for (i, line) in template.header_lines.iter().enumerate() {
+        canvas.text(
+            line,
+            9.0,
+            MARGIN,
+            header_top - 12.0 - i as f32 * LINE_HEIGHT,
+            false,
+        );
+    }
+
+    // Invoice details
+    canvas.text_right(&labels.title, 20.0, COL_AMOUNT, top - 5.0, true);
+    let details = [
+        format!("{}: {}", labels.number, data.invoice.number),
+        format!("{}: {}", labels.date, data.invoice.date),
+        format!("{}: {}", labels.status, data.invoice.status),
+    ];
+    for (i, line) in details.iter().enumerate() {
+        canvas.text_right(
+            line,
+            9.0,
+            COL_AMOUNT,
+            top - 12.0 - i as f32 * LINE_HEIGHT,
+            false,
+        );
+    }
+
+    // Bill to, below whichever header block is taller
+    let header_bottom = header_top - 12.0 - template.header_lines.len() as f32 * LINE_HEIGHT;
+    canvas.y = header_bottom.min(top - 30.0) - 8.0;
+    canvas.text(&labels.bill_to, 11.0, MARGIN, canvas.y, true);
+    for line in &template.bill_to_lines {
+        canvas.y -= LINE_HEIGHT;
+        canvas.text(line, 9.0, MARGIN, canvas.y, false);
+    }
+
+    // Line items
+    canvas.y -= 12.0;
+    draw_table_header(&canvas, template);
+    canvas.y -= ROW_HEIGHT;
+    for goods in &data.invoice.goods {
+        if canvas.y < MARGIN + ROW_HEIGHT {
+            canvas.new_page();
+            draw_table_header(&canvas, template);
+            canvas.y -= ROW_HEIGHT;
+        }
+        canvas.text(&goods.name, 10.0, COL_ITEM, canvas.y, false);
+        canvas.text_right(&goods.quantity.to_string(), 10.0, COL_QTY, canvas.y, false);
+        canvas.text_right(&goods.price, 10.0, COL_PRICE, canvas.y, false);
+        canvas.text_right(&goods.amount, 10.0, COL_AMOUNT, canvas.y, false);
+        canvas.y -= ROW_HEIGHT;
+    }
+
+    // Totals
+    let footer_height = template.footer_lines.len() as f32 * LINE_HEIGHT;
+    if canvas.y < MARGIN + ROW_HEIGHT * 4.0 + footer_height {
+        canvas.new_page();
+    }
+    canvas.rule(canvas.y + ROW_HEIGHT - 2.0);
+    let totals = [
+        (&labels.total, &data.totals.total, false),
+        (&labels.paid, &data.totals.paid, false),
+        (&labels.balance_due, &data.totals.balance_due, true),
+    ];
+    for (label, value, bold) in totals {
+        canvas.text(label, 10.0, COL_PRICE - 35.0, canvas.y, bold);
+        let value = format!("{} {}", value, data.totals.currency);
+        canvas.text_right(value.trim_end(), 10.0, COL_AMOUNT, canvas.y, bold);
+        canvas.y -= ROW_HEIGHT;
+    }
+
+    // Footer
+    canvas.y -= ROW_HEIGHT;
+    for line in &template.footer_lines {
+        canvas.text(line, 9.0, MARGIN, canvas.y, false);
+        canvas.y -= LINE_HEIGHT;
+    }
+
+    canvas.doc.save_to_bytes().map_err(pdf_error)
+}
+
+fn draw_table_header(canvas: &Canvas, template: &RenderedTemplate) {
+    let labels = &template.labels;
+    let y = canvas.y;
+    canvas.text(&labels.item, 10.0, COL_ITEM, y, true);
+    canvas.text_right(&labels.quantity, 10.0, COL_QTY, y, true);
+    canvas.text_right(&labels.unit_price, 10.0, COL_PRICE, y, true);
+    canvas.text_right(&labels.amount, 10.0, COL_AMOUNT, y, true);
+    canvas.rule(y - 2.0);
+}
@@ -0,0 +1,21 @@
+pub mod eta;
+pub mod invoice_pdf;
+pub mod pdf;
+pub mod statement;
+pub mod templates;
+pub mod ubl;
+pub mod xml;
+
+use mongodb::bson::DateTime;
+
+/// Formats a stored date as `YYYY-MM-DD` for printed documents.
+pub fn format_date(date: &DateTime) -> String {
+    date.try_to_rfc3339_string()
+        .map(|s| s.chars().take(10).collect())
+        .unwrap_or_default()
+}
+
+/// Formats an amount with two decimal places.
+pub fn format_money(amount: f64) -> String {
+    format!("{:.2}", amount)
+}
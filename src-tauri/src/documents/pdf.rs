@@ -0,0 +1,107 @@
+//! Drawing helpers shared by the PDF documents.
+
+use printpdf::{
+    BuiltinFont, IndirectFontRef, Line, Mm, PdfDocument, PdfDocumentReference, PdfLayerReference,
+    Point,
+};
+
+use crate::schema::error::{AppResult, ErrorResponse};
+
+// A4 portrait, in millimetres
+pub const PAGE_WIDTH: f32 = 210.0;
+pub const PAGE_HEIGHT: f32 = 297.0;
+pub const MARGIN: f32 = 15.0;
+pub const ROW_HEIGHT: f32 = 7.0;
+pub const LINE_HEIGHT: f32 = 4.5;
+
+pub struct Fonts {
+    pub regular: IndirectFontRef,
+    pub bold: IndirectFontRef,
+}
+
+/// Keeps track of the current page and vertical cursor while drawing.
+pub struct Canvas {
+    pub doc: PdfDocumentReference,
+    pub layer: PdfLayerReference,
+    pub fonts: Fonts,
+    pub y: f32,
+    pages: usize,
+}
+
+impl Canvas {
+    /// Starts a document with a single empty page. `fonts` loads the fonts
+    /// into the new document.
+    pub fn new(
+        title: String,
+        fonts: impl FnOnce(&PdfDocumentReference) -> AppResult<Fonts>,
+    ) -> AppResult<Self> {
+        let (doc, page, layer) = PdfDocument::new(title, Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Page 1");
+        let fonts = fonts(&doc)?;
+        let layer = doc.get_page(page).get_layer(layer);
+        Ok(Self {
+            doc,
+            layer,
+            fonts,
+            y: PAGE_HEIGHT - MARGIN,
+            pages: 1,
+        })
+    }
+
+    pub fn text(&self, text: &str, size: f32, x: f32, y: f32, bold: bool) {
+        let font = if bold {
+            &self.fonts.bold
+        } else {
+            &self.fonts.regular
+        };
+        self.layer.use_text(text, size, Mm(x), Mm(y), font);
+    }
+
+    /// Draws text so that it ends at `right`.
+    pub fn text_right(&self, text: &str, size: f32, right: f32, y: f32, bold: bool) {
+        self.text(text, size, right - text_width(text, size), y, bold);
+    }
+
+    pub fn rule(&self, y: f32) {
+        self.layer.add_line(Line {
+            points: vec![
+                (Point::new(Mm(MARGIN), Mm(y)), false),
+                (Point::new(Mm(PAGE_WIDTH - MARGIN), Mm(y)), false),
+            ],
+            is_closed: false,
+        });
+    }
+
+    pub fn new_page(&mut self) {
+        self.pages += 1;
+        let (page, layer) = self.doc.add_page(
+            Mm(PAGE_WIDTH),
+            Mm(PAGE_HEIGHT),
+            format!("Page {}", self.pages),
+        );
+        self.layer = self.doc.get_page(page).get_layer(layer);
+        self.y = PAGE_HEIGHT - MARGIN;
+    }
+}
+
+/// Approximate width of Helvetica text in millimetres, used to right-align
+/// figures without embedding font metrics.
+pub fn text_width(text: &str, size: f32) -> f32 {
+    text.chars().count() as f32 * size * 0.55 * 0.3528
+}
+
+pub fn pdf_error(message: &str, e: impl ToString) -> ErrorResponse {
+    logger::log_error(message, 500, Some(&e.to_string()));
+    ErrorResponse::new(500, message, Some(e.to_string()))
+}
+
+/// Loads the standard Helvetica fonts, which every PDF reader provides.
+pub fn builtin_fonts(doc: &PdfDocumentReference, error_message: &str) -> AppResult<Fonts> {
+    Ok(Fonts {
+        regular: doc
+            .add_builtin_font(BuiltinFont::Helvetica)
+            .map_err(|e| pdf_error(error_message, e))?,
+        bold: doc
+            .add_builtin_font(BuiltinFont::HelveticaBold)
+            .map_err(|e| pdf_error(error_message, e))?,
+    })
+}
@@ -0,0 +1,261 @@
+//! Printable and spreadsheet exports of a client statement of account.
+
+use serde::Serialize;
+
+use crate::schema::{
+    client_schema::Client,
+    company_schema::Company,
+    error::{AppResult, ErrorResponse},
+    statement_schema::ClientStatement,
+};
+
+use super::{
+    format_date, format_money,
+    pdf::{builtin_fonts, pdf_error, Canvas, LINE_HEIGHT, MARGIN, PAGE_WIDTH, ROW_HEIGHT},
+    templates::CompanyData,
+};
+
+const PDF_ERROR: &str = "Failed to render statement PDF";
+
+// Column anchors for the movement table
+const COL_DATE: f32 = MARGIN;
+const COL_DESCRIPTION: f32 = 40.0;
+const COL_DEBIT: f32 = 130.0;
+const COL_CREDIT: f32 = 162.0;
+const COL_BALANCE: f32 = PAGE_WIDTH - MARGIN;
+
+fn period(statement: &ClientStatement) -> String {
+    match (&statement.from, &statement.to) {
+        (Some(from), Some(to)) => format!("{} to {}", format_date(from), format_date(to)),
+        (Some(from), None) => format!("From {}", format_date(from)),
+        (None, Some(to)) => format!("Up to {}", format_date(to)),
+        (None, None) => "All activity".to_string(),
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct StatementClientData {
+    pub username: String,
+    pub company_name: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct StatementTotalsData {
+    pub period: String,
+    pub opening_balance: String,
+    pub total_debits: String,
+    pub total_credits: String,
+    pub closing_balance: String,
+    pub currency: String,
+}
+
+/// The data exposed to the statement mail subject and body templates.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct StatementMailData {
+    pub client: StatementClientData,
+    pub company: CompanyData,
+    pub statement: StatementTotalsData,
+}
+
+impl StatementMailData {
+    pub fn new(statement: &ClientStatement, client: &Client, company: Option<&Company>) -> Self {
+        let company = CompanyData::new(company);
+        Self {
+            client: StatementClientData {
+                username: client.username.clone(),
+                company_name: client.company_name.clone(),
+            },
+            statement: StatementTotalsData {
+                period: period(statement),
+                opening_balance: format_money(statement.opening_balance),
+                total_debits: format_money(statement.total_debits),
+                total_credits: format_money(statement.total_credits),
+                closing_balance: format_money(statement.closing_balance),
+                currency: company.currency.clone(),
+            },
+            company,
+        }
+    }
+}
+
+fn draw_table_header(canvas: &Canvas) {
+    let y = canvas.y;
+    canvas.text("Date", 10.0, COL_DATE, y, true);
+    canvas.text("Description", 10.0, COL_DESCRIPTION, y, true);
+    canvas.text_right("Debit", 10.0, COL_DEBIT, y, true);
+    canvas.text_right("Credit", 10.0, COL_CREDIT, y, true);
+    canvas.text_right("Balance", 10.0, COL_BALANCE, y, true);
+    canvas.rule(y - 2.0);
+}
+
+// Blank instead of 0.00, so each row shows only the side that moved
+fn amount_cell(amount: f64) -> String {
+    if amount == 0.0 {
+        String::new()
+    } else {
+        format_money(amount)
+    }
+}
+
+/// Renders a statement as an A4 PDF using the standard PDF fonts.
+pub fn build_statement_pdf(
+    statement: &ClientStatement,
+    company: Option<&Company>,
+) -> AppResult<Vec<u8>> {
+    let mut canvas = Canvas::new(
+        format!("Statement of Account {}", statement.client_name),
+        |doc| builtin_fonts(doc, PDF_ERROR),
+    )?;
+    let currency = company.map(|c| c.currency.as_str()).unwrap_or_default();
+
+    // Company header
+    let top = canvas.y;
+    if let Some(company) = company {
+        canvas.text(&company.name, 18.0, MARGIN, top - 5.0, true);
+        let lines = [
+            company.address.clone(),
+            company.city.clone(),
+            company.phone.clone(),
+        ];
+        for (i, line) in lines.iter().filter(|l| !l.is_empty()).enumerate() {
+            canvas.text(
+                line,
+                9.0,
+                MARGIN,
+                top - 12.0 - i as f32 * LINE_HEIGHT,
+                false,
+            );
+        }
+    }
+    canvas.text_right("Statement of Account", 20.0, COL_BALANCE, top - 5.0, true);
+    canvas.text_right(&period(statement), 9.0, COL_BALANCE, top - 12.0, false);
+
+    canvas.y = top - 36.0;
+    canvas.text(&statement.client_name, 11.0, MARGIN, canvas.y, true);
+
+    // Movements
+    canvas.y -= 12.0;
+    draw_table_header(&canvas);
+    canvas.y -= ROW_HEIGHT;
+    canvas.text("Opening balance", 10.0, COL_DESCRIPTION, canvas.y, false);
+    canvas.text_right(
+        &format_money(statement.opening_balance),
+        10.0,
+        COL_BALANCE,
+        canvas.y,
+        false,
+    );
+    canvas.y -= ROW_HEIGHT;
+    for line in &statement.lines {
+        if canvas.y < MARGIN + ROW_HEIGHT {
+            canvas.new_page();
+            draw_table_header(&canvas);
+            canvas.y -= ROW_HEIGHT;
+        }
+        canvas.text(&format_date(&line.date), 10.0, COL_DATE, canvas.y, false);
+        canvas.text(&line.description, 9.0, COL_DESCRIPTION, canvas.y, false);
+        canvas.text_right(&amount_cell(line.debit), 10.0, COL_DEBIT, canvas.y, false);
+        canvas.text_right(&amount_cell(line.credit), 10.0, COL_CREDIT, canvas.y, false);
+        canvas.text_right(
+            &format_money(line.balance),
+            10.0,
+            COL_BALANCE,
+            canvas.y,
+            false,
+        );
+        canvas.y -= ROW_HEIGHT;
+    }
+
+    // Totals
+    if canvas.y < MARGIN + ROW_HEIGHT * 2.0 {
+        canvas.new_page();
+    }
+    canvas.rule(canvas.y + ROW_HEIGHT - 2.0);
+    canvas.text("Closing balance", 10.0, COL_DESCRIPTION, canvas.y, true);
+    canvas.text_right(
+        &format_money(statement.total_debits),
+        10.0,
+        COL_DEBIT,
+        canvas.y,
+        true,
+    );
+    canvas.text_right(
+        &format_money(statement.total_credits),
+        10.0,
+        COL_CREDIT,
+        canvas.y,
+        true,
+    );
+    let closing = format!("{} {}", format_money(statement.closing_balance), currency);
+    canvas.text_right(closing.trim_end(), 10.0, COL_BALANCE, canvas.y, true);
+
+    canvas
+        .doc
+        .save_to_bytes()
+        .map_err(|e| pdf_error(PDF_ERROR, e))
+}
+
+fn csv_error(e: impl ToString) -> ErrorResponse {
+    logger::log_error("Failed to write statement CSV", 500, Some(&e.to_string()));
+    ErrorResponse::new(500, "Failed to write statement CSV", Some(e.to_string()))
+}
+
+/// Writes a statement as CSV, with the opening and closing balances as the
+/// first and last rows.
+pub fn build_statement_csv(statement: &ClientStatement) -> AppResult<Vec<u8>> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer
+        .write_record([
+            "date",
+            "type",
+            "invoice",
+            "description",
+            "debit",
+            "credit",
+            "balance",
+        ])
+        .map_err(csv_error)?;
+
+    let opening_date = statement.from.as_ref().map(format_date).unwrap_or_default();
+    writer
+        .write_record([
+            opening_date.as_str(),
+            "",
+            "",
+            "Opening balance",
+            "",
+            "",
+            &format_money(statement.opening_balance),
+        ])
+        .map_err(csv_error)?;
+    for line in &statement.lines {
+        writer
+            .write_record([
+                format_date(&line.date),
+                line.kind.to_string(),
+                line.invoice_id.to_hex(),
+                line.description.clone(),
+                format_money(line.debit),
+                format_money(line.credit),
+                format_money(line.balance),
+            ])
+            .map_err(csv_error)?;
+    }
+    let closing_date = statement.to.as_ref().map(format_date).unwrap_or_default();
+    writer
+        .write_record([
+            closing_date.as_str(),
+            "",
+            "",
+            "Closing balance",
+            &format_money(statement.total_debits),
+            &format_money(statement.total_credits),
+            &format_money(statement.closing_balance),
+        ])
+        .map_err(csv_error)?;
+
+    writer.into_inner().map_err(csv_error)
+}
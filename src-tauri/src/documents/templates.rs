@@ -0,0 +1,572 @@
+//! Invoice document templates.
+//!
+//! A template is a JSON file in `<app data>/templates/` describing the wording,
+//! language, logo, font and free-text lines of a rendered invoice. Text lines
+//! are Handlebars strings evaluated against [`TemplateData`], which exposes:
+//!
+//! * `invoice` - `number`, `date`, `status`, `totalPrice`, `totalPaid` and
+//!   `goods` (each with `name`, `quantity`, `price`, `amount`)
+//! * `client` - `username`, `companyName`, `email`, `phone`, `city`, `address`
+//!   and `contact`, the name of the billing contact (empty when there is none)
+//! * `company` - `name`, `address`, `city`, `phone`, `email`, `taxNumber`,
+//!   `currency` (empty strings when company settings are not configured)
+//! * `totals` - `total`, `paid`, `balanceDue` formatted to two decimals, and
+//!   `currency`
+//!
+//! Lines that render to an empty string are skipped, so optional fields can be
+//! wrapped in `{{#if ...}}` blocks.
+
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use handlebars::Handlebars;
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+
+use crate::schema::{
+    client_schema::{Client, ClinetStatus},
+    company_schema::Company,
+    error::{AppResult, ErrorResponse},
+    invoice_schema::{Goods, Invoice, Status},
+};
+
+use super::{format_date, format_money};
+
+/// Id of the template that ships with the app and is used when no other
+/// template has been selected.
+pub const BUILTIN_TEMPLATE_ID: &str = "classic";
+
+const TEMPLATES_DIR: &str = "templates";
+const DEFAULT_TEMPLATE_FILE: &str = "default_template";
+
+/// Fixed wording printed on the invoice, translated per template.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct InvoiceLabels {
+    pub title: String,
+    pub number: String,
+    pub date: String,
+    pub status: String,
+    pub bill_to: String,
+    pub item: String,
+    pub quantity: String,
+    pub unit_price: String,
+    pub amount: String,
+    pub total: String,
+    pub paid: String,
+    pub balance_due: String,
+}
+
+impl Default for InvoiceLabels {
+    fn default() -> Self {
+        Self {
+            title: "INVOICE".to_string(),
+            number: "No".to_string(),
+            date: "Date".to_string(),
+            status: "Status".to_string(),
+            bill_to: "Bill To".to_string(),
+            item: "Item".to_string(),
+            quantity: "Qty".to_string(),
+            unit_price: "Unit Price".to_string(),
+            amount: "Amount".to_string(),
+            total: "Total".to_string(),
+            paid: "Paid".to_string(),
+            balance_due: "Balance Due".to_string(),
+        }
+    }
+}
+
+/// An invoice layout as stored on disk.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct InvoiceTemplate {
+    pub name: String,
+    #[serde(default)]
+    pub language: String,
+    #[serde(default)]
+    pub labels: InvoiceLabels,
+    /// PNG or JPEG file, relative to the templates directory.
+    pub logo: Option<String>,
+    /// TrueType font files, relative to the templates directory. The standard
+    /// Helvetica fonts are used when omitted.
+    pub font: Option<String>,
+    pub bold_font: Option<String>,
+    #[serde(default = "default_header_lines")]
+    pub header_lines: Vec<String>,
+    #[serde(default = "default_bill_to_lines")]
+    pub bill_to_lines: Vec<String>,
+    #[serde(default)]
+    pub footer_lines: Vec<String>,
+}
+
+fn default_header_lines() -> Vec<String> {
+    vec![
+        "{{company.address}}".to_string(),
+        "{{company.city}}".to_string(),
+        "{{company.phone}}".to_string(),
+        "{{company.email}}".to_string(),
+        "{{#if company.taxNumber}}Tax No: {{company.taxNumber}}{{/if}}".to_string(),
+    ]
+}
+
+fn default_bill_to_lines() -> Vec<String> {
+    vec![
+        "{{client.username}}".to_string(),
+        "{{client.companyName}}".to_string(),
+        "{{#if client.contact}}Attn: {{client.contact}}{{/if}}".to_string(),
+        "{{client.address}}".to_string(),
+        "{{client.city}}".to_string(),
+        "{{client.phone}}".to_string(),
+        "{{client.email}}".to_string(),
+    ]
+}
+
+impl InvoiceTemplate {
+    pub fn builtin() -> Self {
+        Self {
+            name: "Classic".to_string(),
+            language: "en".to_string(),
+            labels: InvoiceLabels::default(),
+            logo: None,
+            font: None,
+            bold_font: None,
+            header_lines: default_header_lines(),
+            bill_to_lines: default_bill_to_lines(),
+            footer_lines: Vec::new(),
+        }
+    }
+}
+
+/// Summary of an available template, returned by `list_invoice_templates`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateInfo {
+    pub id: String,
+    pub name: String,
+    pub language: String,
+    pub is_default: bool,
+}
+
+/// A template with its text lines rendered and its asset files loaded, ready
+/// to be drawn.
+pub struct RenderedTemplate {
+    pub labels: InvoiceLabels,
+    pub header_lines: Vec<String>,
+    pub bill_to_lines: Vec<String>,
+    pub footer_lines: Vec<String>,
+    pub logo: Option<Vec<u8>>,
+    pub font: Option<Vec<u8>>,
+    pub bold_font: Option<Vec<u8>>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct GoodsData {
+    pub name: String,
+    pub quantity: i64,
+    pub price: String,
+    pub amount: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct InvoiceData {
+    pub number: String,
+    pub date: String,
+    pub status: String,
+    pub total_price: String,
+    pub total_paid: String,
+    pub goods: Vec<GoodsData>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ClientData {
+    pub username: String,
+    pub company_name: String,
+    pub email: String,
+    pub phone: String,
+    pub city: String,
+    pub address: String,
+    pub contact: String, // Billing contact, empty when the client has none
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct CompanyData {
+    pub name: String,
+    pub address: String,
+    pub city: String,
+    pub phone: String,
+    pub email: String,
+    pub tax_number: String,
+    pub currency: String,
+}
+
+impl CompanyData {
+    /// Blank fields when the company details have not been saved yet.
+    pub fn new(company: Option<&Company>) -> Self {
+        company
+            .map(|c| CompanyData {
+                name: c.name.clone(),
+                address: c.address.clone(),
+                city: c.city.clone(),
+                phone: c.phone.clone(),
+                email: c.email.clone().unwrap_or_default(),
+                tax_number: c.tax_number.clone().unwrap_or_default(),
+                currency: c.currency.clone(),
+            })
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TotalsData {
+    pub total: String,
+    pub paid: String,
+    pub balance_due: String,
+    pub currency: String,
+}
+
+/// The data model exposed to templates. See the module docs for the fields.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateData {
+    pub invoice: InvoiceData,
+    pub client: ClientData,
+    pub company: CompanyData,
+    pub totals: TotalsData,
+}
+
+impl TemplateData {
+    pub fn new(invoice: &Invoice, client: &Client, company: Option<&Company>) -> Self {
+        let company = CompanyData::new(company);
+        let billed_to = invoice.billed_to(client);
+
+        Self {
+            invoice: InvoiceData {
+                number: invoice.id.map(|id| id.to_hex()).unwrap_or_default(),
+                date: format_date(&invoice.created_at),
+                status: invoice.status.to_string(),
+                total_price: format_money(invoice.total_price),
+                total_paid: format_money(invoice.total_paid),
+                goods: invoice
+                    .goods
+                    .iter()
+                    .map(|g| GoodsData {
+                        name: g.name.clone(),
+                        quantity: g.quantity,
+                        price: format_money(g.price),
+                        amount: format_money(g.price * g.quantity as f64),
+                    })
+                    .collect(),
+            },
+            client: ClientData {
+                username: client.username.clone(),
+                company_name: client.company_name.clone(),
+                email: billed_to.email.unwrap_or_default(),
+                phone: billed_to.phone,
+                city: billed_to.city,
+                address: billed_to.address,
+                contact: billed_to.contact.unwrap_or_default(),
+            },
+            totals: TotalsData {
+                total: format_money(invoice.total_price),
+                paid: format_money(invoice.total_paid),
+                balance_due: format_money(invoice.total_price - invoice.total_paid),
+                currency: company.currency.clone(),
+            },
+            company,
+        }
+    }
+
+    /// Made-up invoice used to preview a template before any real invoice exists.
+    pub fn sample(company: Option<&Company>) -> Self {
+        let invoice = Invoice {
+            id: Some(ObjectId::new()),
+            client_id: ObjectId::new(),
+            goods: vec![
+                Goods {
+                    line_id: Some(ObjectId::new()),
+                    name: "Sample product".to_string(),
+                    price: 120.0,
+                    quantity: 2,
+                    product_id: ObjectId::new(),
+                    list_price: Some(120.0),
+                    price_overridden: false,
+                    override_reason: None,
+                    price_list_id: None,
+                },
+                Goods {
+                    line_id: Some(ObjectId::new()),
+                    name: "Another product".to_string(),
+                    price: 45.5,
+                    quantity: 4,
+                    product_id: ObjectId::new(),
+                    list_price: Some(45.5),
+                    price_overridden: false,
+                    override_reason: None,
+                    price_list_id: None,
+                },
+            ],
+            total_paid: 100.0,
+            status: Status::PartialPaid,
+            total_price: 422.0,
+            billing_address: None,
+            created_at: DateTime::now(),
+            updated_at: DateTime::now(),
+        };
+        let client = Client {
+            id: None,
+            username: "Jane Doe".to_string(),
+            email: Some("jane@example.com".to_string()),
+            phone: "0100 000 0000".to_string(),
+            company_name: "Example Trading Co.".to_string(),
+            city: "Cairo".to_string(),
+            address: "1 Example Street".to_string(),
+            contacts: Vec::new(),
+            addresses: Vec::new(),
+            tax_number: None,
+            tags: Vec::new(),
+            group_id: None,
+            price_list_id: None,
+            status: ClinetStatus::Active,
+            invoices: Vec::new(),
+            total_owed: 0.0,
+            total_paid: 0.0,
+            outstanding_balance: 0.0,
+            credit_limit: None,
+            merged_into: None,
+            deactivated_at: None,
+            deactivation_reason: None,
+            anonymized_at: None,
+            created_at: DateTime::now(),
+            updated_at: DateTime::now(),
+        };
+
+        Self::new(&invoice, &client, company)
+    }
+}
+
+fn template_error(message: &str, e: impl ToString) -> ErrorResponse {
+    logger::log_error(message, 500, Some(&e.to_string()));
+    ErrorResponse::new(500, message, Some(e.to_string()))
+}
+
+/// Returns `<app data>/templates`, creating it if needed.
+pub fn templates_dir(app_data_dir: &Path) -> AppResult<PathBuf> {
+    let dir = app_data_dir.join(TEMPLATES_DIR);
+    fs::create_dir_all(&dir)
+        .map_err(|e| template_error("Failed to create templates directory", e))?;
+    Ok(dir)
+}
+
+/// Rejects ids that could escape the templates directory.
+fn template_path(dir: &Path, id: &str) -> AppResult<PathBuf> {
+    if id.is_empty() || id.contains(['/', '\\', '.']) {
+        logger::log_error(&format!("Invalid template ID: {}", id), 400, None);
+        return Err(ErrorResponse::new(400, "Invalid template ID", None));
+    }
+    Ok(dir.join(format!("{}.json", id)))
+}
+
+/// Returns the id of the template selected as default.
+pub fn default_template_id(dir: &Path) -> String {
+    fs::read_to_string(dir.join(DEFAULT_TEMPLATE_FILE))
+        .map(|id| id.trim().to_string())
+        .ok()
+        .filter(|id| !id.is_empty())
+        .unwrap_or_else(|| BUILTIN_TEMPLATE_ID.to_string())
+}
+
+pub fn set_default_template_id(dir: &Path, id: &str) -> AppResult<()> {
+    // Make sure the template exists and parses before selecting it
+    load_template(dir, id)?;
+    fs::write(dir.join(DEFAULT_TEMPLATE_FILE), id)
+        .map_err(|e| template_error("Failed to save default template", e))
+}
+
+pub fn load_template(dir: &Path, id: &str) -> AppResult<InvoiceTemplate> {
+    if id == BUILTIN_TEMPLATE_ID {
+        return Ok(InvoiceTemplate::builtin());
+    }
+    let path = template_path(dir, id)?;
+    let contents = fs::read_to_string(&path).map_err(|e| {
+        logger::log_error(
+            &format!("Template not found: {}", id),
+            404,
+            Some(&e.to_string()),
+        );
+        ErrorResponse::new(404, "Template not found", Some(e.to_string()))
+    })?;
+    serde_json::from_str(&contents).map_err(|e| {
+        logger::log_error(
+            &format!("Invalid template file: {}", id),
+            400,
+            Some(&e.to_string()),
+        );
+        ErrorResponse::new(400, "Invalid template file", Some(e.to_string()))
+    })
+}
+
+/// Lists the built-in template followed by every parsable template file.
+pub fn list_templates(dir: &Path) -> AppResult<Vec<TemplateInfo>> {
+    let default_id = default_template_id(dir);
+    let builtin = InvoiceTemplate::builtin();
+    let mut templates = vec![TemplateInfo {
+        id: BUILTIN_TEMPLATE_ID.to_string(),
+        name: builtin.name,
+        language: builtin.language,
+        is_default: default_id == BUILTIN_TEMPLATE_ID,
+    }];
+
+    let entries =
+        fs::read_dir(dir).map_err(|e| template_error("Failed to read templates directory", e))?;
+    for path in entries.filter_map(|entry| entry.ok().map(|e| e.path())) {
+        if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+            continue;
+        }
+        let Some(id) = path.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        match load_template(dir, id) {
+            Ok(template) => templates.push(TemplateInfo {
+                id: id.to_string(),
+                name: template.name,
+                language: template.language,
+                is_default: default_id == id,
+            }),
+            Err(e) => logger::log_warn(
+                &format!("Skipping template: {}", id),
+                400,
+                e.details.as_deref(),
+            ),
+        }
+    }
+
+    Ok(templates)
+}
+
+/// Rejects asset names that could escape the templates directory, such as
+/// absolute paths or `..`.
+fn asset_path(dir: &Path, file: &str) -> AppResult<PathBuf> {
+    let path = Path::new(file);
+    if file.is_empty()
+        || !path
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        logger::log_error(&format!("Invalid template asset: {}", file), 400, None);
+        return Err(ErrorResponse::new(
+            400,
+            "Invalid template asset",
+            Some(format!(
+                "{} must be a file in the templates directory",
+                file
+            )),
+        ));
+    }
+    Ok(dir.join(path))
+}
+
+fn read_asset(dir: &Path, file: &Option<String>) -> AppResult<Option<Vec<u8>>> {
+    match file {
+        Some(file) => fs::read(asset_path(dir, file)?)
+            .map(Some)
+            .map_err(|e| template_error(&format!("Failed to read template asset: {}", file), e)),
+        None => Ok(None),
+    }
+}
+
+/// Evaluates the template's text lines against `data` and loads its assets.
+pub fn render_template(
+    dir: &Path,
+    template: &InvoiceTemplate,
+    data: &TemplateData,
+) -> AppResult<RenderedTemplate> {
+    let mut handlebars = Handlebars::new();
+    // Output goes into a PDF, not HTML
+    handlebars.register_escape_fn(handlebars::no_escape);
+
+    let render_lines = |lines: &[String]| -> AppResult<Vec<String>> {
+        let mut rendered = Vec::new();
+        for line in lines {
+            let text = handlebars
+                .render_template(line, data)
+                .map_err(|e| template_error("Failed to render template", e))?;
+            if !text.trim().is_empty() {
+                rendered.push(text);
+            }
+        }
+        Ok(rendered)
+    };
+
+    Ok(RenderedTemplate {
+        labels: template.labels.clone(),
+        header_lines: render_lines(&template.header_lines)?,
+        bill_to_lines: render_lines(&template.bill_to_lines)?,
+        footer_lines: render_lines(&template.footer_lines)?,
+        logo: read_asset(dir, &template.logo)?,
+        font: read_asset(dir, &template.font)?,
+        bold_font: read_asset(dir, &template.bold_font)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn asset_must_stay_in_templates_directory() {
+        let dir = Path::new("/data/templates");
+        assert_eq!(
+            asset_path(dir, "logo.png").unwrap(),
+            Path::new("/data/templates/logo.png")
+        );
+        assert_eq!(
+            asset_path(dir, "./logo.png").unwrap(),
+            Path::new("/data/templates/logo.png")
+        );
+        assert_eq!(
+            asset_path(dir, "fonts/Amiri-Regular.ttf").unwrap(),
+            Path::new("/data/templates/fonts/Amiri-Regular.ttf")
+        );
+
+        for file in [
+            "",
+            "../secrets.txt",
+            "fonts/../../secrets.txt",
+            "/etc/passwd",
+        ] {
+            let error = asset_path(dir, file).unwrap_err();
+            assert_eq!(error.code, 400, "{}", file);
+        }
+    }
+
+    #[test]
+    fn template_id_must_be_a_plain_name() {
+        let dir = Path::new("/data/templates");
+        assert_eq!(
+            template_path(dir, "arabic").unwrap(),
+            Path::new("/data/templates/arabic.json")
+        );
+        for id in ["", "../arabic", "fonts/arabic", "arabic.json"] {
+            assert_eq!(template_path(dir, id).unwrap_err().code, 400, "{}", id);
+        }
+    }
+
+    #[test]
+    fn template_without_assets_reads_no_files() {
+        let rendered = render_template(
+            Path::new("/missing"),
+            &InvoiceTemplate::builtin(),
+            &TemplateData::sample(None),
+        )
+        .unwrap();
+        assert!(rendered.logo.is_none());
+        assert!(rendered.font.is_none());
+    }
+}
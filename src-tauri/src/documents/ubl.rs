@@ -0,0 +1,572 @@
+//! UBL 2.1 electronic invoice export.
+//!
+//! Builds `Invoice` and `CreditNote` documents following the element order of
+//! the OASIS UBL 2.1 schemas. Invoices carry no tax breakdown, so the tax total
+//! is always zero and the tax exclusive and inclusive amounts are equal.
+
+use mongodb::bson::DateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::schema::{
+    client_schema::Client,
+    company_schema::Company,
+    error::{AppResult, ErrorResponse},
+    invoice_schema::Invoice,
+};
+
+use super::{format_date, format_money, xml::XmlWriter};
+
+const INVOICE_NS: &str = "urn:oasis:names:specification:ubl:schema:xsd:Invoice-2";
+const CREDIT_NOTE_NS: &str = "urn:oasis:names:specification:ubl:schema:xsd:CreditNote-2";
+const CAC_NS: &str = "urn:oasis:names:specification:ubl:schema:xsd:CommonAggregateComponents-2";
+const CBC_NS: &str = "urn:oasis:names:specification:ubl:schema:xsd:CommonBasicComponents-2";
+
+// UN/CEFACT code for "one", used as the unit of every line
+const UNIT_CODE: &str = "C62";
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum UblDocumentType {
+    #[default]
+    Invoice,
+    /// Credits the full invoice, referencing it as the billing document.
+    CreditNote,
+}
+
+impl UblDocumentType {
+    fn root(&self) -> &str {
+        match self {
+            UblDocumentType::Invoice => "Invoice",
+            UblDocumentType::CreditNote => "CreditNote",
+        }
+    }
+
+    fn namespace(&self) -> &str {
+        match self {
+            UblDocumentType::Invoice => INVOICE_NS,
+            UblDocumentType::CreditNote => CREDIT_NOTE_NS,
+        }
+    }
+
+    /// UNCL1001 document type code
+    fn type_code(&self) -> &str {
+        match self {
+            UblDocumentType::Invoice => "380",
+            UblDocumentType::CreditNote => "381",
+        }
+    }
+}
+
+struct PartyDetails<'a> {
+    name: &'a str,
+    street: &'a str,
+    city: &'a str,
+    country: Option<&'a str>,
+    tax_number: Option<&'a str>,
+    phone: &'a str,
+    email: Option<&'a str>,
+}
+
+fn write_party(xml: &mut XmlWriter, role: &str, party: &PartyDetails) {
+    xml.open(role, &[]).open("cac:Party", &[]);
+
+    xml.open("cac:PartyName", &[])
+        .leaf("cbc:Name", &[], party.name)
+        .close();
+
+    xml.open("cac:PostalAddress", &[])
+        .optional_leaf("cbc:StreetName", Some(party.street))
+        .optional_leaf("cbc:CityName", Some(party.city));
+    if let Some(country) = party.country {
+        xml.open("cac:Country", &[])
+            .leaf("cbc:IdentificationCode", &[], country)
+            .close();
+    }
+    xml.close();
+
+    if let Some(tax_number) = party.tax_number.filter(|t| !t.trim().is_empty()) {
+        xml.open("cac:PartyTaxScheme", &[])
+            .leaf("cbc:CompanyID", &[], tax_number)
+            .open("cac:TaxScheme", &[])
+            .leaf("cbc:ID", &[], "VAT")
+            .close()
+            .close();
+    }
+
+    xml.open("cac:Contact", &[])
+        .optional_leaf("cbc:Telephone", Some(party.phone))
+        .optional_leaf("cbc:ElectronicMail", party.email)
+        .close();
+
+    xml.close().close();
+}
+
+/// Serializes an invoice, or a credit note for it, as UBL 2.1 XML.
+pub fn build_ubl_document(
+    document_type: UblDocumentType,
+    invoice: &Invoice,
+    client: &Client,
+    company: &Company,
+) -> AppResult<String> {
+    // Every amount carries `currencyID`, which must be an ISO 4217 code
+    let currency = company.currency.trim();
+    if currency.len() != 3 || !currency.chars().all(|c| c.is_ascii_uppercase()) {
+        let details = format!("Company currency is \"{}\"", company.currency);
+        logger::log_error(
+            "UBL invoices need a three letter currency code",
+            400,
+            Some(&details),
+        );
+        return Err(ErrorResponse::new(
+            400,
+            "UBL invoices need a three letter currency code",
+            Some(details),
+        ));
+    }
+    let amount_attrs = [("currencyID", currency)];
+    let invoice_number = invoice.id.map(|id| id.to_hex()).unwrap_or_default();
+    let is_credit_note = document_type == UblDocumentType::CreditNote;
+    let billed_to = invoice.billed_to(client);
+
+    let mut xml = XmlWriter::new();
+    xml.open(
+        document_type.root(),
+        &[
+            ("xmlns", document_type.namespace()),
+            ("xmlns:cac", CAC_NS),
+            ("xmlns:cbc", CBC_NS),
+        ],
+    );
+    xml.leaf("cbc:UBLVersionID", &[], "2.1");
+
+    if is_credit_note {
+        xml.leaf("cbc:ID", &[], &format!("CN-{}", invoice_number))
+            .leaf("cbc:IssueDate", &[], &format_date(&DateTime::now()))
+            .leaf("cbc:CreditNoteTypeCode", &[], document_type.type_code())
+            .leaf("cbc:DocumentCurrencyCode", &[], currency);
+        xml.open("cac:BillingReference", &[])
+            .open("cac:InvoiceDocumentReference", &[])
+            .leaf("cbc:ID", &[], &invoice_number)
+            .leaf("cbc:IssueDate", &[], &format_date(&invoice.created_at))
+            .close()
+            .close();
+    } else {
+        xml.leaf("cbc:ID", &[], &invoice_number)
+            .leaf("cbc:IssueDate", &[], &format_date(&invoice.created_at))
+            .leaf("cbc:InvoiceTypeCode", &[], document_type.type_code())
+            .leaf("cbc:DocumentCurrencyCode", &[], currency);
+    }
+
+    write_party(
+        &mut xml,
+        "cac:AccountingSupplierParty",
+        &PartyDetails {
+            name: &company.name,
+            street: &company.address,
+            city: &company.city,
+            country: company.country.as_deref(),
+            tax_number: company.tax_number.as_deref(),
+            phone: &company.phone,
+            email: company.email.as_deref(),
+        },
+    );
+    write_party(
+        &mut xml,
+        "cac:AccountingCustomerParty",
+        &PartyDetails {
+            name: &billed_to.name,
+            street: &billed_to.address,
+            city: &billed_to.city,
+            country: None,
+            tax_number: billed_to.tax_number.as_deref(),
+            phone: &billed_to.phone,
+            email: billed_to.email.as_deref(),
+        },
+    );
+
+    let total = format_money(invoice.total_price);
+    xml.open("cac:TaxTotal", &[])
+        .leaf("cbc:TaxAmount", &amount_attrs, &format_money(0.0))
+        .close();
+
+    // A credit note refunds the whole invoice, so nothing is prepaid on it
+    let (prepaid, payable) = if is_credit_note {
+        (0.0, invoice.total_price)
+    } else {
+        (invoice.total_paid, invoice.total_price - invoice.total_paid)
+    };
+    xml.open("cac:LegalMonetaryTotal", &[])
+        .leaf("cbc:LineExtensionAmount", &amount_attrs, &total)
+        .leaf("cbc:TaxExclusiveAmount", &amount_attrs, &total)
+        .leaf("cbc:TaxInclusiveAmount", &amount_attrs, &total)
+        .leaf("cbc:PrepaidAmount", &amount_attrs, &format_money(prepaid))
+        .leaf("cbc:PayableAmount", &amount_attrs, &format_money(payable))
+        .close();
+
+    let (line_element, quantity_element) = if is_credit_note {
+        ("cac:CreditNoteLine", "cbc:CreditedQuantity")
+    } else {
+        ("cac:InvoiceLine", "cbc:InvoicedQuantity")
+    };
+    for (i, goods) in invoice.goods.iter().enumerate() {
+        xml.open(line_element, &[])
+            .leaf("cbc:ID", &[], &(i + 1).to_string())
+            .leaf(
+                quantity_element,
+                &[("unitCode", UNIT_CODE)],
+                &goods.quantity.to_string(),
+            )
+            .leaf(
+                "cbc:LineExtensionAmount",
+                &amount_attrs,
+                &format_money(goods.price * goods.quantity as f64),
+            );
+        xml.open("cac:Item", &[])
+            .leaf("cbc:Name", &[], &goods.name)
+            .open("cac:SellersItemIdentification", &[])
+            .leaf("cbc:ID", &[], &goods.product_id.to_hex())
+            .close()
+            .close();
+        xml.open("cac:Price", &[])
+            .leaf("cbc:PriceAmount", &amount_attrs, &format_money(goods.price))
+            .close();
+        xml.close();
+    }
+
+    Ok(xml.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        schema::{invoice_schema::Goods, product_schema::Product},
+        test_fixtures::{client, company, date, invoice, object_id, product},
+    };
+
+    /// Line `n` selling `quantity` of `product` at its catalog price.
+    fn line(n: u8, product: &Product, quantity: i64) -> Goods {
+        Goods {
+            line_id: Some(object_id(n)),
+            name: product.name.clone(),
+            price: product.price,
+            quantity,
+            product_id: product.id.unwrap(),
+            list_price: Some(product.price),
+            price_overridden: false,
+            override_reason: None,
+            price_list_id: None,
+        }
+    }
+
+    // Element sequences of the UBL 2.1 schemas, limited to the elements this
+    // exporter can write, in the order the XSDs require them
+    const INVOICE_SEQUENCE: &[&str] = &[
+        "cbc:UBLVersionID",
+        "cbc:ID",
+        "cbc:IssueDate",
+        "cbc:DueDate",
+        "cbc:InvoiceTypeCode",
+        "cbc:Note",
+        "cbc:DocumentCurrencyCode",
+        "cac:OrderReference",
+        "cac:BillingReference",
+        "cac:AccountingSupplierParty",
+        "cac:AccountingCustomerParty",
+        "cac:PaymentMeans",
+        "cac:TaxTotal",
+        "cac:LegalMonetaryTotal",
+        "cac:InvoiceLine",
+    ];
+    const CREDIT_NOTE_SEQUENCE: &[&str] = &[
+        "cbc:UBLVersionID",
+        "cbc:ID",
+        "cbc:IssueDate",
+        "cbc:CreditNoteTypeCode",
+        "cbc:Note",
+        "cbc:DocumentCurrencyCode",
+        "cac:DiscrepancyResponse",
+        "cac:OrderReference",
+        "cac:BillingReference",
+        "cac:AccountingSupplierParty",
+        "cac:AccountingCustomerParty",
+        "cac:PaymentMeans",
+        "cac:TaxTotal",
+        "cac:LegalMonetaryTotal",
+        "cac:CreditNoteLine",
+    ];
+    const PARTY_SEQUENCE: &[&str] = &[
+        "cac:PartyIdentification",
+        "cac:PartyName",
+        "cac:PostalAddress",
+        "cac:PartyTaxScheme",
+        "cac:PartyLegalEntity",
+        "cac:Contact",
+    ];
+    const ADDRESS_SEQUENCE: &[&str] = &["cbc:StreetName", "cbc:CityName", "cac:Country"];
+    const MONETARY_TOTAL_SEQUENCE: &[&str] = &[
+        "cbc:LineExtensionAmount",
+        "cbc:TaxExclusiveAmount",
+        "cbc:TaxInclusiveAmount",
+        "cbc:AllowanceTotalAmount",
+        "cbc:PrepaidAmount",
+        "cbc:PayableAmount",
+    ];
+    const INVOICE_LINE_SEQUENCE: &[&str] = &[
+        "cbc:ID",
+        "cbc:Note",
+        "cbc:InvoicedQuantity",
+        "cbc:LineExtensionAmount",
+        "cac:Item",
+        "cac:Price",
+    ];
+    const CREDIT_NOTE_LINE_SEQUENCE: &[&str] = &[
+        "cbc:ID",
+        "cbc:Note",
+        "cbc:CreditedQuantity",
+        "cbc:LineExtensionAmount",
+        "cac:Item",
+        "cac:Price",
+    ];
+    const ITEM_SEQUENCE: &[&str] = &[
+        "cbc:Description",
+        "cbc:Name",
+        "cac:BuyersItemIdentification",
+        "cac:SellersItemIdentification",
+    ];
+
+    struct StartTag {
+        path: Vec<String>,     // Names of the enclosing elements
+        parent: Option<usize>, // Index of the enclosing start tag
+        name: String,
+        attrs: String,
+    }
+
+    fn start_tags(xml: &str) -> Vec<StartTag> {
+        let mut tags: Vec<StartTag> = Vec::new();
+        let mut stack: Vec<usize> = Vec::new();
+        for part in xml.split('<').skip(1) {
+            let tag = &part[..part.find('>').unwrap()];
+            if tag.starts_with('?') {
+                continue;
+            }
+            if let Some(name) = tag.strip_prefix('/') {
+                let open = stack.pop().map(|i| tags[i].name.as_str());
+                assert_eq!(open, Some(name), "unbalanced </{}>", name);
+                continue;
+            }
+            let (name, attrs) = tag.split_once(' ').unwrap_or((tag, ""));
+            tags.push(StartTag {
+                path: stack.iter().map(|&i| tags[i].name.clone()).collect(),
+                parent: stack.last().copied(),
+                name: name.to_string(),
+                attrs: attrs.to_string(),
+            });
+            stack.push(tags.len() - 1);
+        }
+        assert!(stack.is_empty(), "unclosed elements");
+        tags
+    }
+
+    /// Checks the children of every element at `path`, e.g. `["Invoice", "cac:InvoiceLine"]`,
+    /// are known, in schema order and include `required`.
+    fn assert_conforms(xml: &str, path: &[&str], sequence: &[&str], required: &[&str]) {
+        let tags = start_tags(xml);
+        let parents: Vec<usize> = (0..tags.len())
+            .filter(|&i| {
+                let mut full = tags[i].path.clone();
+                full.push(tags[i].name.clone());
+                full == path
+            })
+            .collect();
+        assert!(!parents.is_empty(), "no {:?} element", path);
+        for parent in parents {
+            let children: Vec<&str> = tags
+                .iter()
+                .filter(|tag| tag.parent == Some(parent))
+                .map(|tag| tag.name.as_str())
+                .collect();
+            let positions: Vec<usize> = children
+                .iter()
+                .map(|name| {
+                    sequence
+                        .iter()
+                        .position(|s| s == name)
+                        .unwrap_or_else(|| panic!("{} is not allowed in {:?}", name, path))
+                })
+                .collect();
+            assert!(
+                positions.windows(2).all(|w| w[0] <= w[1]),
+                "{:?} children out of order: {:?}",
+                path,
+                children
+            );
+            for name in required {
+                assert!(children.contains(name), "{:?} is missing {}", path, name);
+            }
+        }
+    }
+
+    fn sample_invoice() -> Invoice {
+        let pens = product(10, "Pens & Markers", 12.5);
+        let paper = product(11, "A4 Paper", 150.0);
+        invoice(
+            20,
+            vec![line(31, &pens, 4), line(32, &paper, 2)],
+            100.0,
+            date(2024, 3, 15),
+        )
+    }
+
+    fn build(document_type: UblDocumentType) -> String {
+        build_ubl_document(document_type, &sample_invoice(), &client(), &company()).unwrap()
+    }
+
+    #[test]
+    fn invoice_follows_the_ubl_schema() {
+        let xml = build(UblDocumentType::Invoice);
+
+        assert_conforms(
+            &xml,
+            &["Invoice"],
+            INVOICE_SEQUENCE,
+            &[
+                "cbc:ID",
+                "cbc:IssueDate",
+                "cac:AccountingSupplierParty",
+                "cac:AccountingCustomerParty",
+                "cac:LegalMonetaryTotal",
+                "cac:InvoiceLine",
+            ],
+        );
+        for role in ["cac:AccountingSupplierParty", "cac:AccountingCustomerParty"] {
+            assert_conforms(&xml, &["Invoice", role], &["cac:Party"], &["cac:Party"]);
+            assert_conforms(&xml, &["Invoice", role, "cac:Party"], PARTY_SEQUENCE, &[]);
+            assert_conforms(
+                &xml,
+                &["Invoice", role, "cac:Party", "cac:PostalAddress"],
+                ADDRESS_SEQUENCE,
+                &[],
+            );
+            assert_conforms(
+                &xml,
+                &["Invoice", role, "cac:Party", "cac:PartyTaxScheme"],
+                &["cbc:CompanyID", "cac:TaxScheme"],
+                &["cac:TaxScheme"],
+            );
+        }
+        assert_conforms(
+            &xml,
+            &["Invoice", "cac:LegalMonetaryTotal"],
+            MONETARY_TOTAL_SEQUENCE,
+            &["cbc:PayableAmount"],
+        );
+        assert_conforms(
+            &xml,
+            &["Invoice", "cac:InvoiceLine"],
+            INVOICE_LINE_SEQUENCE,
+            &["cbc:ID", "cbc:LineExtensionAmount", "cac:Item"],
+        );
+        assert_conforms(
+            &xml,
+            &["Invoice", "cac:InvoiceLine", "cac:Item"],
+            ITEM_SEQUENCE,
+            &[],
+        );
+    }
+
+    #[test]
+    fn credit_note_follows_the_ubl_schema() {
+        let xml = build(UblDocumentType::CreditNote);
+
+        assert_conforms(
+            &xml,
+            &["CreditNote"],
+            CREDIT_NOTE_SEQUENCE,
+            &[
+                "cbc:ID",
+                "cbc:IssueDate",
+                "cac:BillingReference",
+                "cac:AccountingSupplierParty",
+                "cac:AccountingCustomerParty",
+                "cac:LegalMonetaryTotal",
+                "cac:CreditNoteLine",
+            ],
+        );
+        assert_conforms(
+            &xml,
+            &["CreditNote", "cac:CreditNoteLine"],
+            CREDIT_NOTE_LINE_SEQUENCE,
+            &["cbc:ID", "cbc:LineExtensionAmount", "cac:Item"],
+        );
+        assert!(xml.contains(
+            "<CreditNote xmlns=\"urn:oasis:names:specification:ubl:schema:xsd:CreditNote-2\""
+        ));
+        assert!(xml.contains(&format!(
+            "<cbc:ID>CN-{}</cbc:ID>",
+            sample_invoice().id.unwrap().to_hex()
+        )));
+    }
+
+    #[test]
+    fn amounts_match_the_sample_invoice() {
+        let xml = build(UblDocumentType::Invoice);
+
+        assert!(xml.contains("<cbc:IssueDate>2024-03-15</cbc:IssueDate>"));
+        assert!(xml.contains("<cbc:InvoiceTypeCode>380</cbc:InvoiceTypeCode>"));
+        assert!(xml.contains("<cbc:DocumentCurrencyCode>EGP</cbc:DocumentCurrencyCode>"));
+        assert!(xml.contains(
+            "<cbc:LineExtensionAmount currencyID=\"EGP\">350.00</cbc:LineExtensionAmount>"
+        ));
+        assert!(xml.contains("<cbc:PrepaidAmount currencyID=\"EGP\">100.00</cbc:PrepaidAmount>"));
+        assert!(xml.contains("<cbc:PayableAmount currencyID=\"EGP\">250.00</cbc:PayableAmount>"));
+        assert!(xml.contains("<cbc:InvoicedQuantity unitCode=\"C62\">4</cbc:InvoicedQuantity>"));
+        // Text is escaped
+        assert!(xml.contains("<cbc:Name>Pens &amp; Markers</cbc:Name>"));
+    }
+
+    #[test]
+    fn credit_note_refunds_the_whole_invoice() {
+        let xml = build(UblDocumentType::CreditNote);
+
+        assert!(xml.contains("<cbc:CreditNoteTypeCode>381</cbc:CreditNoteTypeCode>"));
+        assert!(xml.contains("<cbc:PrepaidAmount currencyID=\"EGP\">0.00</cbc:PrepaidAmount>"));
+        assert!(xml.contains("<cbc:PayableAmount currencyID=\"EGP\">350.00</cbc:PayableAmount>"));
+        assert!(xml.contains("<cbc:CreditedQuantity unitCode=\"C62\">2</cbc:CreditedQuantity>"));
+    }
+
+    #[test]
+    fn every_amount_has_the_currency() {
+        for document_type in [UblDocumentType::Invoice, UblDocumentType::CreditNote] {
+            let xml = build(document_type);
+            let amounts: Vec<_> = start_tags(&xml)
+                .into_iter()
+                .filter(|tag| tag.name.ends_with("Amount"))
+                .collect();
+            assert!(!amounts.is_empty());
+            for tag in amounts {
+                assert_eq!(
+                    tag.attrs, "currencyID=\"EGP\"",
+                    "{} has no currency",
+                    tag.name
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_a_missing_currency() {
+        for currency in ["", "  ", "egp", "EGYPT"] {
+            let company = Company {
+                currency: currency.to_string(),
+                ..company()
+            };
+            let result = build_ubl_document(
+                UblDocumentType::Invoice,
+                &sample_invoice(),
+                &client(),
+                &company,
+            );
+            assert_eq!(result.unwrap_err().code, 400, "currency {:?}", currency);
+        }
+    }
+}
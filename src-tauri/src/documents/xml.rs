@@ -0,0 +1,88 @@
+/// Minimal indented XML writer for the structured document exporters.
+pub struct XmlWriter {
+    out: String,
+    open_elements: Vec<String>,
+}
+
+/// Escapes text and attribute values.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl Default for XmlWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl XmlWriter {
+    pub fn new() -> Self {
+        Self {
+            out: String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n"),
+            open_elements: Vec::new(),
+        }
+    }
+
+    fn start_tag(&mut self, name: &str, attrs: &[(&str, &str)]) {
+        self.out.push_str(&"  ".repeat(self.open_elements.len()));
+        self.out.push('<');
+        self.out.push_str(name);
+        for (key, value) in attrs {
+            self.out
+                .push_str(&format!(" {}=\"{}\"", key, escape(value)));
+        }
+        self.out.push('>');
+    }
+
+    /// Opens an element that will contain child elements.
+    pub fn open(&mut self, name: &str, attrs: &[(&str, &str)]) -> &mut Self {
+        self.start_tag(name, attrs);
+        self.out.push('\n');
+        self.open_elements.push(name.to_string());
+        self
+    }
+
+    /// Closes the most recently opened element.
+    pub fn close(&mut self) -> &mut Self {
+        if let Some(name) = self.open_elements.pop() {
+            self.out.push_str(&"  ".repeat(self.open_elements.len()));
+            self.out.push_str(&format!("</{}>\n", name));
+        }
+        self
+    }
+
+    /// Writes an element containing only text.
+    pub fn leaf(&mut self, name: &str, attrs: &[(&str, &str)], text: &str) -> &mut Self {
+        self.start_tag(name, attrs);
+        self.out.push_str(&escape(text));
+        self.out.push_str(&format!("</{}>\n", name));
+        self
+    }
+
+    /// Writes a text element, skipping it when the text is empty.
+    pub fn optional_leaf(&mut self, name: &str, text: Option<&str>) -> &mut Self {
+        match text.map(str::trim).filter(|t| !t.is_empty()) {
+            Some(text) => self.leaf(name, &[], text),
+            None => self,
+        }
+    }
+
+    /// Closes any elements still open and returns the document.
+    pub fn finish(mut self) -> String {
+        while !self.open_elements.is_empty() {
+            self.close();
+        }
+        self.out
+    }
+}
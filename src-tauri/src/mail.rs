@@ -0,0 +1,310 @@
+use std::env;
+
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    Aes256Gcm, Nonce,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use handlebars::Handlebars;
+use lettre::{
+    message::{header::ContentType, Attachment, Mailbox, MultiPart, SinglePart},
+    transport::smtp::authentication::Credentials,
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::schema::{
+    error::{AppResult, ErrorResponse},
+    mail_schema::{MailSettings, SmtpSecurity},
+};
+
+/// A file attached to an outgoing mail.
+pub struct MailAttachment {
+    pub filename: String,
+    pub content_type: String,
+    pub data: Vec<u8>,
+}
+
+/// A plain text mail ready to be sent.
+pub struct OutgoingMail {
+    pub to: Vec<String>,
+    pub subject: String,
+    pub body: String,
+    pub attachments: Vec<MailAttachment>,
+}
+
+fn mail_error(message: &str, code: u16, e: impl ToString) -> ErrorResponse {
+    logger::log_error(message, code, Some(&e.to_string()));
+    ErrorResponse::new(code, message, Some(e.to_string()))
+}
+
+// Size of the AES-GCM nonce stored in front of the encrypted password
+const NONCE_LEN: usize = 12;
+
+fn password_cipher() -> AppResult<Aes256Gcm> {
+    let secret = env::var("MAIL_SECRET_KEY").map_err(|e| {
+        mail_error(
+            "Please provide a MAIL_SECRET_KEY environment variable to store the SMTP password.",
+            500,
+            e,
+        )
+    })?;
+    Aes256Gcm::new_from_slice(&Sha256::digest(secret.as_bytes()))
+        .map_err(|e| mail_error("Invalid MAIL_SECRET_KEY", 500, e))
+}
+
+/// Encrypts the SMTP password before it is saved in `mail_settings`, with a
+/// key derived from the MAIL_SECRET_KEY environment variable.
+pub fn encrypt_password(password: &str) -> AppResult<String> {
+    let cipher = password_cipher()?;
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let encrypted = cipher
+        .encrypt(&nonce, password.as_bytes())
+        .map_err(|e| mail_error("Failed to encrypt SMTP password", 500, e))?;
+
+    let mut stored = nonce.to_vec();
+    stored.extend(encrypted);
+    Ok(STANDARD.encode(stored))
+}
+
+/// Reverses `encrypt_password`.
+pub fn decrypt_password(stored: &str) -> AppResult<String> {
+    let cipher = password_cipher()?;
+    let decoded = STANDARD
+        .decode(stored)
+        .ok()
+        .filter(|d| d.len() > NONCE_LEN)
+        .ok_or_else(|| {
+            mail_error(
+                "Failed to decrypt SMTP password",
+                500,
+                "The saved password is not encrypted, save it again",
+            )
+        })?;
+    let (nonce, encrypted) = decoded.split_at(NONCE_LEN);
+    let password = cipher
+        .decrypt(Nonce::from_slice(nonce), encrypted)
+        .map_err(|_| {
+            mail_error(
+                "Failed to decrypt SMTP password",
+                500,
+                "MAIL_SECRET_KEY changed since the password was saved, save it again",
+            )
+        })?;
+    String::from_utf8(password).map_err(|e| mail_error("Failed to decrypt SMTP password", 500, e))
+}
+
+/// Evaluates a subject or body template against `data`.
+pub fn render_mail_text(template: &str, data: &impl Serialize) -> AppResult<String> {
+    let mut handlebars = Handlebars::new();
+    // Mail is sent as plain text, not HTML
+    handlebars.register_escape_fn(handlebars::no_escape);
+    handlebars
+        .render_template(template, data)
+        .map_err(|e| mail_error("Failed to render mail template", 400, e))
+}
+
+fn parse_mailbox(address: &str, name: Option<&str>) -> AppResult<Mailbox> {
+    let mut mailbox: Mailbox = address
+        .trim()
+        .parse()
+        .map_err(|e| mail_error(&format!("Invalid email address: {}", address), 400, e))?;
+    if let Some(name) = name.filter(|n| !n.trim().is_empty()) {
+        mailbox.name = Some(name.trim().to_string());
+    }
+    Ok(mailbox)
+}
+
+fn build_message(settings: &MailSettings, mail: OutgoingMail) -> AppResult<Message> {
+    let mut builder = Message::builder()
+        .from(parse_mailbox(
+            &settings.from_address,
+            settings.from_name.as_deref(),
+        )?)
+        .subject(mail.subject);
+    for to in &mail.to {
+        builder = builder.to(parse_mailbox(to, None)?);
+    }
+
+    let mut body = MultiPart::mixed().singlepart(SinglePart::plain(mail.body));
+    for attachment in mail.attachments {
+        let content_type = ContentType::parse(&attachment.content_type)
+            .map_err(|e| mail_error("Invalid attachment content type", 400, e))?;
+        body = body
+            .singlepart(Attachment::new(attachment.filename).body(attachment.data, content_type));
+    }
+
+    builder
+        .multipart(body)
+        .map_err(|e| mail_error("Failed to build mail", 400, e))
+}
+
+fn build_transport(settings: &MailSettings) -> AppResult<AsyncSmtpTransport<Tokio1Executor>> {
+    let builder = match settings.security {
+        SmtpSecurity::None => {
+            AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&settings.host)
+        }
+        SmtpSecurity::StartTls => {
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&settings.host)
+                .map_err(|e| mail_error("Failed to configure SMTP server", 400, e))?
+        }
+        SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&settings.host)
+            .map_err(|e| mail_error("Failed to configure SMTP server", 400, e))?,
+    };
+    let mut builder = builder.port(settings.port);
+
+    if let Some(username) = settings.username.as_ref().filter(|u| !u.is_empty()) {
+        let password = match settings.password.as_deref() {
+            Some(stored) => decrypt_password(stored)?,
+            None => String::new(),
+        };
+        builder = builder.credentials(Credentials::new(username.clone(), password));
+    }
+    Ok(builder.build())
+}
+
+/// Sends `mail` through the configured SMTP server and returns its reply.
+pub async fn send_mail(settings: &MailSettings, mail: OutgoingMail) -> AppResult<String> {
+    let message = build_message(settings, mail)?;
+    let transport = build_transport(settings)?;
+
+    let response = transport
+        .send(message)
+        .await
+        .map_err(|e| mail_error("Failed to send mail", 500, e))?;
+    Ok(format!(
+        "{} {}",
+        response.code(),
+        response.message().collect::<Vec<_>>().join(" ")
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use mongodb::bson::DateTime;
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+        net::TcpListener,
+        sync::oneshot,
+    };
+
+    use super::*;
+
+    fn settings(port: u16) -> MailSettings {
+        MailSettings {
+            id: None,
+            host: "127.0.0.1".to_string(),
+            port,
+            security: SmtpSecurity::None,
+            username: None,
+            password: None,
+            from_address: "billing@delta.example".to_string(),
+            from_name: Some("Delta Supplies".to_string()),
+            invoice_subject: String::new(),
+            invoice_body: String::new(),
+            statement_subject: String::new(),
+            statement_body: String::new(),
+            updated_at: DateTime::now(),
+        }
+    }
+
+    /// Accepts one plain SMTP session and sends back the commands and message it received.
+    async fn smtp_sink() -> (u16, oneshot::Receiver<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (tx, rx) = oneshot::channel();
+
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            let mut received = Vec::new();
+            let mut in_data = false;
+
+            writer.write_all(b"220 sink ESMTP\r\n").await.unwrap();
+            while let Ok(Some(line)) = lines.next_line().await {
+                if in_data {
+                    if line == "." {
+                        in_data = false;
+                        writer
+                            .write_all(b"250 2.0.0 queued as 1\r\n")
+                            .await
+                            .unwrap();
+                    } else {
+                        received.push(line);
+                    }
+                    continue;
+                }
+                let reply: &[u8] = match line.as_str() {
+                    "DATA" => {
+                        in_data = true;
+                        b"354 end data with <CR><LF>.<CR><LF>\r\n"
+                    }
+                    "QUIT" => b"221 bye\r\n",
+                    _ if line.starts_with("EHLO") => b"250 sink\r\n",
+                    _ => b"250 OK\r\n",
+                };
+                writer.write_all(reply).await.unwrap();
+                if line == "QUIT" {
+                    break;
+                }
+                received.push(line);
+            }
+            tx.send(received).ok();
+        });
+        (port, rx)
+    }
+
+    #[tokio::test]
+    async fn sends_plain_mail_with_attachment() {
+        let (port, received) = smtp_sink().await;
+        let mail = OutgoingMail {
+            to: vec!["mona@nile-traders.example".to_string()],
+            subject: "Invoice 0101 from Delta Supplies".to_string(),
+            body: "Please find attached invoice 0101.".to_string(),
+            attachments: vec![MailAttachment {
+                filename: "invoice-0101.pdf".to_string(),
+                content_type: "application/pdf".to_string(),
+                data: b"%PDF-1.3\n%\xe2\xe3\xcf\xd3\n".to_vec(),
+            }],
+        };
+
+        let response = send_mail(&settings(port), mail).await.unwrap();
+        assert_eq!(response, "250 2.0.0 queued as 1");
+
+        let received = received.await.unwrap().join("\n");
+        assert!(received.contains("MAIL FROM:<billing@delta.example>"));
+        assert!(received.contains("RCPT TO:<mona@nile-traders.example>"));
+        assert!(received.contains("Subject: Invoice 0101 from Delta Supplies"));
+        assert!(received.contains("Please find attached invoice 0101."));
+        assert!(received.contains("filename=\"invoice-0101.pdf\""));
+        assert!(received.contains(&STANDARD.encode(b"%PDF-1.3\n%\xe2\xe3\xcf\xd3\n")));
+    }
+
+    #[test]
+    fn rejects_invalid_recipient() {
+        let mail = OutgoingMail {
+            to: vec!["not an address".to_string()],
+            subject: String::new(),
+            body: String::new(),
+            attachments: Vec::new(),
+        };
+        let error = build_message(&settings(25), mail).unwrap_err();
+        assert_eq!(error.code, 400);
+    }
+
+    #[test]
+    fn encrypts_password() {
+        env::set_var("MAIL_SECRET_KEY", "test secret");
+
+        let stored = encrypt_password("s3cret").unwrap();
+        assert!(!stored.contains("s3cret"));
+        // A new nonce every time
+        assert_ne!(stored, encrypt_password("s3cret").unwrap());
+        assert_eq!(decrypt_password(&stored).unwrap(), "s3cret");
+
+        // Passwords saved before they were encrypted have to be saved again
+        assert_eq!(decrypt_password("s3cret").unwrap_err().code, 500);
+    }
+}
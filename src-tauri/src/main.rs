@@ -1,22 +1,64 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod client_csv;
 mod commands;
 mod db;
+mod diff;
+mod documents;
+mod mail;
+mod pricing;
 mod schema;
+mod statement;
+#[cfg(test)]
+mod test_fixtures;
 mod utils;
+mod validation;
 use commands::{
+    audit_command::list_audit_log,
     client_command::{
-        activate_client, add_new_client, deactive_client, find_client_by_id, list_all_clients,
-        update_client,
+        activate_client, add_new_client, anonymize_client, check_new_client, deactive_client,
+        find_client_by_id, list_all_clients, list_clients_by_segment, merge_clients,
+        reconcile_client_balances, update_client,
     },
+    client_csv_command::{export_clients_csv, import_clients_csv, preview_client_import},
+    client_note_command::{
+        add_client_note, client_timeline, delete_client_note, list_client_notes,
+    },
+    company_command::{get_company_settings, update_company_settings},
+    document_command::{export_invoice_eta, export_invoice_ubl, render_invoice_pdf},
     invoice_command::{
         create_invoice, get_invoice_by_id, list_all_invoices, list_all_invoices_with_client_id,
-        update_invoice_by_id,
+        query_invoices, update_invoice_by_id,
+    },
+    invoice_revision_command::{diff_invoice_revisions, list_invoice_revisions},
+    mail_command::{
+        get_mail_settings, list_client_deliveries, list_invoice_deliveries, send_invoice_email,
+        send_statement_email, update_mail_settings,
+    },
+    payable_command::{
+        create_supplier_bill, list_supplier_bills, list_supplier_payments, record_supplier_payment,
+    },
+    price_list_command::{
+        create_client_group, create_price_list, delete_client_group, delete_price_list,
+        list_client_groups, list_price_lists, update_client_group, update_price_list,
     },
     product_command::{
         create_product, delete_product, get_all_products, get_product_by_id, update_product,
     },
+    purchase_order_command::{
+        create_purchase_order, get_purchase_order_by_id, list_purchase_orders,
+        receive_purchase_order, set_purchase_order_status,
+    },
+    report_command::{payable_aging_report, receivable_aging_report},
+    statement_command::{
+        client_statement, export_client_statement_csv, export_client_statement_pdf,
+    },
+    stock_command::{adjust_product_stock, list_low_stock_products, product_stock_history},
+    supplier_command::{create_supplier, get_supplier_by_id, list_suppliers, update_supplier},
+    template_command::{
+        list_invoice_templates, preview_invoice_template, set_default_invoice_template,
+    },
 };
 use db::init_db;
 use dotenvy::dotenv;
@@ -60,7 +102,66 @@ fn main() {
                     list_all_invoices,
                     get_invoice_by_id,
                     update_invoice_by_id,
-                    list_all_invoices_with_client_id
+                    list_all_invoices_with_client_id,
+                    get_company_settings,
+                    update_company_settings,
+                    render_invoice_pdf,
+                    list_invoice_templates,
+                    preview_invoice_template,
+                    set_default_invoice_template,
+                    list_invoice_revisions,
+                    diff_invoice_revisions,
+                    query_invoices,
+                    export_invoice_ubl,
+                    export_invoice_eta,
+                    get_mail_settings,
+                    update_mail_settings,
+                    send_invoice_email,
+                    send_statement_email,
+                    list_invoice_deliveries,
+                    list_client_deliveries,
+                    client_statement,
+                    export_client_statement_pdf,
+                    export_client_statement_csv,
+                    receivable_aging_report,
+                    check_new_client,
+                    merge_clients,
+                    reconcile_client_balances,
+                    preview_client_import,
+                    import_clients_csv,
+                    export_clients_csv,
+                    anonymize_client,
+                    list_audit_log,
+                    list_clients_by_segment,
+                    create_price_list,
+                    update_price_list,
+                    list_price_lists,
+                    delete_price_list,
+                    create_client_group,
+                    update_client_group,
+                    list_client_groups,
+                    delete_client_group,
+                    add_client_note,
+                    list_client_notes,
+                    delete_client_note,
+                    client_timeline,
+                    adjust_product_stock,
+                    product_stock_history,
+                    list_low_stock_products,
+                    create_supplier,
+                    update_supplier,
+                    list_suppliers,
+                    get_supplier_by_id,
+                    create_purchase_order,
+                    list_purchase_orders,
+                    get_purchase_order_by_id,
+                    set_purchase_order_status,
+                    receive_purchase_order,
+                    create_supplier_bill,
+                    list_supplier_bills,
+                    record_supplier_payment,
+                    list_supplier_payments,
+                    payable_aging_report
                 ])
                 .run(tauri::generate_context!())
                 .expect("Error while running Tauri application");
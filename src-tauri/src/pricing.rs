@@ -0,0 +1,260 @@
+use mongodb::bson::oid::ObjectId;
+
+use crate::schema::{
+    error::{AppResult, ErrorResponse},
+    invoice_schema::Goods,
+    price_list_schema::PriceList,
+    product_schema::Product,
+};
+
+/// An explicit unit price requested for an invoice line.
+pub struct PriceOverride {
+    pub price: f64,
+    pub reason: Option<String>,
+}
+
+/// The price lists that apply to a client, most specific first: the client's
+/// own list, then the list of the client's group.
+#[derive(Default)]
+pub struct ClientPricing {
+    pub price_lists: Vec<PriceList>,
+}
+
+impl ClientPricing {
+    /// Price of `product_id` from the first list that has it, with the list's ID.
+    pub fn price_for(&self, product_id: ObjectId) -> Option<(f64, Option<ObjectId>)> {
+        self.price_lists.iter().find_map(|list| {
+            list.items
+                .iter()
+                .find(|item| item.product_id == product_id)
+                .map(|item| (item.price, list.id))
+        })
+    }
+}
+
+/// Builds an invoice line priced from the client's price lists, falling back
+/// to the product catalog.
+///
+/// The catalog price is always recorded in `list_price`. When `price_override`
+/// is given the line is flagged as overridden, unless `overrides_locked` is set
+/// by the company-wide lock, in which case the line is rejected whoever
+/// submits it.
+pub fn price_line(
+    product: &Product,
+    quantity: i64,
+    pricing: &ClientPricing,
+    price_override: Option<PriceOverride>,
+    overrides_locked: bool,
+) -> AppResult<Goods> {
+    if quantity <= 0 {
+        logger::log_error(
+            &format!("Invalid quantity for product: {}", product.name),
+            400,
+            None,
+        );
+        return Err(ErrorResponse::new(
+            400,
+            &format!("Invalid quantity for product: {}", product.name),
+            Some("Quantity must be greater than zero".to_string()),
+        ));
+    }
+
+    let product_id = product.id.unwrap_or_default();
+    let (price, price_list_id) = pricing
+        .price_for(product_id)
+        .unwrap_or((product.price, None));
+    let mut goods = Goods {
+        line_id: Some(ObjectId::new()),
+        name: product.name.clone(),
+        price,
+        quantity,
+        product_id,
+        list_price: Some(product.price),
+        price_overridden: false,
+        override_reason: None,
+        price_list_id,
+    };
+
+    if let Some(price_override) = price_override {
+        if overrides_locked {
+            logger::log_error(
+                &format!("Price override rejected for product: {}", product.name),
+                403,
+                None,
+            );
+            return Err(ErrorResponse::new(
+                403,
+                "Price overrides are locked in the company settings",
+                Some(format!(
+                    "Product {} must be invoiced at {:.2}",
+                    product.name, price
+                )),
+            ));
+        }
+        if price_override.price < 0.0 {
+            logger::log_error(
+                &format!("Invalid override price for product: {}", product.name),
+                400,
+                None,
+            );
+            return Err(ErrorResponse::new(
+                400,
+                &format!("Invalid override price for product: {}", product.name),
+                None,
+            ));
+        }
+        if price_override.price != price {
+            logger::log_warn(
+                &format!(
+                    "Price of {} overridden from {:.2} to {:.2}",
+                    product.name, price, price_override.price
+                ),
+                200,
+                price_override.reason.as_deref(),
+            );
+            goods.price = price_override.price;
+            goods.price_overridden = true;
+            goods.override_reason = price_override.reason;
+            goods.price_list_id = None;
+        }
+    }
+
+    Ok(goods)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        schema::price_list_schema::PriceListItem,
+        test_fixtures::{date, object_id, product},
+    };
+
+    fn price_list(n: u8, prices: &[(&Product, f64)]) -> PriceList {
+        PriceList {
+            id: Some(object_id(n)),
+            name: format!("List {}", n),
+            items: prices
+                .iter()
+                .map(|(product, price)| PriceListItem {
+                    product_id: product.id.unwrap(),
+                    price: *price,
+                })
+                .collect(),
+            created_at: date(2024, 1, 1),
+            updated_at: date(2024, 1, 1),
+        }
+    }
+
+    fn override_to(price: f64) -> Option<PriceOverride> {
+        Some(PriceOverride {
+            price,
+            reason: Some("Loyal customer".to_string()),
+        })
+    }
+
+    #[test]
+    fn uses_catalog_price_without_price_list() {
+        let chair = product(11, "Office chair", 150.0);
+        let goods = price_line(&chair, 2, &ClientPricing::default(), None, false).unwrap();
+
+        assert_eq!(goods.name, "Office chair");
+        assert_eq!(goods.product_id, object_id(11));
+        assert_eq!(goods.quantity, 2);
+        assert_eq!(goods.price, 150.0);
+        assert_eq!(goods.list_price, Some(150.0));
+        assert_eq!(goods.price_list_id, None);
+        assert!(!goods.price_overridden);
+    }
+
+    #[test]
+    fn client_list_comes_before_group_list() {
+        let chair = product(11, "Office chair", 150.0);
+        let desk = product(12, "Desk", 200.0);
+        let lamp = product(13, "Lamp", 40.0);
+        let pricing = ClientPricing {
+            price_lists: vec![
+                price_list(31, &[(&chair, 120.0)]),
+                price_list(32, &[(&chair, 130.0), (&desk, 180.0)]),
+            ],
+        };
+
+        let goods = price_line(&chair, 1, &pricing, None, false).unwrap();
+        assert_eq!(goods.price, 120.0);
+        assert_eq!(goods.price_list_id, Some(object_id(31)));
+        // The catalog price is kept for reference
+        assert_eq!(goods.list_price, Some(150.0));
+
+        let goods = price_line(&desk, 1, &pricing, None, false).unwrap();
+        assert_eq!(goods.price, 180.0);
+        assert_eq!(goods.price_list_id, Some(object_id(32)));
+
+        let goods = price_line(&lamp, 1, &pricing, None, false).unwrap();
+        assert_eq!(goods.price, 40.0);
+        assert_eq!(goods.price_list_id, None);
+    }
+
+    #[test]
+    fn override_beats_price_list() {
+        let chair = product(11, "Office chair", 150.0);
+        let pricing = ClientPricing {
+            price_lists: vec![price_list(31, &[(&chair, 120.0)])],
+        };
+
+        let goods = price_line(&chair, 1, &pricing, override_to(100.0), false).unwrap();
+        assert_eq!(goods.price, 100.0);
+        assert!(goods.price_overridden);
+        assert_eq!(goods.override_reason.as_deref(), Some("Loyal customer"));
+        assert_eq!(goods.price_list_id, None);
+        assert_eq!(goods.list_price, Some(150.0));
+    }
+
+    #[test]
+    fn override_at_the_same_price_changes_nothing() {
+        let chair = product(11, "Office chair", 150.0);
+        let pricing = ClientPricing {
+            price_lists: vec![price_list(31, &[(&chair, 120.0)])],
+        };
+
+        let goods = price_line(&chair, 1, &pricing, override_to(120.0), false).unwrap();
+        assert_eq!(goods.price, 120.0);
+        assert!(!goods.price_overridden);
+        assert_eq!(goods.override_reason, None);
+        assert_eq!(goods.price_list_id, Some(object_id(31)));
+    }
+
+    #[test]
+    fn locked_overrides_are_rejected() {
+        let chair = product(11, "Office chair", 150.0);
+        let pricing = ClientPricing::default();
+
+        let error = price_line(&chair, 1, &pricing, override_to(100.0), true).unwrap_err();
+        assert_eq!(error.code, 403);
+        assert_eq!(
+            error.details.as_deref(),
+            Some("Product Office chair must be invoiced at 150.00")
+        );
+        // Any override is refused, even one matching the price
+        let error = price_line(&chair, 1, &pricing, override_to(150.0), true).unwrap_err();
+        assert_eq!(error.code, 403);
+
+        let goods = price_line(&chair, 1, &pricing, None, true).unwrap();
+        assert_eq!(goods.price, 150.0);
+    }
+
+    #[test]
+    fn rejects_negative_price_and_quantity() {
+        let chair = product(11, "Office chair", 150.0);
+        let pricing = ClientPricing::default();
+
+        let error = price_line(&chair, 1, &pricing, override_to(-1.0), false).unwrap_err();
+        assert_eq!(error.code, 400);
+        let error = price_line(&chair, 0, &pricing, None, false).unwrap_err();
+        assert_eq!(error.code, 400);
+
+        // Giving goods away is allowed
+        let goods = price_line(&chair, 1, &pricing, override_to(0.0), false).unwrap();
+        assert_eq!(goods.price, 0.0);
+        assert!(goods.price_overridden);
+    }
+}
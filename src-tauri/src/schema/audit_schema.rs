@@ -0,0 +1,29 @@
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+
+/// Changes recorded on clients, the only documents audited so far.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum AuditAction {
+    Updated,
+    Deactivated,
+    Activated,
+    Merged, // Recorded on the client the other one was merged into
+    Anonymized,
+}
+
+/// A record of a sensitive change. Entries only name the fields touched, never
+/// their values, so the trail holds no personal data itself.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AuditEntry {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub action: AuditAction,
+    #[serde(rename = "entityId")]
+    pub entity_id: ObjectId, // The document the action was applied to
+    pub fields: Vec<String>, // Fields changed by the action
+    #[serde(rename = "performedBy")]
+    pub performed_by: Option<String>,
+    pub reason: Option<String>,
+    // Date fields
+    pub created_at: DateTime,
+}
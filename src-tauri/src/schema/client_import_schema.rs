@@ -0,0 +1,67 @@
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    client_schema::{ClientMatch, NewClient},
+    error::FieldError,
+};
+
+/// CSV column header to read each client field from. Fields left out are
+/// read from a column named like the field, ignoring case, spaces and
+/// underscores, e.g. `Company Name` for `companyName`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ClientCsvMapping {
+    pub username: Option<String>,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    pub company_name: Option<String>,
+    pub city: Option<String>,
+    pub address: Option<String>,
+    pub tax_number: Option<String>,
+    pub credit_limit: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum ImportRowStatus {
+    Valid,     // Passed validation, imported unless this is a dry run
+    Invalid,   // Failed validation
+    Duplicate, // Looks like an existing client or an earlier row
+}
+
+/// Outcome for one data row of the file. `row` counts data rows from 1, after the header.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ImportRowResult {
+    pub row: usize,
+    pub status: ImportRowStatus,
+    pub client: Option<NewClient>,
+    pub errors: Vec<FieldError>,
+    pub matches: Vec<ClientMatch>,
+    #[serde(rename = "duplicateOfRow")]
+    pub duplicate_of_row: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ClientImportPreview {
+    pub headers: Vec<String>,
+    pub mapping: ClientCsvMapping, // With every field resolved to a column, where one matched
+    #[serde(rename = "totalRows")]
+    pub total_rows: usize,
+    pub rows: Vec<ImportRowResult>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ClientImportReport {
+    #[serde(rename = "dryRun")]
+    pub dry_run: bool,
+    #[serde(rename = "totalRows")]
+    pub total_rows: usize,
+    pub imported: usize,
+    pub invalid: usize,
+    pub duplicates: usize,
+    #[serde(rename = "importedIds")]
+    pub imported_ids: Vec<ObjectId>,
+    pub rows: Vec<ImportRowResult>, // Only the rows that were not imported
+    #[serde(rename = "errorReportPath")]
+    pub error_report_path: Option<String>,
+}
@@ -0,0 +1,43 @@
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+
+/// A note staff keep about a client, e.g. what was agreed on a phone call.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ClientNote {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    #[serde(rename = "clientId")]
+    pub client_id: ObjectId,
+    pub author: String,
+    pub body: String,
+    // Date fields
+    pub created_at: DateTime,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct NewClientNote {
+    pub author: String,
+    pub body: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum TimelineEventKind {
+    Note,
+    Invoice,       // An invoice was issued
+    InvoiceChange, // An invoice total was raised or lowered after it was issued
+    Payment,       // A payment, or a reversal of one when `amount` is negative
+    StatusChange,  // The client was activated, deactivated or anonymized
+    Edit,          // Client details were changed
+}
+
+/// One entry of a client's activity timeline.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TimelineEvent {
+    pub date: DateTime,
+    pub kind: TimelineEventKind,
+    pub summary: String,
+    pub author: Option<String>,
+    #[serde(rename = "referenceId")]
+    pub reference_id: Option<ObjectId>, // The note, invoice or audit entry behind the event
+    pub amount: Option<f64>,
+}
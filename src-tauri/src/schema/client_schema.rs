@@ -4,6 +4,8 @@ use std::fmt::Display;
 use mongodb::bson::{oid::ObjectId, DateTime};
 use serde::{Deserialize, Serialize};
 
+use super::error::FieldError;
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Client {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
@@ -14,6 +16,18 @@ pub struct Client {
     pub company_name: String,
     pub city: String,
     pub address: String,
+    #[serde(default)]
+    pub contacts: Vec<ClientContact>,
+    #[serde(default)]
+    pub addresses: Vec<ClientAddress>, // Extra addresses; `address` and `city` remain the main one
+    #[serde(rename = "taxNumber")]
+    pub tax_number: Option<String>, // Tax registration number of business clients
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(rename = "groupId", default)]
+    pub group_id: Option<ObjectId>,
+    #[serde(rename = "priceListId", default)]
+    pub price_list_id: Option<ObjectId>, // Takes precedence over the group's price list
     pub status: ClinetStatus,
     // Financial fields
     pub invoices: Vec<ObjectId>,
@@ -23,12 +37,22 @@ pub struct Client {
     pub total_paid: f64, // Total amount the client has paid
     #[serde(rename = "outstandingBalance")]
     pub outstanding_balance: f64, // Outstanding balance to be paid
+    #[serde(rename = "creditLimit")]
+    pub credit_limit: Option<f64>, // Highest outstanding balance allowed, `None` for no limit
+    #[serde(rename = "mergedInto", skip_serializing_if = "Option::is_none")]
+    pub merged_into: Option<ObjectId>, // Set on a tombstone left behind by `merge_clients`
+    #[serde(rename = "deactivatedAt", skip_serializing_if = "Option::is_none")]
+    pub deactivated_at: Option<DateTime>, // When the client was last deactivated
+    #[serde(rename = "deactivationReason", skip_serializing_if = "Option::is_none")]
+    pub deactivation_reason: Option<String>,
+    #[serde(rename = "anonymizedAt", skip_serializing_if = "Option::is_none")]
+    pub anonymized_at: Option<DateTime>, // Set once `anonymize_client` has erased the personal data
 
     // Date fields
     pub created_at: DateTime, // When the client record was created
     pub updated_at: DateTime, // When the client record was last updated
 }
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 // TODO: add time
 pub struct NewClient {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
@@ -39,6 +63,149 @@ pub struct NewClient {
     pub company_name: String,
     pub city: String,
     pub address: String,
+    #[serde(default)]
+    pub contacts: Vec<ClientContact>,
+    #[serde(default)]
+    pub addresses: Vec<ClientAddress>,
+    #[serde(rename = "taxNumber")]
+    pub tax_number: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(rename = "groupId", default)]
+    pub group_id: Option<ObjectId>,
+    #[serde(rename = "priceListId", default)]
+    pub price_list_id: Option<ObjectId>,
+    #[serde(rename = "creditLimit")]
+    pub credit_limit: Option<f64>,
+}
+
+/// What a contact or address is used for.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum ContactRole {
+    Billing,
+    Delivery,
+    Accounts,
+    General,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ClientContact {
+    pub name: String,
+    pub role: ContactRole,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    #[serde(rename = "isDefault", default)]
+    pub is_default: bool, // The one to use among contacts with the same role
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ClientAddress {
+    pub role: ContactRole,
+    pub label: Option<String>, // e.g. "Head office" or "Warehouse 2"
+    pub address: String,
+    pub city: String,
+    #[serde(rename = "isDefault", default)]
+    pub is_default: bool, // The one to use among addresses with the same role
+}
+
+impl Client {
+    pub fn is_active(&self) -> bool {
+        matches!(self.status, ClinetStatus::Active)
+    }
+
+    /// The default contact for `role`, or the first one with that role.
+    pub fn contact(&self, role: ContactRole) -> Option<&ClientContact> {
+        let mut contacts = self.contacts.iter().filter(|c| c.role == role);
+        contacts
+            .clone()
+            .find(|c| c.is_default)
+            .or_else(|| contacts.next())
+    }
+
+    /// The default address for `role`, or the first one with that role.
+    pub fn address_for(&self, role: ContactRole) -> Option<&ClientAddress> {
+        let mut addresses = self.addresses.iter().filter(|a| a.role == role);
+        addresses
+            .clone()
+            .find(|a| a.is_default)
+            .or_else(|| addresses.next())
+    }
+
+    /// Credit left before the limit is reached, or `None` when there is no limit.
+    pub fn available_credit(&self) -> Option<f64> {
+        self.credit_limit
+            .map(|limit| (limit - self.outstanding_balance).max(0.0))
+    }
+}
+
+/// Invoice IDs and financial totals of a client, computed from its invoices.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct ClientTotals {
+    #[serde(rename = "_id")]
+    pub client_id: ObjectId,
+    pub invoices: Vec<ObjectId>,
+    #[serde(rename = "totalOwed")]
+    pub total_owed: f64,
+    #[serde(rename = "totalPaid")]
+    pub total_paid: f64,
+}
+
+/// The balance fields kept on a client document.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ClientBalances {
+    #[serde(rename = "totalOwed")]
+    pub total_owed: f64,
+    #[serde(rename = "totalPaid")]
+    pub total_paid: f64,
+    #[serde(rename = "outstandingBalance")]
+    pub outstanding_balance: f64,
+    #[serde(rename = "invoiceCount")]
+    pub invoice_count: usize,
+}
+
+/// A client whose stored balances differ from those computed from its invoices.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BalanceMismatch {
+    #[serde(rename = "clientId")]
+    pub client_id: ObjectId,
+    pub username: String,
+    pub stored: ClientBalances,
+    pub computed: ClientBalances,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ReconciliationReport {
+    pub applied: bool, // False for a dry run
+    #[serde(rename = "clientsChecked")]
+    pub clients_checked: usize,
+    pub mismatches: Vec<BalanceMismatch>,
+}
+
+/// Why an existing client looks like the one being added.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum MatchReason {
+    Phone,
+    Email,
+    CompanyName,
+}
+
+/// An existing client that may be the same as a new one.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ClientMatch {
+    #[serde(rename = "clientId")]
+    pub client_id: ObjectId,
+    pub username: String,
+    pub company_name: String,
+    pub phone: String,
+    pub email: Option<String>,
+    pub reasons: Vec<MatchReason>,
+}
+
+/// Result of checking a new client before saving it.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ClientCheck {
+    pub errors: Vec<FieldError>,
+    pub matches: Vec<ClientMatch>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
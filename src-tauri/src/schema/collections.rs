@@ -2,6 +2,19 @@ pub enum Collection {
     Client,
     Product,
     Invoice,
+    Company,
+    InvoiceRevision,
+    MailSettings,
+    EmailDelivery,
+    AuditLog,
+    PriceList,
+    ClientGroup,
+    ClientNote,
+    StockMovement,
+    Supplier,
+    PurchaseOrder,
+    SupplierBill,
+    SupplierPayment,
 }
 
 impl Collection {
@@ -11,6 +24,19 @@ impl Collection {
             Collection::Client => "clients",
             Collection::Product => "products",
             Collection::Invoice => "invoices",
+            Collection::Company => "company",
+            Collection::InvoiceRevision => "invoice_revisions",
+            Collection::MailSettings => "mail_settings",
+            Collection::EmailDelivery => "email_deliveries",
+            Collection::AuditLog => "audit_log",
+            Collection::PriceList => "price_lists",
+            Collection::ClientGroup => "client_groups",
+            Collection::ClientNote => "client_notes",
+            Collection::StockMovement => "stock_movements",
+            Collection::Supplier => "suppliers",
+            Collection::PurchaseOrder => "purchase_orders",
+            Collection::SupplierBill => "supplier_bills",
+            Collection::SupplierPayment => "supplier_payments",
         }
     }
 }
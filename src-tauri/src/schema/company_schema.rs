@@ -0,0 +1,70 @@
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+
+/// The business issuing invoices. Stored as a single document in the
+/// `company` collection and printed on every rendered document.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Company {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub name: String,
+    pub address: String,
+    pub city: String,
+    pub country: Option<String>, // ISO 3166-1 alpha-2 code, e.g. "EG"
+    pub phone: String,
+    pub email: Option<String>,
+    #[serde(rename = "taxNumber")]
+    pub tax_number: Option<String>,
+    pub currency: String,
+    /// Locks invoice prices for every user: while set, no invoice line may
+    /// override its catalog or price list price. There are no per-user
+    /// permissions, so this is all or nothing.
+    #[serde(rename = "lockPriceOverrides", default)]
+    pub lock_price_overrides: bool,
+    /// Registration details required for Egyptian Tax Authority e-invoices.
+    #[serde(default)]
+    pub eta: Option<EtaSettings>,
+    // Date fields
+    pub updated_at: DateTime,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NewCompany {
+    pub name: String,
+    pub address: String,
+    pub city: String,
+    pub country: Option<String>,
+    pub phone: String,
+    pub email: Option<String>,
+    #[serde(rename = "taxNumber")]
+    pub tax_number: Option<String>,
+    pub currency: String,
+    #[serde(rename = "lockPriceOverrides", default)]
+    pub lock_price_overrides: bool,
+    #[serde(default)]
+    pub eta: Option<EtaSettings>,
+}
+
+/// Issuer details the Egyptian Tax Authority expects on every e-invoice.
+/// The registration number itself is the company `taxNumber`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct EtaSettings {
+    pub branch_id: String, // "0" for the head office
+    pub governate: String,
+    pub region_city: String,
+    pub building_number: String,
+    pub activity_code: String, // Taxpayer activity code, e.g. "4620"
+    #[serde(default = "default_item_type")]
+    pub item_type: String, // "EGS" for internal codes or "GS1"
+    #[serde(default = "default_vat_rate")]
+    pub vat_rate: f64, // Percentage applied to every line as tax type T1
+}
+
+fn default_item_type() -> String {
+    "EGS".to_string()
+}
+
+fn default_vat_rate() -> f64 {
+    14.0
+}
@@ -5,6 +5,15 @@ pub struct ErrorResponse {
     pub code: u16,               // HTTP-like status code
     pub message: String,         // Error message
     pub details: Option<String>, // Optional additional details
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<FieldError>, // Per-field problems for rejected input
+}
+
+/// A problem with one input field, keyed by its serialized name.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
 }
 
 impl ErrorResponse {
@@ -13,6 +22,17 @@ impl ErrorResponse {
             code,
             message: message.to_string(),
             details,
+            fields: Vec::new(),
+        }
+    }
+
+    /// A 400 error listing every invalid field.
+    pub fn validation(message: &str, fields: Vec<FieldError>) -> Self {
+        Self {
+            code: 400,
+            message: message.to_string(),
+            details: None,
+            fields,
         }
     }
 }
@@ -0,0 +1,29 @@
+use mongodb::bson::{oid::ObjectId, Bson, DateTime};
+use serde::{Deserialize, Serialize};
+
+use super::invoice_schema::Invoice;
+
+/// A previous version of an invoice, saved before each update replaces it.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct InvoiceRevision {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    #[serde(rename = "invoiceId")]
+    pub invoice_id: ObjectId,
+    pub revision: i64, // 1 for the original invoice, increasing with every update
+    pub invoice: Invoice, // The invoice as it was before the change
+    #[serde(rename = "changedBy")]
+    pub changed_by: Option<String>,
+    pub reason: Option<String>,
+    // Date fields
+    pub created_at: DateTime, // When the change replacing this version was made
+}
+
+/// A single field that differs between two invoice versions. Goods are keyed
+/// by line ID, e.g. `goods.<lineId>.quantity`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FieldChange {
+    pub field: String,
+    pub before: Option<Bson>,
+    pub after: Option<Bson>,
+}
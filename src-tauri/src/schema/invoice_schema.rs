@@ -3,23 +3,56 @@ use core::fmt;
 use mongodb::bson::{oid::ObjectId, DateTime};
 use serde::{Deserialize, Serialize};
 
+use super::client_schema::{Client, ContactRole};
+
 #[derive(Serialize, Deserialize)]
 pub struct NewInvoice {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub id: Option<ObjectId>,
     #[serde(rename = "clientId")]
     pub client_id: ObjectId,
-    pub goods: Vec<Goods>,
+    pub goods: Vec<NewGoods>,
     #[serde(rename = "totalPaid")]
     pub total_paid: f64,
 }
+/// A requested invoice line. The name and unit price are taken from the
+/// product catalog unless `overridePrice` is given explicitly.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct NewGoods {
+    #[serde(rename = "productId")]
+    pub product_id: ObjectId,
+    pub quantity: i64,
+    #[serde(rename = "overridePrice")]
+    pub override_price: Option<f64>,
+    #[serde(rename = "overrideReason")]
+    pub override_reason: Option<String>,
+}
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Goods {
+    // Identifies the line, so one product can appear on several lines. `None` on
+    // lines stored before IDs were kept, until the invoice is saved again
+    #[serde(rename = "lineId", default, skip_serializing_if = "Option::is_none")]
+    pub line_id: Option<ObjectId>,
     pub name: String,
     pub price: f64,
     pub quantity: i64,
     #[serde(rename = "productId")]
     pub product_id: ObjectId,
+    // Pricing audit fields
+    // Catalog price when the line was priced, before any price list. `None` on
+    // lines priced before it was recorded
+    #[serde(rename = "listPrice", default, skip_serializing_if = "Option::is_none")]
+    pub list_price: Option<f64>,
+    #[serde(rename = "priceOverridden", default)]
+    pub price_overridden: bool, // True when `price` was set by hand
+    #[serde(rename = "overrideReason", skip_serializing_if = "Option::is_none")]
+    pub override_reason: Option<String>,
+    #[serde(
+        rename = "priceListId",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub price_list_id: Option<ObjectId>, // Price list the price was taken from
 }
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Invoice {
@@ -33,10 +66,72 @@ pub struct Invoice {
     pub status: Status,
     #[serde(rename = "totalPrice")]
     pub total_price: f64,
+    #[serde(
+        rename = "billingAddress",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub billing_address: Option<BillingAddress>, // `None` on invoices issued before snapshots were kept
     // Date fields
     pub created_at: DateTime,
     pub updated_at: DateTime,
 }
+
+/// Who an invoice is billed to, copied from the client when the invoice is
+/// issued so later changes to the client don't alter it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BillingAddress {
+    pub name: String,            // Company name, or the username for clients without one
+    pub contact: Option<String>, // Name of the billing contact
+    pub email: Option<String>,
+    pub phone: String,
+    pub address: String,
+    pub city: String,
+    #[serde(rename = "taxNumber")]
+    pub tax_number: Option<String>,
+}
+
+impl BillingAddress {
+    /// The billing details of `client` as they are now. The billing contact and
+    /// address are preferred, falling back to the accounts contact and then to
+    /// the client's own details.
+    pub fn from_client(client: &Client) -> Self {
+        let contact = client
+            .contact(ContactRole::Billing)
+            .or_else(|| client.contact(ContactRole::Accounts));
+        let (address, city) = match client.address_for(ContactRole::Billing) {
+            Some(billing) => (billing.address.clone(), billing.city.clone()),
+            None => (client.address.clone(), client.city.clone()),
+        };
+        Self {
+            name: if client.company_name.trim().is_empty() {
+                client.username.clone()
+            } else {
+                client.company_name.clone()
+            },
+            contact: contact.map(|c| c.name.clone()),
+            email: contact
+                .and_then(|c| c.email.clone())
+                .or_else(|| client.email.clone()),
+            phone: contact
+                .and_then(|c| c.phone.clone())
+                .unwrap_or_else(|| client.phone.clone()),
+            address,
+            city,
+            tax_number: client.tax_number.clone(),
+        }
+    }
+}
+
+impl Invoice {
+    /// The snapshot taken at issue time, or the client's current details for
+    /// invoices issued without one.
+    pub fn billed_to(&self, client: &Client) -> BillingAddress {
+        self.billing_address
+            .clone()
+            .unwrap_or_else(|| BillingAddress::from_client(client))
+    }
+}
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub enum Status {
     Paid,
@@ -52,3 +147,64 @@ impl fmt::Display for Status {
         }
     }
 }
+
+/// Fields invoices can be sorted by in `query_invoices`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default)]
+pub enum InvoiceSortField {
+    #[default]
+    CreatedAt,
+    TotalPrice,
+    TotalPaid,
+    Status,
+}
+
+impl InvoiceSortField {
+    /// Returns the stored field name
+    pub fn as_str(&self) -> &str {
+        match self {
+            InvoiceSortField::CreatedAt => "created_at",
+            InvoiceSortField::TotalPrice => "totalPrice",
+            InvoiceSortField::TotalPaid => "totalPaid",
+            InvoiceSortField::Status => "status",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+/// Filters, sort and pagination for `query_invoices`. Every filter is optional.
+///
+/// Pages are selected either by `page` (1-based, with `pageSize`) or by passing
+/// the `nextCursor` of the previous page as `cursor`, which stays stable while
+/// invoices are being added.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct InvoiceQuery {
+    pub status: Vec<Status>,
+    pub client_id: Option<ObjectId>,
+    pub created_from: Option<DateTime>,
+    pub created_to: Option<DateTime>,
+    pub min_total: Option<f64>,
+    pub max_total: Option<f64>,
+    pub number: Option<String>, // Part of the invoice ID
+    pub sort_by: InvoiceSortField,
+    pub sort_order: SortOrder,
+    pub page: Option<u64>,
+    pub page_size: Option<u64>,
+    pub cursor: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct InvoicePage {
+    pub invoices: Vec<Invoice>,
+    pub total: u64, // Invoices matching the filters across all pages
+    #[serde(rename = "pageSize")]
+    pub page_size: u64,
+    #[serde(rename = "nextCursor")]
+    pub next_cursor: Option<String>,
+}
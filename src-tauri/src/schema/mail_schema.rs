@@ -0,0 +1,128 @@
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+
+/// How the connection to the SMTP server is secured.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum SmtpSecurity {
+    /// Plain connection, for local SMTP sinks such as MailHog.
+    None,
+    #[default]
+    StartTls,
+    Tls,
+}
+
+/// SMTP server and message templates used to email documents. Stored as a
+/// single document in the `mail_settings` collection.
+///
+/// Invoice subject and body templates are Handlebars strings evaluated against
+/// the same data as invoice templates; statement ones against
+/// `documents::statement::StatementMailData`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MailSettings {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub host: String,
+    pub port: u16,
+    #[serde(default)]
+    pub security: SmtpSecurity,
+    pub username: Option<String>,
+    // Encrypted with the MAIL_SECRET_KEY environment variable, see `mail::encrypt_password`
+    pub password: Option<String>,
+    #[serde(rename = "fromAddress")]
+    pub from_address: String,
+    #[serde(rename = "fromName")]
+    pub from_name: Option<String>,
+    #[serde(rename = "invoiceSubject", default = "default_invoice_subject")]
+    pub invoice_subject: String,
+    #[serde(rename = "invoiceBody", default = "default_invoice_body")]
+    pub invoice_body: String,
+    #[serde(rename = "statementSubject", default = "default_statement_subject")]
+    pub statement_subject: String,
+    #[serde(rename = "statementBody", default = "default_statement_body")]
+    pub statement_body: String,
+    // Date fields
+    pub updated_at: DateTime,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NewMailSettings {
+    pub host: String,
+    pub port: u16,
+    #[serde(default)]
+    pub security: SmtpSecurity,
+    pub username: Option<String>,
+    // Left unchanged when not given
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    #[serde(rename = "fromAddress")]
+    pub from_address: String,
+    #[serde(rename = "fromName")]
+    pub from_name: Option<String>,
+    #[serde(rename = "invoiceSubject", default = "default_invoice_subject")]
+    pub invoice_subject: String,
+    #[serde(rename = "invoiceBody", default = "default_invoice_body")]
+    pub invoice_body: String,
+    #[serde(rename = "statementSubject", default = "default_statement_subject")]
+    pub statement_subject: String,
+    #[serde(rename = "statementBody", default = "default_statement_body")]
+    pub statement_body: String,
+}
+
+fn default_invoice_subject() -> String {
+    "Invoice {{invoice.number}} from {{company.name}}".to_string()
+}
+
+fn default_invoice_body() -> String {
+    "Dear {{client.username}},\n\n\
+     Please find attached invoice {{invoice.number}} dated {{invoice.date}} \
+     for {{totals.total}} {{totals.currency}}. The balance due is \
+     {{totals.balanceDue}} {{totals.currency}}.\n\n\
+     Kind regards,\n{{company.name}}"
+        .to_string()
+}
+
+fn default_statement_subject() -> String {
+    "Statement of account from {{company.name}}".to_string()
+}
+
+fn default_statement_body() -> String {
+    "Dear {{client.username}},\n\n\
+     Please find attached your statement of account ({{statement.period}}). \
+     The closing balance is {{statement.closingBalance}} {{statement.currency}}.\n\n\
+     Kind regards,\n{{company.name}}"
+        .to_string()
+}
+
+/// What was emailed.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum DeliveryKind {
+    #[default]
+    Invoice,
+    Statement,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum DeliveryStatus {
+    Sent,
+    Failed,
+}
+
+/// One attempt to email an invoice or a client statement.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct EmailDelivery {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    #[serde(default)]
+    pub kind: DeliveryKind,
+    #[serde(rename = "invoiceId")]
+    pub invoice_id: Option<ObjectId>, // Not set for statements
+    #[serde(rename = "clientId")]
+    pub client_id: ObjectId,
+    pub to: Vec<String>,
+    pub subject: String,
+    pub status: DeliveryStatus,
+    pub response: Option<String>, // Server reply for sent mail
+    pub error: Option<String>,    // Why sending failed
+    // Date fields
+    pub created_at: DateTime,
+}
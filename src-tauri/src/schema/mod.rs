@@ -1,5 +1,18 @@
+pub mod audit_schema;
+pub mod client_import_schema;
+pub mod client_note_schema;
 pub mod client_schema;
 pub mod collections;
+pub mod company_schema;
 pub mod error;
+pub mod invoice_revision_schema;
 pub mod invoice_schema;
+pub mod mail_schema;
+pub mod payable_schema;
+pub mod price_list_schema;
 pub mod product_schema;
+pub mod purchase_order_schema;
+pub mod report_schema;
+pub mod statement_schema;
+pub mod stock_schema;
+pub mod supplier_schema;
@@ -0,0 +1,83 @@
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+
+use super::invoice_schema::Status;
+
+/// An invoice received from a supplier, for a purchase order or standalone
+/// (rent, utilities, services).
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SupplierBill {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    #[serde(rename = "supplierId")]
+    pub supplier_id: ObjectId,
+    #[serde(rename = "purchaseOrderId")]
+    pub purchase_order_id: Option<ObjectId>,
+    pub reference: String, // The supplier's own invoice number
+    pub description: Option<String>,
+    #[serde(rename = "totalAmount")]
+    pub total_amount: f64,
+    #[serde(rename = "totalPaid")]
+    pub total_paid: f64,
+    pub status: Status,
+    #[serde(rename = "billDate")]
+    pub bill_date: DateTime, // Date on the bill, which aging counts from
+    #[serde(rename = "dueDate")]
+    pub due_date: Option<DateTime>,
+    // Date fields
+    pub created_at: DateTime,
+    pub updated_at: DateTime,
+}
+
+impl SupplierBill {
+    pub fn balance(&self) -> f64 {
+        self.total_amount - self.total_paid
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct NewSupplierBill {
+    #[serde(rename = "supplierId")]
+    pub supplier_id: ObjectId,
+    #[serde(rename = "purchaseOrderId")]
+    pub purchase_order_id: Option<ObjectId>,
+    pub reference: String,
+    pub description: Option<String>,
+    // Without an amount a purchase order bill is for the stock received on
+    // the order and not billed yet
+    #[serde(rename = "totalAmount")]
+    pub total_amount: Option<f64>,
+    #[serde(rename = "billDate")]
+    pub bill_date: Option<DateTime>,
+    #[serde(rename = "dueDate")]
+    pub due_date: Option<DateTime>,
+}
+
+/// Money paid to a supplier against one of their bills.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SupplierPayment {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    #[serde(rename = "supplierId")]
+    pub supplier_id: ObjectId,
+    #[serde(rename = "billId")]
+    pub bill_id: ObjectId,
+    pub amount: f64,
+    pub method: Option<String>, // Cash, bank transfer, cheque...
+    pub reference: Option<String>,
+    #[serde(rename = "paidAt")]
+    pub paid_at: DateTime,
+    // Date fields
+    pub created_at: DateTime,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct NewSupplierPayment {
+    #[serde(rename = "billId")]
+    pub bill_id: ObjectId,
+    pub amount: f64,
+    pub method: Option<String>,
+    pub reference: Option<String>,
+    #[serde(rename = "paidAt")]
+    pub paid_at: Option<DateTime>,
+}
@@ -0,0 +1,52 @@
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+
+/// A price that replaces `Product.price` for the clients the list is assigned to.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PriceListItem {
+    #[serde(rename = "productId")]
+    pub product_id: ObjectId,
+    pub price: f64,
+}
+
+/// Prices for some products, assigned to client groups or to single clients
+/// through their `priceListId`. Products not on the list keep their catalog price.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PriceList {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub name: String,
+    pub items: Vec<PriceListItem>,
+    // Date fields
+    pub created_at: DateTime,
+    pub updated_at: DateTime,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct NewPriceList {
+    pub name: String,
+    #[serde(default)]
+    pub items: Vec<PriceListItem>,
+}
+
+/// A segment of clients, e.g. wholesale, retail or VIP.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ClientGroup {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub name: String,
+    pub description: Option<String>,
+    #[serde(rename = "priceListId")]
+    pub price_list_id: Option<ObjectId>, // Prices for every client in the group
+    // Date fields
+    pub created_at: DateTime,
+    pub updated_at: DateTime,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct NewClientGroup {
+    pub name: String,
+    pub description: Option<String>,
+    #[serde(rename = "priceListId")]
+    pub price_list_id: Option<ObjectId>,
+}
@@ -7,12 +7,27 @@ pub struct Product {
     pub name: String,
     pub discription: Option<String>,
     pub price: f64,
+    #[serde(default)]
+    pub cost: Option<f64>, // Unit cost of the last stock received from a supplier
     pub stock: i64,
+    #[serde(rename = "itemCode")]
+    pub item_code: Option<String>, // GS1 or EGS code registered with the tax authority
+    #[serde(rename = "reorderPoint", default)]
+    pub reorder_point: Option<i64>, // Stock at or below this needs reordering
+    #[serde(rename = "reorderQuantity", default)]
+    pub reorder_quantity: Option<i64>, // How much to order when it does
     // Date fields
     pub created_at: DateTime,
     pub updated_at: DateTime,
 }
 
+impl Product {
+    /// Whether the given stock level is at or below the product's reorder point.
+    pub fn needs_reorder(&self, stock: i64) -> bool {
+        self.reorder_point.is_some_and(|point| stock <= point)
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct NewProduct {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
@@ -20,5 +35,13 @@ pub struct NewProduct {
     pub name: String,
     pub discription: Option<String>,
     pub price: f64,
+    #[serde(default)]
+    pub cost: Option<f64>,
     pub stock: i64,
+    #[serde(rename = "itemCode")]
+    pub item_code: Option<String>,
+    #[serde(rename = "reorderPoint", default)]
+    pub reorder_point: Option<i64>,
+    #[serde(rename = "reorderQuantity", default)]
+    pub reorder_quantity: Option<i64>,
 }
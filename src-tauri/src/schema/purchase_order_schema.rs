@@ -0,0 +1,89 @@
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum PurchaseOrderStatus {
+    Draft,
+    Ordered,           // Sent to the supplier
+    PartiallyReceived, // Some lines are still outstanding
+    Received,          // Every line was received in full
+    Cancelled,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PurchaseOrderLine {
+    #[serde(rename = "productId")]
+    pub product_id: ObjectId,
+    pub name: String,
+    pub quantity: i64,
+    #[serde(rename = "receivedQuantity")]
+    pub received_quantity: i64,
+    #[serde(rename = "unitCost")]
+    pub unit_cost: f64, // Agreed cost per unit
+}
+
+impl PurchaseOrderLine {
+    pub fn outstanding(&self) -> i64 {
+        self.quantity - self.received_quantity
+    }
+}
+
+/// Stock received against a purchase order. An order can be received in several parts.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PurchaseReceipt {
+    pub id: ObjectId,
+    pub lines: Vec<ReceiptLine>,
+    #[serde(rename = "receivedBy")]
+    pub received_by: Option<String>,
+    pub created_at: DateTime,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ReceiptLine {
+    #[serde(rename = "productId")]
+    pub product_id: ObjectId,
+    pub quantity: i64,
+    #[serde(rename = "unitCost")]
+    pub unit_cost: Option<f64>, // Actual cost when it differs from the order, else the order's
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PurchaseOrder {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    #[serde(rename = "supplierId")]
+    pub supplier_id: ObjectId,
+    pub lines: Vec<PurchaseOrderLine>,
+    pub status: PurchaseOrderStatus,
+    #[serde(rename = "expectedDate")]
+    pub expected_date: Option<DateTime>, // When the supplier should deliver
+    pub notes: Option<String>,
+    #[serde(rename = "totalCost")]
+    pub total_cost: f64,
+    #[serde(default)]
+    pub receipts: Vec<PurchaseReceipt>,
+    // Date fields
+    pub created_at: DateTime,
+    pub updated_at: DateTime,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct NewPurchaseOrderLine {
+    #[serde(rename = "productId")]
+    pub product_id: ObjectId,
+    pub quantity: i64,
+    #[serde(rename = "unitCost")]
+    pub unit_cost: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct NewPurchaseOrder {
+    #[serde(rename = "supplierId")]
+    pub supplier_id: ObjectId,
+    pub lines: Vec<NewPurchaseOrderLine>,
+    #[serde(rename = "expectedDate")]
+    pub expected_date: Option<DateTime>,
+    pub notes: Option<String>,
+    #[serde(default)]
+    pub ordered: bool, // Place the order straight away instead of saving a draft
+}
@@ -0,0 +1,81 @@
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+
+/// Open balances split by age in days.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct AgingBuckets {
+    pub current: f64, // 0 to 30 days
+    #[serde(rename = "days30")]
+    pub days_30: f64, // 31 to 60 days
+    #[serde(rename = "days60")]
+    pub days_60: f64, // 61 to 90 days
+    #[serde(rename = "days90")]
+    pub days_90: f64, // 91 to 120 days
+    #[serde(rename = "days120Plus")]
+    pub days_120_plus: f64, // More than 120 days
+    pub total: f64,
+}
+
+impl AgingBuckets {
+    /// Adds a balance that has been open for `age` whole days.
+    pub fn add_balance(&mut self, age: i64, balance: f64) {
+        let bucket = match age {
+            ..=30 => &mut self.current,
+            31..=60 => &mut self.days_30,
+            61..=90 => &mut self.days_60,
+            91..=120 => &mut self.days_90,
+            _ => &mut self.days_120_plus,
+        };
+        *bucket += balance;
+        self.total += balance;
+    }
+
+    pub fn add(&mut self, other: &AgingBuckets) {
+        self.current += other.current;
+        self.days_30 += other.days_30;
+        self.days_60 += other.days_60;
+        self.days_90 += other.days_90;
+        self.days_120_plus += other.days_120_plus;
+        self.total += other.total;
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ReceivableAgingRow {
+    #[serde(rename = "clientId")]
+    pub client_id: ObjectId,
+    #[serde(rename = "clientName")]
+    pub client_name: String,
+    #[serde(rename = "invoiceCount")]
+    pub invoice_count: i64, // Invoices with an open balance
+    pub buckets: AgingBuckets,
+}
+
+/// Receivables still open on `asOf`, one row per client, largest balance first.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ReceivableAgingReport {
+    #[serde(rename = "asOf")]
+    pub as_of: DateTime,
+    pub rows: Vec<ReceivableAgingRow>,
+    pub totals: AgingBuckets,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PayableAgingRow {
+    #[serde(rename = "supplierId")]
+    pub supplier_id: ObjectId,
+    #[serde(rename = "supplierName")]
+    pub supplier_name: String,
+    #[serde(rename = "billCount")]
+    pub bill_count: i64, // Bills with an open balance
+    pub buckets: AgingBuckets,
+}
+
+/// Supplier bills still open on `asOf`, one row per supplier, largest balance first.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PayableAgingReport {
+    #[serde(rename = "asOf")]
+    pub as_of: DateTime,
+    pub rows: Vec<PayableAgingRow>,
+    pub totals: AgingBuckets,
+}
@@ -0,0 +1,58 @@
+use core::fmt;
+
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum StatementEntryKind {
+    Invoice,    // An invoice was issued
+    Adjustment, // An invoice total was raised after it was issued
+    Credit,     // An invoice total was lowered after it was issued
+    Payment,    // A payment, or a reversal of one when it is a debit
+}
+
+impl fmt::Display for StatementEntryKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StatementEntryKind::Invoice => write!(f, "Invoice"),
+            StatementEntryKind::Adjustment => write!(f, "Adjustment"),
+            StatementEntryKind::Credit => write!(f, "Credit"),
+            StatementEntryKind::Payment => write!(f, "Payment"),
+        }
+    }
+}
+
+/// One movement on a client's account. `balance` is the running balance
+/// after the movement.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StatementLine {
+    pub date: DateTime,
+    pub kind: StatementEntryKind,
+    #[serde(rename = "invoiceId")]
+    pub invoice_id: ObjectId,
+    pub description: String,
+    pub debit: f64,
+    pub credit: f64,
+    pub balance: f64,
+}
+
+/// A client's statement of account for a date range. Payments and credits are
+/// reconstructed from the invoice revision history.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ClientStatement {
+    #[serde(rename = "clientId")]
+    pub client_id: ObjectId,
+    #[serde(rename = "clientName")]
+    pub client_name: String,
+    pub from: Option<DateTime>,
+    pub to: Option<DateTime>,
+    #[serde(rename = "openingBalance")]
+    pub opening_balance: f64,
+    pub lines: Vec<StatementLine>,
+    #[serde(rename = "totalDebits")]
+    pub total_debits: f64,
+    #[serde(rename = "totalCredits")]
+    pub total_credits: f64,
+    #[serde(rename = "closingBalance")]
+    pub closing_balance: f64,
+}
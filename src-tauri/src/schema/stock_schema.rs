@@ -0,0 +1,84 @@
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum StockMovementReason {
+    Sale,       // Taken by an invoice
+    Return,     // Given back when an invoice line was reduced or removed
+    Adjustment, // Counted or corrected by hand
+    Purchase,   // Received from a supplier
+}
+
+/// The kind of document that caused a stock movement.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum StockSourceKind {
+    Invoice,
+    Product,       // The product itself was edited
+    PurchaseOrder, // Received from a supplier
+}
+
+/// One change to a product's stock. `quantity` is positive for stock coming in
+/// and negative for stock going out.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StockMovement {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    #[serde(rename = "productId")]
+    pub product_id: ObjectId,
+    pub quantity: i64,
+    pub reason: StockMovementReason,
+    #[serde(rename = "sourceKind")]
+    pub source_kind: StockSourceKind,
+    #[serde(rename = "sourceId")]
+    pub source_id: ObjectId,
+    pub note: Option<String>,
+    // Date fields
+    pub created_at: DateTime,
+}
+
+impl StockMovement {
+    pub fn new(
+        product_id: ObjectId,
+        quantity: i64,
+        reason: StockMovementReason,
+        source_kind: StockSourceKind,
+        source_id: ObjectId,
+    ) -> Self {
+        Self {
+            id: None,
+            product_id,
+            quantity,
+            reason,
+            source_kind,
+            source_id,
+            note: None,
+            created_at: DateTime::now(),
+        }
+    }
+}
+
+/// A product's stock on a given date, with the movements up to that date.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StockHistory {
+    #[serde(rename = "productId")]
+    pub product_id: ObjectId,
+    #[serde(rename = "asOf")]
+    pub as_of: DateTime,
+    #[serde(rename = "stockOnHand")]
+    pub stock_on_hand: i64,
+    pub movements: Vec<StockMovement>, // Newest first
+}
+
+/// Payload of the `low-stock` event, sent when a sale takes a product down to
+/// its reorder point or below.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LowStockAlert {
+    #[serde(rename = "productId")]
+    pub product_id: ObjectId,
+    pub name: String,
+    pub stock: i64,
+    #[serde(rename = "reorderPoint")]
+    pub reorder_point: i64,
+    #[serde(rename = "reorderQuantity")]
+    pub reorder_quantity: Option<i64>,
+}
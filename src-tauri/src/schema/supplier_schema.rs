@@ -0,0 +1,42 @@
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Supplier {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub name: String,
+    #[serde(rename = "contactName")]
+    pub contact_name: Option<String>,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    pub address: Option<String>,
+    pub city: Option<String>,
+    #[serde(rename = "taxNumber")]
+    pub tax_number: Option<String>,
+    // Financial fields, what we owe the supplier
+    #[serde(default)]
+    pub bills: Vec<ObjectId>,
+    #[serde(rename = "totalOwed", default)]
+    pub total_owed: f64, // Total still owed on the supplier's bills
+    #[serde(rename = "totalPaid", default)]
+    pub total_paid: f64, // Total paid to the supplier
+    #[serde(rename = "outstandingBalance", default)]
+    pub outstanding_balance: f64, // Outstanding balance to be paid
+    // Date fields
+    pub created_at: DateTime,
+    pub updated_at: DateTime,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct NewSupplier {
+    pub name: String,
+    #[serde(rename = "contactName")]
+    pub contact_name: Option<String>,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    pub address: Option<String>,
+    pub city: Option<String>,
+    #[serde(rename = "taxNumber")]
+    pub tax_number: Option<String>,
+}
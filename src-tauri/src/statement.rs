@@ -0,0 +1,378 @@
+use mongodb::bson::DateTime;
+
+use crate::schema::{
+    client_schema::Client,
+    invoice_revision_schema::InvoiceRevision,
+    invoice_schema::Invoice,
+    statement_schema::{ClientStatement, StatementEntryKind, StatementLine},
+};
+
+// Ignore floating point noise when comparing amounts between versions
+const EPSILON: f64 = 1e-9;
+
+/// An invoice together with its saved revisions, oldest first.
+pub struct InvoiceHistory {
+    pub invoice: Invoice,
+    pub revisions: Vec<InvoiceRevision>,
+}
+
+fn entry(
+    date: DateTime,
+    kind: StatementEntryKind,
+    invoice: &Invoice,
+    description: String,
+    amount: f64,
+) -> StatementLine {
+    // Positive amounts raise what the client owes
+    StatementLine {
+        date,
+        kind,
+        invoice_id: invoice.id.unwrap_or_default(),
+        description,
+        debit: amount.max(0.0),
+        credit: (-amount).max(0.0),
+        balance: 0.0,
+    }
+}
+
+/// Lists every movement on one invoice: its issue, then each change to its
+/// total or paid amount, dated when the change was saved.
+fn invoice_entries(history: &InvoiceHistory) -> Vec<StatementLine> {
+    let number = history.invoice.id.map(|id| id.to_hex()).unwrap_or_default();
+
+    // Each revision holds the version replaced at its `created_at`
+    let mut versions: Vec<&Invoice> = history.revisions.iter().map(|r| &r.invoice).collect();
+    versions.push(&history.invoice);
+    let original = versions[0];
+
+    let mut entries = vec![entry(
+        original.created_at,
+        StatementEntryKind::Invoice,
+        original,
+        format!("Invoice {}", number),
+        original.total_price,
+    )];
+    if original.total_paid > EPSILON {
+        entries.push(entry(
+            original.created_at,
+            StatementEntryKind::Payment,
+            original,
+            format!("Payment on invoice {}", number),
+            -original.total_paid,
+        ));
+    }
+
+    for (revision, pair) in history.revisions.iter().zip(versions.windows(2)) {
+        let (before, after) = (pair[0], pair[1]);
+        let price_change = after.total_price - before.total_price;
+        if price_change > EPSILON {
+            entries.push(entry(
+                revision.created_at,
+                StatementEntryKind::Adjustment,
+                after,
+                format!("Invoice {} increased", number),
+                price_change,
+            ));
+        } else if price_change < -EPSILON {
+            entries.push(entry(
+                revision.created_at,
+                StatementEntryKind::Credit,
+                after,
+                format!("Credit on invoice {}", number),
+                price_change,
+            ));
+        }
+
+        let paid_change = after.total_paid - before.total_paid;
+        if paid_change.abs() > EPSILON {
+            let description = if paid_change > 0.0 {
+                format!("Payment on invoice {}", number)
+            } else {
+                format!("Payment reversed on invoice {}", number)
+            };
+            entries.push(entry(
+                revision.created_at,
+                StatementEntryKind::Payment,
+                after,
+                description,
+                -paid_change,
+            ));
+        }
+    }
+    entries
+}
+
+/// Builds a statement of account for `client` between `from` and `to`, both
+/// inclusive. Movements before `from` make up the opening balance.
+pub fn build_statement(
+    client: &Client,
+    histories: &[InvoiceHistory],
+    from: Option<DateTime>,
+    to: Option<DateTime>,
+) -> ClientStatement {
+    let mut entries: Vec<StatementLine> = histories.iter().flat_map(invoice_entries).collect();
+    // Stable, so an invoice stays ahead of the payment made when it was issued
+    entries.sort_by_key(|e| e.date);
+
+    let mut opening_balance = 0.0;
+    let mut lines = Vec::new();
+    for line in entries {
+        if from.is_some_and(|from| line.date < from) {
+            opening_balance += line.debit - line.credit;
+        } else if to.is_none_or(|to| line.date <= to) {
+            lines.push(line);
+        }
+    }
+
+    let mut balance = opening_balance;
+    let (mut total_debits, mut total_credits) = (0.0, 0.0);
+    for line in &mut lines {
+        balance += line.debit - line.credit;
+        line.balance = balance;
+        total_debits += line.debit;
+        total_credits += line.credit;
+    }
+
+    let client_name = if client.company_name.trim().is_empty() {
+        client.username.clone()
+    } else {
+        client.company_name.clone()
+    };
+    ClientStatement {
+        client_id: client.id.unwrap_or_default(),
+        client_name,
+        from,
+        to,
+        opening_balance,
+        lines,
+        total_debits,
+        total_credits,
+        closing_balance: balance,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        schema::invoice_schema::Goods,
+        test_fixtures::{client, date, invoice, object_id},
+    };
+
+    fn revision(number: i64, replaced: &Invoice, created_at: DateTime) -> InvoiceRevision {
+        InvoiceRevision {
+            id: None,
+            invoice_id: replaced.id.unwrap(),
+            revision: number,
+            invoice: replaced.clone(),
+            changed_by: None,
+            reason: None,
+            created_at,
+        }
+    }
+
+    /// Line `n` of `quantity` units at `price`; only the invoice total matters here.
+    fn line(n: u8, price: f64, quantity: i64) -> Goods {
+        Goods {
+            line_id: Some(object_id(n)),
+            name: "Office furniture".to_string(),
+            price,
+            quantity,
+            product_id: object_id(10),
+            list_price: Some(price),
+            price_overridden: false,
+            override_reason: None,
+            price_list_id: None,
+        }
+    }
+
+    fn with_totals(invoice: &Invoice, total_price: f64, total_paid: f64) -> Invoice {
+        Invoice {
+            total_price,
+            total_paid,
+            ..invoice.clone()
+        }
+    }
+
+    /// Invoice 21 is issued in January, paid in part in January and raised in
+    /// February. Invoice 22 is issued and paid in part in February, invoice
+    /// 23 in March.
+    fn histories() -> Vec<InvoiceHistory> {
+        let issued = invoice(21, vec![line(31, 150.0, 2)], 0.0, date(2024, 1, 10));
+        let paid = with_totals(&issued, 300.0, 100.0);
+        let raised = with_totals(&issued, 350.0, 100.0);
+
+        vec![
+            InvoiceHistory {
+                revisions: vec![
+                    revision(1, &issued, date(2024, 1, 20)),
+                    revision(2, &paid, date(2024, 2, 15)),
+                ],
+                invoice: raised,
+            },
+            InvoiceHistory {
+                invoice: invoice(22, vec![line(32, 200.0, 1)], 50.0, date(2024, 2, 5)),
+                revisions: Vec::new(),
+            },
+            InvoiceHistory {
+                invoice: invoice(23, vec![line(33, 150.0, 1)], 0.0, date(2024, 3, 10)),
+                revisions: Vec::new(),
+            },
+        ]
+    }
+
+    fn summary(statement: &ClientStatement) -> Vec<(DateTime, StatementEntryKind, f64, f64, f64)> {
+        statement
+            .lines
+            .iter()
+            .map(|l| (l.date, l.kind, l.debit, l.credit, l.balance))
+            .collect()
+    }
+
+    #[test]
+    fn opening_balance_covers_movements_before_the_period() {
+        let statement = build_statement(
+            &client(),
+            &histories(),
+            Some(date(2024, 2, 1)),
+            Some(date(2024, 2, 29)),
+        );
+
+        assert_eq!(statement.client_id, object_id(1));
+        assert_eq!(statement.client_name, "Nile Traders");
+        // 300 invoiced and 100 paid in January
+        assert_eq!(statement.opening_balance, 200.0);
+        assert_eq!(
+            summary(&statement),
+            [
+                (
+                    date(2024, 2, 5),
+                    StatementEntryKind::Invoice,
+                    200.0,
+                    0.0,
+                    400.0
+                ),
+                (
+                    date(2024, 2, 5),
+                    StatementEntryKind::Payment,
+                    0.0,
+                    50.0,
+                    350.0
+                ),
+                (
+                    date(2024, 2, 15),
+                    StatementEntryKind::Adjustment,
+                    50.0,
+                    0.0,
+                    400.0
+                ),
+            ]
+        );
+        assert_eq!(statement.total_debits, 250.0);
+        assert_eq!(statement.total_credits, 50.0);
+        assert_eq!(statement.closing_balance, 400.0);
+    }
+
+    #[test]
+    fn open_range_lists_every_movement() {
+        let statement = build_statement(&client(), &histories(), None, None);
+
+        assert_eq!(statement.opening_balance, 0.0);
+        assert_eq!(statement.lines.len(), 6);
+        assert_eq!(
+            statement.lines[0].description,
+            format!("Invoice {}", object_id(21))
+        );
+        assert_eq!(
+            statement.lines[1].description,
+            format!("Payment on invoice {}", object_id(21))
+        );
+        // Everything invoiced less everything paid
+        assert_eq!(
+            statement.closing_balance,
+            350.0 + 200.0 + 150.0 - 100.0 - 50.0
+        );
+        assert_eq!(
+            statement.closing_balance,
+            statement.total_debits - statement.total_credits
+        );
+    }
+
+    #[test]
+    fn period_bounds_are_inclusive() {
+        let statement = build_statement(
+            &client(),
+            &histories(),
+            Some(date(2024, 2, 5)),
+            Some(date(2024, 2, 5)),
+        );
+        assert_eq!(statement.opening_balance, 200.0);
+        assert_eq!(statement.lines.len(), 2);
+        assert_eq!(statement.closing_balance, 350.0);
+
+        let statement = build_statement(&client(), &histories(), None, Some(date(2024, 1, 31)));
+        assert_eq!(statement.lines.len(), 2);
+        assert_eq!(statement.closing_balance, 200.0);
+    }
+
+    #[test]
+    fn lowered_totals_and_reversed_payments() {
+        let issued = invoice(24, vec![line(34, 500.0, 1)], 200.0, date(2024, 4, 1));
+        let current = with_totals(&issued, 450.0, 150.0);
+        let histories = [InvoiceHistory {
+            revisions: vec![revision(1, &issued, date(2024, 4, 8))],
+            invoice: current,
+        }];
+
+        let statement = build_statement(&client(), &histories, None, None);
+        assert_eq!(
+            summary(&statement),
+            [
+                (
+                    date(2024, 4, 1),
+                    StatementEntryKind::Invoice,
+                    500.0,
+                    0.0,
+                    500.0
+                ),
+                (
+                    date(2024, 4, 1),
+                    StatementEntryKind::Payment,
+                    0.0,
+                    200.0,
+                    300.0
+                ),
+                (
+                    date(2024, 4, 8),
+                    StatementEntryKind::Credit,
+                    0.0,
+                    50.0,
+                    250.0
+                ),
+                (
+                    date(2024, 4, 8),
+                    StatementEntryKind::Payment,
+                    50.0,
+                    0.0,
+                    300.0
+                ),
+            ]
+        );
+        assert_eq!(
+            statement.lines[3].description,
+            format!("Payment reversed on invoice {}", object_id(24))
+        );
+    }
+
+    #[test]
+    fn names_client_without_company_by_username() {
+        let client = Client {
+            company_name: " ".to_string(),
+            ..client()
+        };
+        let statement = build_statement(&client, &[], None, None);
+        assert_eq!(statement.client_name, "Mona Adel");
+        assert!(statement.lines.is_empty());
+        assert_eq!(statement.closing_balance, 0.0);
+    }
+}
@@ -0,0 +1,130 @@
+//! Fixed sample records shared by the unit tests.
+
+use mongodb::bson::{oid::ObjectId, DateTime};
+
+use crate::schema::{
+    client_schema::{Client, ClinetStatus, NewClient},
+    company_schema::Company,
+    invoice_schema::{Goods, Invoice, Status},
+    product_schema::Product,
+};
+
+/// Midnight UTC on the given day.
+pub fn date(year: i32, month: u8, day: u8) -> DateTime {
+    DateTime::builder()
+        .year(year)
+        .month(month)
+        .day(day)
+        .build()
+        .unwrap()
+}
+
+/// A fixed ID, so generated documents are the same on every run.
+pub fn object_id(n: u8) -> ObjectId {
+    ObjectId::from_bytes([n; 12])
+}
+
+pub fn new_client() -> NewClient {
+    NewClient {
+        id: None,
+        username: "Mona Adel".to_string(),
+        email: Some("mona@nile-traders.example".to_string()),
+        phone: "+20 100 123 4567".to_string(),
+        company_name: "Nile Traders".to_string(),
+        city: "Cairo".to_string(),
+        address: "12 Tahrir Street".to_string(),
+        contacts: Vec::new(),
+        addresses: Vec::new(),
+        tax_number: Some("200-300-400".to_string()),
+        tags: Vec::new(),
+        group_id: None,
+        price_list_id: None,
+        credit_limit: None,
+    }
+}
+
+pub fn client() -> Client {
+    let new_client = new_client();
+    Client {
+        id: Some(object_id(1)),
+        username: new_client.username,
+        email: new_client.email,
+        phone: new_client.phone,
+        company_name: new_client.company_name,
+        city: new_client.city,
+        address: new_client.address,
+        contacts: Vec::new(),
+        addresses: Vec::new(),
+        tax_number: new_client.tax_number,
+        tags: Vec::new(),
+        group_id: None,
+        price_list_id: None,
+        status: ClinetStatus::Active,
+        invoices: Vec::new(),
+        total_owed: 0.0,
+        total_paid: 0.0,
+        outstanding_balance: 0.0,
+        credit_limit: None,
+        merged_into: None,
+        deactivated_at: None,
+        deactivation_reason: None,
+        anonymized_at: None,
+        created_at: date(2024, 1, 1),
+        updated_at: date(2024, 1, 1),
+    }
+}
+
+pub fn company() -> Company {
+    Company {
+        id: None,
+        name: "Delta Supplies".to_string(),
+        address: "5 Corniche Road".to_string(),
+        city: "Alexandria".to_string(),
+        country: Some("EG".to_string()),
+        phone: "+20 3 555 0100".to_string(),
+        email: Some("billing@delta.example".to_string()),
+        tax_number: Some("100-200-300".to_string()),
+        currency: "EGP".to_string(),
+        lock_price_overrides: false,
+        eta: None,
+        updated_at: date(2024, 1, 1),
+    }
+}
+
+pub fn product(n: u8, name: &str, price: f64) -> Product {
+    Product {
+        id: Some(object_id(n)),
+        name: name.to_string(),
+        discription: None,
+        price,
+        cost: None,
+        stock: 100,
+        item_code: Some(format!("EG-100-{}", n)),
+        reorder_point: None,
+        reorder_quantity: None,
+        created_at: date(2024, 1, 1),
+        updated_at: date(2024, 1, 1),
+    }
+}
+
+/// An invoice for `goods` issued on `created_at`, with `total_paid` paid.
+pub fn invoice(n: u8, goods: Vec<Goods>, total_paid: f64, created_at: DateTime) -> Invoice {
+    let total_price = goods.iter().map(|g| g.price * g.quantity as f64).sum();
+    Invoice {
+        id: Some(object_id(n)),
+        client_id: object_id(1),
+        goods,
+        total_paid,
+        status: if total_paid >= total_price {
+            Status::Paid
+        } else if total_paid > 0.0 {
+            Status::PartialPaid
+        } else {
+            Status::UnPaid
+        },
+        total_price,
+        billing_address: None,
+        created_at,
+        updated_at: created_at,
+    }
+}
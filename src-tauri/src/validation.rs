@@ -0,0 +1,386 @@
+use crate::schema::{
+    client_schema::{Client, ClientMatch, ContactRole, MatchReason, NewClient},
+    error::FieldError,
+    supplier_schema::NewSupplier,
+};
+
+// E.164 numbers have at most 15 digits; shorter than 7 cannot be dialled
+const MIN_PHONE_DIGITS: usize = 7;
+const MAX_PHONE_DIGITS: usize = 15;
+
+// Numbers are compared on their last digits, so a local number matches the
+// same number written with a country code, e.g. `0100 123 4567` and `+20 100 123 4567`
+const PHONE_MATCH_DIGITS: usize = 10;
+
+fn field_error(field: &str, message: &str) -> FieldError {
+    FieldError {
+        field: field.to_string(),
+        message: message.to_string(),
+    }
+}
+
+/// Digits of a phone number, keeping only the trailing digits used for matching.
+pub fn normalize_phone(phone: &str) -> String {
+    let digits: Vec<char> = phone.chars().filter(|c| c.is_ascii_digit()).collect();
+    let start = digits.len().saturating_sub(PHONE_MATCH_DIGITS);
+    digits[start..].iter().collect()
+}
+
+pub fn normalize_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+/// Lowercases a name and collapses runs of whitespace.
+pub fn normalize_name(name: &str) -> String {
+    name.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+fn is_valid_email(email: &str) -> bool {
+    let Some((local, domain)) = email.split_once('@') else {
+        return false;
+    };
+    !local.is_empty()
+        && !domain.contains('@')
+        && domain.contains('.')
+        && !domain.starts_with('.')
+        && !domain.ends_with('.')
+        && !email.chars().any(char::is_whitespace)
+}
+
+fn phone_error(phone: &str) -> Option<&'static str> {
+    let phone = phone.trim();
+    if phone.is_empty() {
+        return Some("Phone number is required");
+    }
+    let allowed = |c: char| c.is_ascii_digit() || " +-().".contains(c);
+    if !phone.chars().all(allowed) || phone.chars().skip(1).any(|c| c == '+') {
+        return Some("Phone number may only contain digits, spaces, '+', '-', '(', ')' and '.'");
+    }
+    let digits = phone.chars().filter(|c| c.is_ascii_digit()).count();
+    if !(MIN_PHONE_DIGITS..=MAX_PHONE_DIGITS).contains(&digits) {
+        return Some("Phone number must have between 7 and 15 digits");
+    }
+    None
+}
+
+/// Checks the fields of a client, returning every problem found.
+pub fn validate_client(client: &NewClient) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+
+    if client.username.trim().is_empty() {
+        errors.push(field_error("username", "Name is required"));
+    }
+    if let Some(email) = client.email.as_deref().filter(|e| !e.trim().is_empty()) {
+        if !is_valid_email(email.trim()) {
+            errors.push(field_error("email", "Email address is not valid"));
+        }
+    }
+    if let Some(message) = phone_error(&client.phone) {
+        errors.push(field_error("phone", message));
+    }
+    for (i, contact) in client.contacts.iter().enumerate() {
+        let field = |name: &str| format!("contacts[{}].{}", i, name);
+        if contact.name.trim().is_empty() {
+            errors.push(field_error(&field("name"), "Contact name is required"));
+        }
+        if let Some(email) = contact.email.as_deref().filter(|e| !e.trim().is_empty()) {
+            if !is_valid_email(email.trim()) {
+                errors.push(field_error(&field("email"), "Email address is not valid"));
+            }
+        }
+        if let Some(message) = contact.phone.as_deref().and_then(phone_error) {
+            errors.push(field_error(&field("phone"), message));
+        }
+    }
+    for (i, address) in client.addresses.iter().enumerate() {
+        if address.address.trim().is_empty() {
+            errors.push(field_error(
+                &format!("addresses[{}].address", i),
+                "Address is required",
+            ));
+        }
+    }
+    for role in [
+        ContactRole::Billing,
+        ContactRole::Delivery,
+        ContactRole::Accounts,
+        ContactRole::General,
+    ] {
+        if client
+            .contacts
+            .iter()
+            .filter(|c| c.role == role && c.is_default)
+            .count()
+            > 1
+        {
+            errors.push(field_error(
+                "contacts",
+                &format!("Only one {:?} contact can be the default", role),
+            ));
+        }
+        if client
+            .addresses
+            .iter()
+            .filter(|a| a.role == role && a.is_default)
+            .count()
+            > 1
+        {
+            errors.push(field_error(
+                "addresses",
+                &format!("Only one {:?} address can be the default", role),
+            ));
+        }
+    }
+    if client.tags.iter().any(|tag| tag.trim().is_empty()) {
+        errors.push(field_error("tags", "Tags cannot be empty"));
+    }
+    if let Some(limit) = client.credit_limit {
+        if !limit.is_finite() || limit < 0.0 {
+            errors.push(field_error(
+                "creditLimit",
+                "Credit limit must be zero or more",
+            ));
+        }
+    }
+
+    errors
+}
+
+/// Checks the fields of a supplier, returning every problem found.
+pub fn validate_supplier(supplier: &NewSupplier) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+
+    if supplier.name.trim().is_empty() {
+        errors.push(field_error("name", "Name is required"));
+    }
+    if let Some(email) = supplier.email.as_deref().filter(|e| !e.trim().is_empty()) {
+        if !is_valid_email(email.trim()) {
+            errors.push(field_error("email", "Email address is not valid"));
+        }
+    }
+    if let Some(message) = supplier
+        .phone
+        .as_deref()
+        .filter(|p| !p.trim().is_empty())
+        .and_then(phone_error)
+    {
+        errors.push(field_error("phone", message));
+    }
+
+    errors
+}
+
+/// Finds existing clients sharing a normalized phone number, email address or
+/// company name with `client`.
+pub fn find_matches(client: &NewClient, existing: &[Client]) -> Vec<ClientMatch> {
+    let phone = normalize_phone(&client.phone);
+    let email = client
+        .email
+        .as_deref()
+        .map(normalize_email)
+        .filter(|e| !e.is_empty());
+    let company = normalize_name(&client.company_name);
+
+    existing
+        .iter()
+        .filter_map(|other| {
+            let mut reasons = Vec::new();
+            if phone.len() >= MIN_PHONE_DIGITS && normalize_phone(&other.phone) == phone {
+                reasons.push(MatchReason::Phone);
+            }
+            if email.is_some() && other.email.as_deref().map(normalize_email) == email {
+                reasons.push(MatchReason::Email);
+            }
+            if !company.is_empty() && normalize_name(&other.company_name) == company {
+                reasons.push(MatchReason::CompanyName);
+            }
+            if reasons.is_empty() {
+                return None;
+            }
+            Some(ClientMatch {
+                client_id: other.id?,
+                username: other.username.clone(),
+                company_name: other.company_name.clone(),
+                phone: other.phone.clone(),
+                email: other.email.clone(),
+                reasons,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        schema::client_schema::ClientContact,
+        test_fixtures::{client, new_client, object_id},
+    };
+
+    fn with_phone(phone: &str) -> NewClient {
+        NewClient {
+            phone: phone.to_string(),
+            ..new_client()
+        }
+    }
+
+    fn error_fields(client: &NewClient) -> Vec<String> {
+        validate_client(client)
+            .into_iter()
+            .map(|error| error.field)
+            .collect()
+    }
+
+    fn contact(name: &str, role: ContactRole, is_default: bool) -> ClientContact {
+        ClientContact {
+            name: name.to_string(),
+            role,
+            email: None,
+            phone: None,
+            is_default,
+        }
+    }
+
+    #[test]
+    fn accepts_valid_client() {
+        assert!(validate_client(&new_client()).is_empty());
+    }
+
+    #[test]
+    fn phone_needs_between_7_and_15_digits() {
+        assert_eq!(error_fields(&with_phone("123 456")), ["phone"]);
+        assert!(error_fields(&with_phone("123 4567")).is_empty());
+        assert!(error_fields(&with_phone("+123 456 789 012 345")).is_empty());
+        assert_eq!(
+            error_fields(&with_phone("+123 456 789 012 3456")),
+            ["phone"]
+        );
+        assert_eq!(error_fields(&with_phone("  ")), ["phone"]);
+    }
+
+    #[test]
+    fn phone_allows_plus_only_in_front() {
+        assert!(error_fields(&with_phone("+20 (100) 123-4567")).is_empty());
+        assert_eq!(error_fields(&with_phone("20 100+123 4567")), ["phone"]);
+        assert_eq!(error_fields(&with_phone("++20 100 123 4567")), ["phone"]);
+        assert_eq!(error_fields(&with_phone("0100 123 4567 ext 2")), ["phone"]);
+    }
+
+    #[test]
+    fn checks_each_contact() {
+        let client = NewClient {
+            contacts: vec![ClientContact {
+                email: Some("accounts@".to_string()),
+                phone: Some("12".to_string()),
+                ..contact(" ", ContactRole::Accounts, false)
+            }],
+            ..new_client()
+        };
+        assert_eq!(
+            error_fields(&client),
+            ["contacts[0].name", "contacts[0].email", "contacts[0].phone"]
+        );
+    }
+
+    #[test]
+    fn only_one_default_per_role() {
+        let mut client = NewClient {
+            contacts: vec![
+                contact("Mona", ContactRole::Billing, true),
+                contact("Karim", ContactRole::Billing, false),
+                contact("Sara", ContactRole::Delivery, true),
+            ],
+            ..new_client()
+        };
+        assert!(validate_client(&client).is_empty());
+
+        client.contacts[1].is_default = true;
+        let errors = validate_client(&client);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].field, "contacts");
+        assert_eq!(
+            errors[0].message,
+            "Only one Billing contact can be the default"
+        );
+    }
+
+    #[test]
+    fn rejects_negative_credit_limit() {
+        let client = NewClient {
+            credit_limit: Some(-1.0),
+            ..new_client()
+        };
+        assert_eq!(error_fields(&client), ["creditLimit"]);
+    }
+
+    #[test]
+    fn normalizes_phone_to_last_ten_digits() {
+        assert_eq!(normalize_phone("+20 100 123 4567"), "1001234567");
+        assert_eq!(normalize_phone("0100-123-4567"), "1001234567");
+        assert_eq!(normalize_phone("(02) 555"), "02555");
+    }
+
+    #[test]
+    fn matches_local_number_with_country_code() {
+        let existing = [client()];
+        let candidate = NewClient {
+            email: None,
+            company_name: "Cairo Traders".to_string(),
+            ..with_phone("0100 123 4567")
+        };
+
+        let matches = find_matches(&candidate, &existing);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].client_id, object_id(1));
+        assert_eq!(matches[0].reasons, [MatchReason::Phone]);
+    }
+
+    #[test]
+    fn matches_email_and_company_ignoring_case_and_spacing() {
+        let existing = [client()];
+        let candidate = NewClient {
+            email: Some(" MONA@Nile-Traders.example ".to_string()),
+            company_name: "nile   TRADERS".to_string(),
+            ..with_phone("0122 999 8888")
+        };
+
+        let matches = find_matches(&candidate, &existing);
+        assert_eq!(
+            matches[0].reasons,
+            [MatchReason::Email, MatchReason::CompanyName]
+        );
+    }
+
+    #[test]
+    fn ignores_short_and_different_numbers() {
+        let existing = [Client {
+            phone: "555 1234".to_string(),
+            ..client()
+        }];
+        let candidate = NewClient {
+            email: None,
+            company_name: String::new(),
+            ..with_phone("555-1234")
+        };
+        // Both have 7 digits, enough to compare
+        assert_eq!(find_matches(&candidate, &existing).len(), 1);
+
+        let candidate = NewClient {
+            email: None,
+            company_name: String::new(),
+            ..with_phone("0100 765 4321")
+        };
+        assert!(find_matches(&candidate, &existing).is_empty());
+
+        let candidate = NewClient {
+            email: None,
+            company_name: String::new(),
+            phone: String::new(),
+            ..new_client()
+        };
+        assert!(find_matches(&candidate, &existing).is_empty());
+    }
+}
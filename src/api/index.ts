@@ -108,10 +108,16 @@ export async function updateCLientById(
   }
 }
 
-export async function deactivateClientById(clientId: string): Promise<Client> {
+export async function deactivateClientById(
+  clientId: string,
+  reason: string,
+  force?: boolean
+): Promise<Client> {
   try {
     const updatedClient = await invoke<Client>("deactive_client", {
       clientId,
+      reason,
+      force,
     });
     return updatedClient;
   } catch (error: any) {
@@ -90,7 +90,8 @@ export const useDeactivateClient = (clientId: string) => {
   const queryClient = useQueryClient();
 
   return useMutation({
-    mutationFn: () => deactivateClientById(clientId),
+    mutationFn: ({ reason, force }: { reason: string; force?: boolean }) =>
+      deactivateClientById(clientId, reason, force),
     onSuccess: () => {
       queryClient.invalidateQueries({
         queryKey: [QUERY_KEYS.GET_CLIENT_BY_ID, clientId],
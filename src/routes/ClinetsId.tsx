@@ -108,7 +108,12 @@ export default function ClientDetails() {
           {client.status === "Active" ? (
             <Button
               disabled={deactivateClientMutation.isPending}
-              onClick={() => deactivateClientMutation.mutate()}
+              onClick={() => {
+                const reason = window.prompt("Reason for deactivating");
+                if (reason?.trim()) {
+                  deactivateClientMutation.mutate({ reason });
+                }
+              }}
               className="bg-red-500"
             >
               Deactivate
@@ -21,6 +21,8 @@ export type Client = {
   totalOwed?: number | null;
   totalPaid?: number | null;
   outstandingBalance?: number | null;
+  deactivatedAt?: { $date: { $numberLong: string } } | null;
+  deactivationReason?: string | null;
   // dates
   created_at: { $date: { $numberLong: string } };
   updated_at: { $date: { $numberLong: string } };
@@ -31,7 +33,10 @@ export type Product = {
   discription?: string | null;
   tags: string[];
   price: number;
+  cost?: number | null;
   stock: number;
+  reorderPoint?: number | null;
+  reorderQuantity?: number | null;
   // dates
   created_at: { $date: { $numberLong: string } };
   updated_at: { $date: { $numberLong: string } };